@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::{Path, PathBuf};
 
 /// Command line interface for preload-ng.
@@ -32,6 +32,121 @@ pub struct Cli {
     /// Increase verbosity (-v, -vv, -vvv).
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// One-shot commands; without one the daemon runs.
+#[derive(Debug, Subcommand, Clone)]
+pub enum Command {
+    /// Show what the next prefetch would read.
+    Plan(PlanArgs),
+    /// Write the learned model to a portable file.
+    Export(ExportArgs),
+    /// Load a model written by `export` into the state database. Refused
+    /// while the daemon is running on the same database.
+    Import(ImportArgs),
+    /// Combine two state databases into a new one.
+    Merge(MergeArgs),
+    /// Query the usage history kept with `[persistence.history]`.
+    History(HistoryArgs),
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct PlanArgs {
+    /// Print the plan without prefetching anything.
+    #[arg(long, required = true)]
+    pub dry_run: bool,
+
+    /// Plan from the persisted state only, without scanning processes.
+    #[arg(long)]
+    pub no_scan: bool,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct ExportArgs {
+    /// Destination file ("-" for stdout).
+    #[arg(value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// Encoding of the exported model.
+    #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct ImportArgs {
+    /// File written by `export` ("-" for stdin). JSON and CBOR are detected
+    /// automatically.
+    #[arg(value_name = "FILE")]
+    pub input: PathBuf,
+
+    /// Discard the existing model instead of merging the import into it.
+    #[arg(long)]
+    pub replace: bool,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct MergeArgs {
+    /// State databases to merge.
+    #[arg(value_name = "DB", num_args = 2, required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// New database to write the merged model to.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// Replace the output database if it already exists.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Halve an exe's running time for every SECONDS of model time since it
+    /// was last seen (0 sums running times as is).
+    #[arg(long, value_name = "SECONDS", default_value_t = 0)]
+    pub half_life: u64,
+}
+
+#[derive(Debug, Args, Clone)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub query: HistoryQuery,
+
+    /// Only consider the last DAYS days.
+    #[arg(long, value_name = "DAYS", default_value_t = 7, global = true)]
+    pub days: u64,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum HistoryQuery {
+    /// Apps launched most often, and how many launches were prefetched for.
+    Apps {
+        /// Number of apps to list.
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Launches and bytes prefetched per day.
+    Days,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Cbor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
 }
 
 impl Cli {
@@ -0,0 +1,111 @@
+use crate::cli::{HistoryArgs, HistoryQuery, OutputFormat};
+use anyhow::Context;
+use config::{Config, PersistenceBackend};
+use orchestrator::persistence::{AppUsage, DayUsage, SqliteRepository};
+use std::io::Write;
+use std::time::SystemTime;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Answer a usage history query from the state database.
+pub async fn run(config: &Config, args: &HistoryArgs) -> anyhow::Result<()> {
+    if config.persistence.backend != PersistenceBackend::Sqlite {
+        anyhow::bail!("usage history needs the sqlite persistence backend");
+    }
+    let path = config
+        .persistence
+        .state_path
+        .as_ref()
+        .context("usage history needs a state database")?;
+    if !path.exists() {
+        anyhow::bail!("no state database at {}", path.display());
+    }
+    // Only read the database: a query must not migrate, recover or
+    // quarantine the daemon's state.
+    let repo = SqliteRepository::open_read_only(path).await?;
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    let since = now.saturating_sub(args.days.saturating_mul(SECONDS_PER_DAY));
+    let mut out = std::io::stdout().lock();
+    match args.query {
+        HistoryQuery::Apps { limit } => {
+            let apps = repo.usage_by_app(since, limit).await?;
+            match args.format {
+                OutputFormat::Table => print_apps(&mut out, &apps)?,
+                OutputFormat::Json => serde_json::to_writer_pretty(&mut out, &apps)?,
+            }
+        }
+        HistoryQuery::Days => {
+            let days = repo.usage_by_day(since).await?;
+            match args.format {
+                OutputFormat::Table => print_days(&mut out, &days)?,
+                OutputFormat::Json => serde_json::to_writer_pretty(&mut out, &days)?,
+            }
+        }
+    }
+    if args.format == OutputFormat::Json {
+        writeln!(out)?;
+    }
+    repo.close().await;
+    Ok(())
+}
+
+fn print_apps(out: &mut impl Write, apps: &[AppUsage]) -> std::io::Result<()> {
+    writeln!(out, "{:>8} {:>10}  PATH", "LAUNCHES", "PREFETCHED")?;
+    for app in apps {
+        writeln!(
+            out,
+            "{:>8} {:>10}  {}",
+            app.launches,
+            app.predicted,
+            app.exe_path.display()
+        )?;
+    }
+    Ok(())
+}
+
+fn print_days(out: &mut impl Write, days: &[DayUsage]) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "{:>10} {:>7} {:>8} {:>10} {:>14}",
+        "DAY", "TICKS", "LAUNCHES", "PREFETCHED", "PREFETCHED_KB"
+    )?;
+    for day in days {
+        writeln!(
+            out,
+            "{:>10} {:>7} {:>8} {:>10} {:>14}",
+            civil_date(day.day / SECONDS_PER_DAY),
+            day.ticks,
+            day.launches,
+            day.predicted,
+            day.prefetched_bytes / 1024,
+        )?;
+    }
+    if !days.is_empty() {
+        let total: u64 = days.iter().map(|day| day.prefetched_bytes).sum();
+        writeln!(
+            out,
+            "{} days, {} KB prefetched per day on average",
+            days.len(),
+            total / days.len() as u64 / 1024,
+        )?;
+    }
+    Ok(())
+}
+
+/// Format days since the Unix epoch as a UTC `YYYY-MM-DD` date, using
+/// Howard Hinnant's `civil_from_days`.
+fn civil_date(days: u64) -> String {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
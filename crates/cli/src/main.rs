@@ -1,23 +1,35 @@
 #![deny(unsafe_code)]
 
 mod cli;
+mod history;
+mod plan;
+mod portable;
 mod priority;
 mod signals;
+mod state_lock;
 
+use anyhow::Context;
 use clap::Parser;
-use cli::Cli;
-use config::Config;
+use cli::{Cli, Command};
+use config::{Config, PersistenceBackend, PowerSource};
 use orchestrator::{
     ControlEvent, PreloadEngine, ReloadBundle, Services,
     clock::SystemClock,
-    observation::{DefaultAdmissionPolicy, DefaultModelUpdater, FanotifyWatcher, ProcfsScanner},
-    persistence::{NoopRepository, SqliteRepository},
-    prediction::MarkovPredictor,
+    observation::{
+        DefaultAdmissionPolicy, DefaultModelUpdater, FanotifyWatcher, ProcfsScanner,
+        read_power_source,
+    },
+    persistence::{NoopRepository, RkyvFileRepository, SqliteRepository},
+    prediction::{
+        CompositePredictor, FrequencyPredictor, MarkovPredictor, Predictor, SequencePredictor,
+        TimeOfDayPredictor,
+    },
     prefetch::{
-        GreedyPrefetchPlanner, MadvisePrefetcher, NoopPrefetcher, Prefetcher, ReadPrefetcher,
-        ReadaheadPrefetcher,
+        DensityPrefetchPlanner, GreedyPrefetchPlanner, MadvisePrefetcher, NoopPrefetcher,
+        PrefetchPlanner, Prefetcher, ReadPrefetcher, ReadaheadPrefetcher, ThrottleSettings,
     },
 };
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
@@ -30,22 +42,59 @@ async fn main() -> anyhow::Result<()> {
     priority::lower_process_priority();
     let config = load_config_from_cli(&cli)?;
 
+    if let Some(Command::Merge(args)) = &cli.command {
+        return portable::merge(&config, args).await;
+    }
+    if let Some(Command::History(args)) = &cli.command {
+        return history::run(&config, args).await;
+    }
+
+    if let Some(Command::Plan(args)) = &cli.command {
+        // A dry run must not migrate, recover or quarantine the state.
+        let repo = open_repo_read_only(&cli, &config)
+            .await?
+            .unwrap_or_else(|| Box::new(NoopRepository));
+        let bundle = build_reload_bundle(config.clone(), true);
+        let services = Services {
+            scanner: Box::new(ProcfsScanner::new(None)),
+            admission: bundle.admission,
+            updater: bundle.updater,
+            predictor: bundle.predictor,
+            planner: bundle.planner,
+            prefetcher: bundle.prefetcher,
+            repo,
+            clock: Box::new(SystemClock),
+        };
+        let mut engine = PreloadEngine::load(config, services).await?;
+        return plan::run(&mut engine, args).await;
+    }
+
+    if let Some(Command::Export(args)) = &cli.command {
+        // Exporting only reads: never migrate, recover or quarantine the
+        // state, and never export an empty model in its place.
+        let Some(repo) = open_repo_read_only(&cli, &config).await? else {
+            anyhow::bail!("export needs a saved state database");
+        };
+        return portable::export(repo.as_ref(), args).await;
+    }
+    let importing = matches!(cli.command, Some(Command::Import(_)));
+    if importing && (cli.no_persist || config.persistence.state_path.is_none()) {
+        anyhow::bail!("import needs a state database");
+    }
+
+    let _state_lock = lock_state(&cli, &config, importing)?;
+    let repo = build_repo(&cli, &config).await?;
+
+    if let Some(Command::Import(args)) = &cli.command {
+        return portable::import(repo.as_ref(), args).await;
+    }
+
     let fanotify = if config.system.fanotify {
         FanotifyWatcher::try_new()
     } else {
         None
     };
 
-    let repo = if cli.no_persist {
-        Box::new(NoopRepository) as Box<dyn orchestrator::persistence::StateRepository>
-    } else if let Some(path) = &config.persistence.state_path {
-        let repo = SqliteRepository::new(path.clone()).await?;
-        Box::new(repo) as Box<dyn orchestrator::persistence::StateRepository>
-    } else {
-        warn!("no persistence path resolved (HOME not set?); using in-memory state only");
-        Box::new(NoopRepository) as Box<dyn orchestrator::persistence::StateRepository>
-    };
-
     let reload_bundle = build_reload_bundle(config.clone(), cli.no_prefetch);
 
     let services = Services {
@@ -59,10 +108,18 @@ async fn main() -> anyhow::Result<()> {
         clock: Box::new(SystemClock),
     };
 
+    let power_monitor = (!config.profile.is_empty()).then(|| {
+        (
+            config.profile.poll_interval,
+            config.active_profile.unwrap_or_default(),
+        )
+    });
+    let metrics_listen = config.metrics.listen;
     let mut engine = PreloadEngine::load(config, services).await?;
 
     if cli.once {
         let report = engine.tick().await?;
+        engine.flush_usage().await;
         info!(?report, "tick completed");
         return Ok(());
     }
@@ -71,8 +128,26 @@ async fn main() -> anyhow::Result<()> {
     signals::install_ctrl_c(cancel.clone());
 
     let (control_tx, control_rx) = mpsc::unbounded_channel();
+    if let Some((interval, source)) = power_monitor {
+        install_power_monitor(cli.clone(), interval, source, control_tx.clone());
+    }
     install_signal_handlers(cli.clone(), control_tx);
 
+    if let Some(addr) = metrics_listen {
+        if !addr.ip().is_loopback() {
+            warn!(%addr, "metrics are served without authentication on a non-loopback address");
+        }
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to listen for metrics on {addr}"))?;
+        info!(%addr, "serving metrics");
+        tokio::spawn(orchestrator::metrics::serve(
+            listener,
+            engine.subscribe_metrics(),
+            cancel.clone(),
+        ));
+    }
+
     engine.run_until(cancel, control_rx).await?;
     Ok(())
 }
@@ -87,7 +162,10 @@ fn init_tracing(verbosity: u8) {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
 
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    tracing_subscriber::fmt()
+        .with_env_filter(env_filter)
+        .with_writer(std::io::stderr)
+        .init();
 }
 
 /// Load configuration files and apply CLI overrides.
@@ -108,6 +186,8 @@ fn load_config_from_cli(cli: &Cli) -> anyhow::Result<Config> {
         config.persistence.state_path = default_state_path();
     }
 
+    config.apply_profile(read_power_source());
+
     Ok(config)
 }
 
@@ -116,24 +196,133 @@ fn default_state_path() -> Option<std::path::PathBuf> {
     let cache_dir = std::env::var_os("XDG_CACHE_HOME")
         .map(std::path::PathBuf::from)
         .filter(|p| p.is_absolute())
-        .or_else(|| {
-            std::env::var_os("HOME").map(|h| std::path::PathBuf::from(h).join(".cache"))
-        })?;
+        .or_else(|| std::env::var_os("HOME").map(|h| std::path::PathBuf::from(h).join(".cache")))?;
     Some(cache_dir.join("preload-ng").join("state.db"))
 }
 
+/// Open the state repository selected by configuration and CLI flags.
+async fn build_repo(
+    cli: &Cli,
+    config: &Config,
+) -> anyhow::Result<Box<dyn orchestrator::persistence::StateRepository>> {
+    let repo = if cli.no_persist {
+        Box::new(NoopRepository) as Box<dyn orchestrator::persistence::StateRepository>
+    } else if let Some(path) = &config.persistence.state_path {
+        let backups = config.persistence.backups;
+        match config.persistence.backend {
+            PersistenceBackend::Sqlite => {
+                let repo = SqliteRepository::new(path.clone())
+                    .await?
+                    .with_backups(backups);
+                Box::new(repo) as Box<dyn orchestrator::persistence::StateRepository>
+            }
+            PersistenceBackend::Rkyv => {
+                if config.persistence.history.enabled {
+                    warn!(
+                        "usage history is kept by the sqlite backend only; \
+                         [persistence.history] is ignored"
+                    );
+                }
+                let repo = RkyvFileRepository::new(path.clone())
+                    .await?
+                    .with_backups(backups);
+                Box::new(repo) as Box<dyn orchestrator::persistence::StateRepository>
+            }
+        }
+    } else {
+        warn!("no persistence path resolved (HOME not set?); using in-memory state only");
+        Box::new(NoopRepository) as Box<dyn orchestrator::persistence::StateRepository>
+    };
+    Ok(repo)
+}
+
+/// Lock the state database for this process, failing when a daemon (or an
+/// import) already uses it. `None` when persistence is disabled.
+fn lock_state(
+    cli: &Cli,
+    config: &Config,
+    importing: bool,
+) -> anyhow::Result<Option<state_lock::StateLock>> {
+    let Some(path) = config
+        .persistence
+        .state_path
+        .as_ref()
+        .filter(|_| !cli.no_persist)
+    else {
+        return Ok(None);
+    };
+    match state_lock::StateLock::try_acquire(path)? {
+        Some(lock) => Ok(Some(lock)),
+        None if importing => anyhow::bail!(
+            "{} is in use by a running daemon; stop it before importing",
+            path.display()
+        ),
+        None => anyhow::bail!("{} is in use by another instance", path.display()),
+    }
+}
+
+/// Open the configured state repository read-only, for commands that only
+/// inspect it. `None` when persistence is disabled or nothing has been
+/// saved yet.
+async fn open_repo_read_only(
+    cli: &Cli,
+    config: &Config,
+) -> anyhow::Result<Option<Box<dyn orchestrator::persistence::StateRepository>>> {
+    let Some(path) = config
+        .persistence
+        .state_path
+        .as_ref()
+        .filter(|_| !cli.no_persist)
+    else {
+        return Ok(None);
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+    let repo = match config.persistence.backend {
+        PersistenceBackend::Sqlite => Box::new(SqliteRepository::open_read_only(path).await?)
+            as Box<dyn orchestrator::persistence::StateRepository>,
+        PersistenceBackend::Rkyv => Box::new(RkyvFileRepository::open_read_only(path).await?)
+            as Box<dyn orchestrator::persistence::StateRepository>,
+    };
+    Ok(Some(repo))
+}
+
 /// Construct runtime services for a new configuration snapshot.
 fn build_reload_bundle(config: Config, no_prefetch: bool) -> ReloadBundle {
     ReloadBundle {
         admission: Box::new(DefaultAdmissionPolicy::new(&config)),
         updater: Box::new(DefaultModelUpdater::new(&config)),
-        predictor: Box::new(MarkovPredictor::new(&config)),
-        planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+        predictor: build_predictor(&config),
+        planner: build_planner(&config),
         prefetcher: build_prefetcher(&config, no_prefetch),
         config,
     }
 }
 
+/// Select the planner implementation based on configuration.
+fn build_planner(config: &Config) -> Box<dyn PrefetchPlanner> {
+    use config::PlannerKind;
+
+    match config.system.planner {
+        PlannerKind::Greedy => Box::new(GreedyPrefetchPlanner::new(config)),
+        PlannerKind::Density => Box::new(DensityPrefetchPlanner::new(config)),
+    }
+}
+
+/// Select the predictor implementation based on configuration.
+fn build_predictor(config: &Config) -> Box<dyn Predictor> {
+    use config::PredictorKind;
+
+    match config.model.predictor {
+        PredictorKind::Markov => Box::new(MarkovPredictor::new(config)),
+        PredictorKind::TimeOfDay => Box::new(TimeOfDayPredictor::new(config)),
+        PredictorKind::Sequence => Box::new(SequencePredictor::new(config)),
+        PredictorKind::Frequency => Box::new(FrequencyPredictor::new(config)),
+        PredictorKind::Composite => Box::new(CompositePredictor::new(config)),
+    }
+}
+
 /// Select the prefetcher implementation based on configuration and CLI flags.
 fn build_prefetcher(config: &Config, no_prefetch: bool) -> Box<dyn Prefetcher> {
     use config::PrefetchBackend;
@@ -150,35 +339,107 @@ fn build_prefetcher(config: &Config, no_prefetch: bool) -> Box<dyn Prefetcher> {
             .unwrap_or(1),
     };
 
+    let throttle = ThrottleSettings::new(&config.system.throttle);
+    let chunk_size = config.system.prefetch_chunk_size;
+
     match config.system.prefetch_backend {
         PrefetchBackend::Readahead => {
             info!(concurrency, backend = "readahead", "prefetcher selected");
-            Box::new(ReadaheadPrefetcher::new(concurrency))
+            Box::new(
+                ReadaheadPrefetcher::new(concurrency)
+                    .with_chunk_size(chunk_size)
+                    .with_throttle(throttle),
+            )
         }
         PrefetchBackend::Madvise => {
             info!(concurrency, backend = "madvise", "prefetcher selected");
-            Box::new(MadvisePrefetcher::new(concurrency))
+            Box::new(
+                MadvisePrefetcher::new(concurrency)
+                    .with_chunk_size(chunk_size)
+                    .with_throttle(throttle),
+            )
         }
         PrefetchBackend::Read => {
             info!(concurrency, backend = "read", "prefetcher selected");
-            Box::new(ReadPrefetcher::new(concurrency))
+            Box::new(
+                ReadPrefetcher::new(concurrency)
+                    .with_chunk_size(chunk_size)
+                    .with_throttle(throttle),
+            )
         }
         PrefetchBackend::Auto => {
             // Probe available backends: readahead → madvise → read
             if ReadaheadPrefetcher::probe() {
-                info!(concurrency, backend = "readahead", "prefetcher auto-selected");
-                Box::new(ReadaheadPrefetcher::new(concurrency))
+                info!(
+                    concurrency,
+                    backend = "readahead",
+                    "prefetcher auto-selected"
+                );
+                Box::new(
+                    ReadaheadPrefetcher::new(concurrency)
+                        .with_chunk_size(chunk_size)
+                        .with_throttle(throttle),
+                )
             } else if MadvisePrefetcher::probe() {
                 info!(concurrency, backend = "madvise", "prefetcher auto-selected");
-                Box::new(MadvisePrefetcher::new(concurrency))
+                Box::new(
+                    MadvisePrefetcher::new(concurrency)
+                        .with_chunk_size(chunk_size)
+                        .with_throttle(throttle),
+                )
             } else {
-                info!(concurrency, backend = "read", "prefetcher auto-selected (fallback)");
-                Box::new(ReadPrefetcher::new(concurrency))
+                info!(
+                    concurrency,
+                    backend = "read",
+                    "prefetcher auto-selected (fallback)"
+                );
+                Box::new(
+                    ReadPrefetcher::new(concurrency)
+                        .with_chunk_size(chunk_size)
+                        .with_throttle(throttle),
+                )
             }
         }
     }
 }
 
+/// Poll the power source and reload with the matching profile whenever it
+/// changes.
+fn install_power_monitor(
+    cli: Cli,
+    interval: Duration,
+    mut current: PowerSource,
+    control_tx: mpsc::UnboundedSender<ControlEvent>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_secs(1)));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            let source = read_power_source();
+            if source == current {
+                continue;
+            }
+            match load_config_from_cli(&cli) {
+                Ok(config) => {
+                    info!(profile = ?config.active_profile, "power source changed");
+                    current = config.active_profile.unwrap_or(source);
+                    let bundle = build_reload_bundle(config, cli.no_prefetch);
+                    if control_tx
+                        .send(ControlEvent::Reload(Box::new(bundle)))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    warn!(?err, "failed to reload config for power profile");
+                }
+            }
+        }
+    });
+}
+
 /// Install signal handlers for runtime control (reload, dump, save).
 fn install_signal_handlers(cli: Cli, control_tx: mpsc::UnboundedSender<ControlEvent>) {
     #[cfg(unix)]
@@ -0,0 +1,56 @@
+use crate::cli::{OutputFormat, PlanArgs};
+use orchestrator::{PlanPreview, PreloadEngine, ProcfsScanner};
+use std::io::Write;
+
+/// Run one observe/predict/plan cycle and print the resulting plan.
+pub async fn run(engine: &mut PreloadEngine, args: &PlanArgs) -> anyhow::Result<()> {
+    let scanned = if args.no_scan {
+        None
+    } else {
+        engine.observe()?.1
+    };
+    let memstat = match scanned {
+        Some(mem) => mem,
+        None => ProcfsScanner::read_memstat()?,
+    };
+    let preview = engine.preview(&memstat).await;
+
+    let mut out = std::io::stdout().lock();
+    match args.format {
+        OutputFormat::Table => print_table(&mut out, &preview)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &preview)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_table(out: &mut impl Write, preview: &PlanPreview) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "{:>4} {:>6} {:>10} {:>10} {:>12}  PATH",
+        "#", "SCORE", "SIZE_KB", "CACHED_KB", "CUMUL_KB"
+    )?;
+    for (index, entry) in preview.entries.iter().enumerate() {
+        writeln!(
+            out,
+            "{:>4} {:>6.3} {:>10} {:>10} {:>12}  {} [{}+{}]",
+            index + 1,
+            entry.score,
+            entry.length.div_ceil(1024),
+            entry.resident_bytes / 1024,
+            entry.cumulative_bytes.div_ceil(1024),
+            entry.path.display(),
+            entry.offset,
+            entry.length,
+        )?;
+    }
+    writeln!(
+        out,
+        "{} maps, {} KB charged of {} KB budget",
+        preview.entries.len(),
+        preview.total_bytes.div_ceil(1024),
+        preview.budget_bytes / 1024,
+    )
+}
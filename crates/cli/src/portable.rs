@@ -0,0 +1,161 @@
+use crate::cli::{ExportArgs, ExportFormat, ImportArgs, MergeArgs};
+use anyhow::Context;
+use config::Config;
+use orchestrator::persistence::{
+    MergeOptions, PortableSnapshot, SqliteRepository, StateRepository, StoresSnapshot,
+    merge_snapshots, overlay_snapshot, upgrade_snapshot,
+};
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Write the persisted model to `args.output`.
+pub async fn export(repo: &dyn StateRepository, args: &ExportArgs) -> anyhow::Result<()> {
+    let snapshot = upgrade_snapshot(repo.load().await?)?;
+    let portable = PortableSnapshot::new(snapshot);
+
+    let mut bytes = Vec::new();
+    match args.format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut bytes, &portable)?;
+            bytes.push(b'\n');
+        }
+        ExportFormat::Cbor => ciborium::into_writer(&portable, &mut bytes)?,
+    }
+    if is_stdio(&args.output) {
+        std::io::stdout().lock().write_all(&bytes)?;
+    } else {
+        std::fs::write(&args.output, &bytes)
+            .with_context(|| format!("writing {}", args.output.display()))?;
+    }
+    info!(
+        exes = portable.snapshot.state.exes.len(),
+        maps = portable.snapshot.state.maps.len(),
+        bytes = bytes.len(),
+        "model exported"
+    );
+    Ok(())
+}
+
+/// Read a model from `args.input` and merge it into, or replace, the
+/// persisted one.
+pub async fn import(repo: &dyn StateRepository, args: &ImportArgs) -> anyhow::Result<()> {
+    let mut bytes = Vec::new();
+    if is_stdio(&args.input) {
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+    } else {
+        bytes = std::fs::read(&args.input)
+            .with_context(|| format!("reading {}", args.input.display()))?;
+    }
+    let portable: PortableSnapshot = if is_json(&bytes) {
+        serde_json::from_slice(&bytes)?
+    } else {
+        ciborium::from_reader(bytes.as_slice())?
+    };
+    let imported = portable.into_snapshot()?;
+
+    let snapshot = if args.replace {
+        imported
+    } else {
+        overlay_snapshot(upgrade_snapshot(repo.load().await?)?, imported)
+    };
+    repo.save(&snapshot).await?;
+    info!(
+        exes = snapshot.state.exes.len(),
+        maps = snapshot.state.maps.len(),
+        replace = args.replace,
+        "model imported"
+    );
+    Ok(())
+}
+
+/// Merge two state databases into `args.output`.
+///
+/// The merged model is written to a fresh database beside the output and
+/// moved into place once complete, so the output never keeps rows or usage
+/// history of an earlier database, and an existing one (only replaced with
+/// `--force`) survives a failed merge.
+pub async fn merge(config: &Config, args: &MergeArgs) -> anyhow::Result<()> {
+    let [first, second] = args.inputs.as_slice() else {
+        anyhow::bail!("merge takes exactly two databases");
+    };
+    if args.output.exists() && !args.force {
+        anyhow::bail!(
+            "{} already exists; pass --force to replace it",
+            args.output.display()
+        );
+    }
+    let first = load_database(first).await?;
+    let second = load_database(second).await?;
+    let options = MergeOptions {
+        half_life: args.half_life,
+        histogram_decay: config.model.time_of_day.decay_factor(),
+    };
+    let merged = merge_snapshots(first, second, &options);
+
+    let staging = sibling(&args.output, ".merge-tmp");
+    remove_database(&staging)?;
+    let repo = SqliteRepository::create(&staging).await?;
+    let saved = repo.save(&merged).await;
+    repo.close().await;
+    if let Err(err) = saved {
+        remove_database(&staging)?;
+        return Err(err.into());
+    }
+    remove_database(&args.output)?;
+    std::fs::rename(&staging, &args.output)
+        .with_context(|| format!("writing {}", args.output.display()))?;
+    // Closing checkpointed the WAL; drop whatever SQLite left beside it.
+    remove_database(&staging)?;
+    info!(
+        exes = merged.state.exes.len(),
+        maps = merged.state.maps.len(),
+        markov_edges = merged.state.markov_edges.len(),
+        output = %args.output.display(),
+        "models merged"
+    );
+    Ok(())
+}
+
+async fn load_database(path: &Path) -> anyhow::Result<StoresSnapshot> {
+    if !path.exists() {
+        anyhow::bail!("state database not found: {}", path.display());
+    }
+    // Merge inputs are only read: never migrate, recover or quarantine
+    // them, and fail rather than merge an empty model in their place.
+    let repo = SqliteRepository::open_read_only(path).await?;
+    let snapshot = repo.load().await;
+    repo.close().await;
+    Ok(upgrade_snapshot(snapshot?)?)
+}
+
+/// `path` with `suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Remove the database at `path` with its WAL and shared-memory files.
+fn remove_database(path: &Path) -> std::io::Result<()> {
+    for suffix in ["", "-wal", "-shm"] {
+        match std::fs::remove_file(sibling(path, suffix)) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            other => other?,
+        }
+    }
+    Ok(())
+}
+
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// JSON exports start with an object; CBOR maps never start with `{`.
+fn is_json(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| *byte == b'{')
+}
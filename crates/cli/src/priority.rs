@@ -49,12 +49,10 @@ fn set_nice(inc: i32) {
 /// IDLE means prefetch I/O only runs when no other process needs disk,
 /// eliminating contention with foreground applications.
 fn set_ionice_idle() {
-    let ioprio = (IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) | 0;
+    let ioprio = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
 
     #[allow(unsafe_code)]
-    let ret = unsafe {
-        libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio)
-    };
+    let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
 
     if ret < 0 {
         let err = std::io::Error::last_os_error();
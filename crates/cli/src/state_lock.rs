@@ -0,0 +1,46 @@
+#![deny(unsafe_code)]
+
+//! Exclusive use of the state database.
+//!
+//! A running engine keeps the model in memory and saves only what changed
+//! since its last save, so a database rewritten underneath it (by
+//! `import`, say) is left inconsistent by its next save. The daemon and
+//! commands that rewrite the state hold an advisory lock on
+//! `<state>.lock` while they use it.
+
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+/// Held for as long as the state database is in use; released on drop.
+#[derive(Debug)]
+pub struct StateLock {
+    _file: File,
+}
+
+impl StateLock {
+    /// Lock the state database at `state_path`, or `None` when another
+    /// process holds the lock.
+    pub fn try_acquire(state_path: &Path) -> anyhow::Result<Option<Self>> {
+        let path = lock_path(state_path);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(err)) => Err(err.into()),
+        }
+    }
+}
+
+fn lock_path(state_path: &Path) -> PathBuf {
+    let mut name = OsString::from(state_path.as_os_str());
+    name.push(".lock");
+    PathBuf::from(name)
+}
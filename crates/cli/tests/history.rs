@@ -0,0 +1,89 @@
+#![forbid(unsafe_code)]
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::tempdir;
+
+fn cli(dir: &Path, args: &[&str]) -> io::Result<Output> {
+    let config_path = dir.join("config.toml");
+    fs::write(
+        &config_path,
+        "[model]\ncycle = 1\n\n[persistence.history]\nenabled = true\n",
+    )?;
+    Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--state")
+        .arg(dir.join("state.db"))
+        .args(args)
+        .output()
+}
+
+#[test]
+fn ticks_are_recorded_and_queryable() -> io::Result<()> {
+    let dir = tempdir()?;
+    let output = cli(dir.path(), &["--once", "--no-prefetch"])?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = cli(dir.path(), &["history", "days", "--format", "json"])?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let days: serde_json::Value = serde_json::from_slice(&output.stdout).expect("stdout is JSON");
+    let days = days.as_array().expect("a list of days");
+    assert_eq!(days.len(), 1);
+    assert!(days[0]["ticks"].as_u64() >= Some(1));
+
+    let output = cli(dir.path(), &["history", "days"])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let day = stdout
+        .lines()
+        .nth(1)
+        .and_then(|row| row.split_whitespace().next())
+        .expect("a day row");
+    let parts: Vec<&str> = day.split('-').collect();
+    assert_eq!(
+        parts.iter().map(|part| part.len()).collect::<Vec<_>>(),
+        vec![4, 2, 2],
+        "{day}"
+    );
+    assert!(parts[0].parse::<u32>().unwrap() >= 2024, "{day}");
+
+    let output = cli(dir.path(), &["history", "apps"])?;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("LAUNCHES"));
+    Ok(())
+}
+
+#[test]
+fn history_leaves_a_corrupt_database_alone() -> io::Result<()> {
+    let dir = tempdir()?;
+    fs::write(dir.path().join("state.db"), b"definitely not a database")?;
+    let output = cli(dir.path(), &["history", "apps"])?;
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read(dir.path().join("state.db"))?,
+        b"definitely not a database"
+    );
+    assert!(!dir.path().join("state.db.corrupt").exists());
+    Ok(())
+}
+
+#[test]
+fn history_needs_an_existing_database() -> io::Result<()> {
+    let dir = tempdir()?;
+    let output = cli(dir.path(), &["history", "apps"])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no state database"));
+    assert!(!dir.path().join("state.db").exists());
+    Ok(())
+}
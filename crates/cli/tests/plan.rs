@@ -0,0 +1,68 @@
+#![forbid(unsafe_code)]
+
+use std::fs;
+use std::io;
+use std::process::Command;
+use tempfile::tempdir;
+
+#[test]
+fn plan_dry_run_prints_json_plan() -> io::Result<()> {
+    let dir = tempdir()?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, "[model]\ncycle = 1\n")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--state")
+        .arg(dir.path().join("state.db"))
+        .args(["plan", "--dry-run", "--no-scan", "--format", "json"])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let plan: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout is a JSON plan");
+    assert!(plan["entries"].as_array().is_some());
+    assert!(plan["budget_bytes"].as_u64().is_some());
+    assert_eq!(plan["total_bytes"].as_u64(), Some(0));
+    // A dry run never creates the state database.
+    assert!(!dir.path().join("state.db").exists());
+    Ok(())
+}
+
+#[test]
+fn plan_dry_run_leaves_a_corrupt_database_alone() -> io::Result<()> {
+    let dir = tempdir()?;
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, "[model]\ncycle = 1\n")?;
+    fs::write(dir.path().join("state.db"), b"definitely not a database")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--state")
+        .arg(dir.path().join("state.db"))
+        .args(["plan", "--dry-run", "--no-scan"])
+        .output()?;
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read(dir.path().join("state.db"))?,
+        b"definitely not a database"
+    );
+    assert_eq!(fs::read_dir(dir.path())?.count(), 2);
+    Ok(())
+}
+
+#[test]
+fn plan_requires_dry_run() -> io::Result<()> {
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .args(["--no-persist", "plan"])
+        .output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--dry-run"));
+    Ok(())
+}
@@ -0,0 +1,310 @@
+#![forbid(unsafe_code)]
+
+use serde_json::{Value, json};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+fn model(model_time: u64, exes: &[(&str, u64)]) -> Value {
+    json!({
+        "format": "preload-ng-state",
+        "version": 1,
+        "snapshot": {
+            "meta": { "schema_version": 3, "app_version": null, "created_at": null },
+            "state": {
+                "model_time": model_time,
+                "last_accounting_time": model_time,
+                "exes": exes
+                    .iter()
+                    .map(|(path, time)| json!({
+                        "path": path,
+                        "total_running_time": time,
+                        "last_seen_time": null,
+                    }))
+                    .collect::<Vec<_>>(),
+                "maps": [
+                    { "path": "/usr/lib/libshared.so", "offset": 0, "length": 4096, "update_time": 1 }
+                ],
+                "exe_maps": [],
+                "markov_edges": [],
+                "launch_histograms": [],
+                "launch_sequences": [],
+            }
+        }
+    })
+}
+
+fn cli(dir: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+    let config_path = dir.join("config.toml");
+    fs::write(&config_path, "[model]\ncycle = 1\n")?;
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--state")
+        .arg(dir.join("state.db"))
+        .args(args)
+        .current_dir(dir)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(output.stdout)
+}
+
+fn exes(export: &Value) -> Vec<(String, u64)> {
+    export["snapshot"]["state"]["exes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|exe| {
+            let path = exe["path"].as_str().unwrap().to_owned();
+            (path, exe["total_running_time"].as_u64().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn import_replace_then_export_round_trips_through_json_and_cbor() -> io::Result<()> {
+    let dir = tempdir()?;
+    let source = model(100, &[("/usr/bin/a", 10), ("/usr/bin/b", 20)]);
+    fs::write(dir.path().join("in.json"), source.to_string())?;
+
+    cli(dir.path(), &["import", "in.json", "--replace"])?;
+    cli(dir.path(), &["export", "out.cbor", "--format", "cbor"])?;
+
+    let other = tempdir()?;
+    fs::copy(dir.path().join("out.cbor"), other.path().join("in.cbor"))?;
+    cli(other.path(), &["import", "in.cbor", "--replace"])?;
+    let exported: Value =
+        serde_json::from_slice(&cli(other.path(), &["export", "-"])?).expect("JSON export");
+
+    assert_eq!(exported["format"], "preload-ng-state");
+    assert_eq!(exported["snapshot"]["state"]["model_time"], 100);
+    let mut paths = exes(&exported);
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![("/usr/bin/a".into(), 10), ("/usr/bin/b".into(), 20)]
+    );
+    assert_eq!(
+        exported["snapshot"]["state"]["maps"],
+        source["snapshot"]["state"]["maps"]
+    );
+    Ok(())
+}
+
+#[test]
+fn import_merges_into_existing_model_by_default() -> io::Result<()> {
+    let dir = tempdir()?;
+    fs::write(
+        dir.path().join("base.json"),
+        model(100, &[("/usr/bin/a", 10), ("/usr/bin/b", 20)]).to_string(),
+    )?;
+    fs::write(
+        dir.path().join("extra.json"),
+        model(50, &[("/usr/bin/b", 5), ("/usr/bin/c", 7)]).to_string(),
+    )?;
+
+    cli(dir.path(), &["import", "base.json", "--replace"])?;
+    cli(dir.path(), &["import", "extra.json"])?;
+    let exported: Value =
+        serde_json::from_slice(&cli(dir.path(), &["export", "-"])?).expect("JSON export");
+
+    let mut paths = exes(&exported);
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            ("/usr/bin/a".into(), 10),
+            ("/usr/bin/b".into(), 5),
+            ("/usr/bin/c".into(), 7),
+        ]
+    );
+    assert_eq!(exported["snapshot"]["state"]["model_time"], 100);
+    Ok(())
+}
+
+#[test]
+fn merge_combines_two_state_databases() -> io::Result<()> {
+    let first = tempdir()?;
+    let second = tempdir()?;
+    fs::write(
+        first.path().join("in.json"),
+        model(100, &[("/usr/bin/a", 10), ("/usr/bin/b", 20)]).to_string(),
+    )?;
+    fs::write(
+        second.path().join("in.json"),
+        model(40, &[("/usr/bin/b", 5), ("/usr/bin/c", 7)]).to_string(),
+    )?;
+    cli(first.path(), &["import", "in.json", "--replace"])?;
+    cli(second.path(), &["import", "in.json", "--replace"])?;
+
+    let out = tempdir()?;
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("merge")
+        .arg(first.path().join("state.db"))
+        .arg(second.path().join("state.db"))
+        .arg("--output")
+        .arg(out.path().join("state.db"))
+        .output()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let exported: Value =
+        serde_json::from_slice(&cli(out.path(), &["export", "-"])?).expect("JSON export");
+    let mut paths = exes(&exported);
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            ("/usr/bin/a".into(), 10),
+            ("/usr/bin/b".into(), 25),
+            ("/usr/bin/c".into(), 7),
+        ]
+    );
+    assert_eq!(exported["snapshot"]["state"]["model_time"], 100);
+    assert_eq!(
+        exported["snapshot"]["state"]["maps"]
+            .as_array()
+            .map(Vec::len),
+        Some(1)
+    );
+    Ok(())
+}
+
+#[test]
+fn merge_replaces_an_existing_output_only_with_force() -> io::Result<()> {
+    let input = tempdir()?;
+    fs::write(
+        input.path().join("in.json"),
+        model(100, &[("/usr/bin/a", 10)]).to_string(),
+    )?;
+    cli(input.path(), &["import", "in.json", "--replace"])?;
+    let out = tempdir()?;
+    fs::write(
+        out.path().join("in.json"),
+        model(10, &[("/usr/bin/stale", 1)]).to_string(),
+    )?;
+    cli(out.path(), &["import", "in.json", "--replace"])?;
+
+    let merge = |force: bool| {
+        Command::new(env!("CARGO_BIN_EXE_cli"))
+            .arg("merge")
+            .arg(input.path().join("state.db"))
+            .arg(input.path().join("state.db"))
+            .arg("--output")
+            .arg(out.path().join("state.db"))
+            .args(force.then_some("--force"))
+            .output()
+    };
+    let refused = merge(false)?;
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("--force"));
+
+    let forced = merge(true)?;
+    assert!(
+        forced.status.success(),
+        "{}",
+        String::from_utf8_lossy(&forced.stderr)
+    );
+    let exported: Value =
+        serde_json::from_slice(&cli(out.path(), &["export", "-"])?).expect("JSON export");
+    assert_eq!(exes(&exported), vec![("/usr/bin/a".into(), 20)]);
+    assert!(!out.path().join("state.db.merge-tmp").exists());
+    Ok(())
+}
+
+#[test]
+fn merge_fails_on_corrupt_input_and_leaves_it_alone() -> io::Result<()> {
+    let first = tempdir()?;
+    let second = tempdir()?;
+    fs::write(
+        first.path().join("in.json"),
+        model(100, &[("/usr/bin/a", 10)]).to_string(),
+    )?;
+    cli(first.path(), &["import", "in.json", "--replace"])?;
+    let corrupt = second.path().join("state.db");
+    fs::write(&corrupt, b"definitely not a database")?;
+
+    let out = tempdir()?;
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("merge")
+        .arg(first.path().join("state.db"))
+        .arg(&corrupt)
+        .arg("--output")
+        .arg(out.path().join("state.db"))
+        .output()?;
+    assert!(!output.status.success());
+    assert_eq!(fs::read(&corrupt)?, b"definitely not a database");
+    assert!(!second.path().join("state.db.corrupt").exists());
+    assert!(!out.path().join("state.db").exists());
+    Ok(())
+}
+
+#[test]
+fn import_rejects_unknown_format() -> io::Result<()> {
+    let dir = tempdir()?;
+    let mut foreign = model(0, &[]);
+    foreign["format"] = json!("something-else");
+    fs::write(dir.path().join("in.json"), foreign.to_string())?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("--state")
+        .arg(dir.path().join("state.db"))
+        .args(["import"])
+        .arg(dir.path().join("in.json"))
+        .output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unsupported export"));
+    Ok(())
+}
+
+#[test]
+fn import_is_refused_while_the_state_is_locked() -> io::Result<()> {
+    let dir = tempdir()?;
+    fs::write(
+        dir.path().join("in.json"),
+        model(100, &[("/usr/bin/a", 10)]).to_string(),
+    )?;
+    // Stands in for a running daemon.
+    let lock = fs::File::create(dir.path().join("state.db.lock"))?;
+    lock.lock()?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .arg("--state")
+        .arg(dir.path().join("state.db"))
+        .args(["import", "--replace"])
+        .arg(dir.path().join("in.json"))
+        .output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("stop it before importing"));
+    assert!(!dir.path().join("state.db").exists());
+
+    drop(lock);
+    cli(dir.path(), &["import", "in.json", "--replace"])?;
+    Ok(())
+}
+
+#[test]
+fn export_without_saved_state_fails_and_creates_nothing() -> io::Result<()> {
+    let dir = tempdir()?;
+    for extra in [&[][..], &["--no-persist"][..]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+            .arg("--state")
+            .arg(dir.path().join("state.db"))
+            .args(extra)
+            .args(["export", "-"])
+            .output()?;
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+    assert!(!dir.path().join("state.db").exists());
+    Ok(())
+}
@@ -0,0 +1,14 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CombineMode {
+    /// Weighted mean of component scores.
+    #[default]
+    Weighted,
+    /// Noisy-OR: an exe is needed if any component (scaled by its weight,
+    /// clamped to 0..=1) says so.
+    NoisyOr,
+}
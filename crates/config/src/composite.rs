@@ -0,0 +1,34 @@
+#![forbid(unsafe_code)]
+
+use crate::combine_mode::CombineMode;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct Composite {
+    /// How component scores are combined.
+    pub combine: CombineMode,
+
+    /// Per-component weights. Components with weight 0 are not run.
+    pub weights: ComponentWeights,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ComponentWeights {
+    pub markov: f32,
+    pub frequency: f32,
+    pub time_of_day: f32,
+    pub sequence: f32,
+}
+
+impl Default for ComponentWeights {
+    fn default() -> Self {
+        Self {
+            markov: 1.0,
+            frequency: 0.5,
+            time_of_day: 0.5,
+            sequence: 1.0,
+        }
+    }
+}
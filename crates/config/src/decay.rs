@@ -0,0 +1,10 @@
+#![forbid(unsafe_code)]
+
+use std::time::Duration;
+
+/// Exponential decay rate (1/sec) with the given half-life. A zero
+/// half-life disables decay.
+pub(crate) fn rate_for_half_life(half_life: Duration) -> f32 {
+    let secs = half_life.as_secs_f32();
+    if secs > 0.0 { 2.0_f32.ln() / secs } else { 0.0 }
+}
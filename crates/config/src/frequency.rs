@@ -0,0 +1,32 @@
+#![forbid(unsafe_code)]
+
+use crate::decay::rate_for_half_life;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::time::Duration;
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Frequency {
+    /// Half-life of the recency factor, measured from when an exe was last
+    /// seen running.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub recency_half_life: Duration,
+}
+
+impl Default for Frequency {
+    fn default() -> Self {
+        Self {
+            recency_half_life: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl Frequency {
+    /// Recency decay rate (1/sec) derived from `recency_half_life`. Zero
+    /// disables the recency factor.
+    pub fn decay_factor(&self) -> f32 {
+        rate_for_half_life(self.recency_half_life)
+    }
+}
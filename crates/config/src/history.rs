@@ -0,0 +1,27 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::time::Duration;
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct History {
+    /// Append a usage summary per tick to the state database.
+    pub enabled: bool,
+
+    /// How long summaries are kept; older rows are overwritten as new ones
+    /// arrive.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub retention: Duration,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
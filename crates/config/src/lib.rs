@@ -1,18 +1,45 @@
 #![forbid(unsafe_code)]
 
+mod combine_mode;
+mod composite;
+mod decay;
 mod error;
+mod frequency;
+mod history;
 mod memory_policy;
+mod metrics;
 mod model;
 mod persistence;
+mod planner_kind;
+mod power_source;
+mod predictor_kind;
+mod pressure_policy;
+mod profile;
+mod sequence;
 mod sort_strategy;
 mod system;
+mod throttle;
+mod time_of_day;
 
+pub use combine_mode::CombineMode;
+pub use composite::{ComponentWeights, Composite};
 pub use error::Error;
+pub use frequency::Frequency;
+pub use history::History;
 pub use memory_policy::MemoryPolicy;
+pub use metrics::Metrics;
 pub use model::Model;
-pub use persistence::Persistence;
+pub use persistence::{Persistence, PersistenceBackend};
+pub use planner_kind::PlannerKind;
+pub use power_source::PowerSource;
+pub use predictor_kind::PredictorKind;
+pub use pressure_policy::PressurePolicy;
+pub use profile::{Profile, Profiles};
+pub use sequence::Sequence;
 pub use sort_strategy::SortStrategy;
 pub use system::{PrefetchBackend, System};
+pub use throttle::Throttle;
+pub use time_of_day::TimeOfDay;
 
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -23,6 +50,11 @@ pub struct Config {
     pub model: Model,
     pub system: System,
     pub persistence: Persistence,
+    pub metrics: Metrics,
+    pub profile: Profiles,
+    /// Profile applied by [`Config::apply_profile`], if any.
+    #[serde(skip)]
+    pub active_profile: Option<PowerSource>,
 }
 
 impl Config {
@@ -62,6 +94,23 @@ impl Config {
         Ok(config)
     }
 
+    /// Layer the profile for `source` over this configuration. Records the
+    /// active profile even when no overrides are configured for it.
+    pub fn apply_profile(&mut self, source: PowerSource) {
+        if let Some(profile) = self.profile.get(source).cloned() {
+            if let Some(memory) = profile.memory {
+                self.model.memory = memory;
+            }
+            if let Some(concurrency) = profile.prefetch_concurrency {
+                self.system.prefetch_concurrency = Some(concurrency);
+            }
+            if let Some(cycle) = profile.cycle {
+                self.model.cycle = cycle;
+            }
+        }
+        self.active_profile = Some(source);
+    }
+
     fn apply_defaults(&mut self) {
         // Ensure vectors are sorted for prefix matching semantics.
         self.system.exeprefix.sort();
@@ -133,4 +182,33 @@ mod tests {
             Some(Duration::from_secs(120))
         );
     }
+
+    #[test]
+    fn battery_profile_overrides_base() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[model]\ncycle = 20\n\
+             [profile.battery]\ncycle = 120\nprefetch_concurrency = 1\n\
+             [profile.battery.memory]\nmemtotal = 0\nmemavailable = 20\n",
+        )
+        .unwrap();
+
+        let base = Config::load(&path).unwrap();
+        assert!(base.profile.ac.is_none());
+
+        let mut ac = base.clone();
+        ac.apply_profile(PowerSource::Ac);
+        assert_eq!(ac.model.cycle, Duration::from_secs(20));
+        assert_eq!(ac.active_profile, Some(PowerSource::Ac));
+
+        let mut battery = base;
+        battery.apply_profile(PowerSource::Battery);
+        assert_eq!(battery.model.cycle, Duration::from_secs(120));
+        assert_eq!(battery.system.prefetch_concurrency, Some(1));
+        assert_eq!(battery.model.memory.memavailable, 20);
+        assert!(battery.model.memory.cgroup);
+        assert_eq!(battery.active_profile, Some(PowerSource::Battery));
+    }
 }
@@ -9,6 +9,9 @@ pub struct MemoryPolicy {
     pub memtotal: i32,
     /// Percentage of available memory (clamped to -100..=100).
     pub memavailable: i32,
+    /// Clamp total and available memory to the daemon's cgroup v2
+    /// `memory.max`/`memory.high` headroom before applying the percentages.
+    pub cgroup: bool,
 }
 
 impl Default for MemoryPolicy {
@@ -16,6 +19,7 @@ impl Default for MemoryPolicy {
         Self {
             memtotal: -5,
             memavailable: 95,
+            cgroup: true,
         }
     }
 }
@@ -25,6 +29,7 @@ impl MemoryPolicy {
         Self {
             memtotal: self.memtotal.clamp(-100, 100),
             memavailable: self.memavailable.clamp(-100, 100),
+            cgroup: self.cgroup,
         }
     }
 }
@@ -37,7 +42,7 @@ mod tests {
     proptest! {
         #[test]
         fn clamp_limits_values(a in -1000i32..1000, b in -1000i32..1000) {
-            let policy = MemoryPolicy { memtotal: a, memavailable: b }.clamp();
+            let policy = MemoryPolicy { memtotal: a, memavailable: b, cgroup: true }.clamp();
             prop_assert!((-100..=100).contains(&policy.memtotal));
             prop_assert!((-100..=100).contains(&policy.memavailable));
         }
@@ -0,0 +1,17 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Metrics {
+    /// File rewritten with the current metrics after every tick, e.g. in
+    /// node_exporter's textfile collector directory.
+    pub textfile: Option<PathBuf>,
+
+    /// Local address to serve the metrics over HTTP, without
+    /// authentication. Non-loopback addresses are allowed with a warning.
+    pub listen: Option<SocketAddr>,
+}
@@ -1,6 +1,13 @@
 #![forbid(unsafe_code)]
 
+use crate::composite::Composite;
+use crate::decay::rate_for_half_life;
+use crate::frequency::Frequency;
 use crate::memory_policy::MemoryPolicy;
+use crate::predictor_kind::PredictorKind;
+use crate::pressure_policy::PressurePolicy;
+use crate::sequence::Sequence;
+use crate::time_of_day::TimeOfDay;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use std::time::Duration;
@@ -13,6 +20,13 @@ pub struct Model {
     #[serde_as(as = "serde_with::DurationSeconds")]
     pub cycle: Duration,
 
+    /// Prediction horizon as a number of cycles. Ignored if `horizon` is set.
+    pub horizon_cycles: u32,
+
+    /// Prediction horizon in seconds; overrides `horizon_cycles`.
+    #[serde_as(as = "Option<serde_with::DurationSeconds>")]
+    pub horizon: Option<Duration>,
+
     /// Whether to use correlation in prediction.
     pub use_correlation: bool,
 
@@ -23,6 +37,12 @@ pub struct Model {
     #[serde_as(as = "serde_with::DurationSeconds")]
     pub active_window: Duration,
 
+    /// Keep the statistics of edges whose exes leave the active set in a
+    /// compact cold store, restored when both exes are active again,
+    /// instead of deleting them. The store is bounded; turning this off
+    /// empties it.
+    pub cold_edges: bool,
+
     /// Half-life for exponentially-fading means.
     #[serde_as(as = "Option<serde_with::DurationSeconds>")]
     pub half_life: Option<Duration>,
@@ -31,31 +51,63 @@ pub struct Model {
     pub decay: f32,
 
     pub memory: MemoryPolicy,
+
+    /// Pressure-stall thresholds that shrink the memory budget.
+    pub pressure: PressurePolicy,
+
+    /// Prediction model selection.
+    pub predictor: PredictorKind,
+
+    /// Settings for the time-of-day launch predictor.
+    pub time_of_day: TimeOfDay,
+
+    /// Settings for the launch sequence predictor.
+    pub sequence: Sequence,
+
+    /// Settings for the frequency/recency predictor.
+    pub frequency: Frequency,
+
+    /// Settings for the ensemble predictor.
+    pub composite: Composite,
 }
 
 impl Default for Model {
     fn default() -> Self {
         Self {
             cycle: Duration::from_secs(20),
+            horizon_cycles: 1,
+            horizon: None,
             use_correlation: true,
             minsize: 100_000,
             active_window: Duration::from_secs(6 * 60 * 60),
+            cold_edges: false,
             half_life: None,
             decay: 0.01,
             memory: MemoryPolicy::default(),
+            pressure: PressurePolicy::default(),
+            predictor: PredictorKind::default(),
+            time_of_day: TimeOfDay::default(),
+            sequence: Sequence::default(),
+            frequency: Frequency::default(),
+            composite: Composite::default(),
         }
     }
 }
 
 impl Model {
+    /// Prediction horizon in seconds: `horizon` if set, otherwise
+    /// `horizon_cycles` (at least one) cycles.
+    pub fn horizon_secs(&self) -> f32 {
+        match self.horizon {
+            Some(horizon) => horizon.as_secs_f32(),
+            None => self.cycle.as_secs_f32() * self.horizon_cycles.max(1) as f32,
+        }
+    }
+
     pub fn decay_factor(&self) -> f32 {
-        if let Some(half_life) = self.half_life {
-            let secs = half_life.as_secs_f32();
-            if secs > 0.0 {
-                return (2.0_f32.ln()) / secs;
-            }
-            return 0.0;
+        match self.half_life {
+            Some(half_life) => rate_for_half_life(half_life),
+            None => self.decay.max(0.0),
         }
-        self.decay.max(0.0)
     }
 }
@@ -1,13 +1,27 @@
 #![forbid(unsafe_code)]
 
+use crate::History;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use std::{path::PathBuf, time::Duration};
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PersistenceBackend {
+    /// SQLite database with incremental saves.
+    #[default]
+    Sqlite,
+    /// Single checksummed rkyv archive, rewritten on every save.
+    Rkyv,
+}
+
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct Persistence {
+    /// Storage format of the state file.
+    pub backend: PersistenceBackend,
+
     /// Optional path to the state database.
     pub state_path: Option<PathBuf>,
 
@@ -16,14 +30,26 @@ pub struct Persistence {
     pub autosave_interval: Option<Duration>,
 
     pub save_on_shutdown: bool,
+
+    /// Rotated copies of the state file kept next to it, refreshed by a
+    /// successful save at most once a day and used when the database is
+    /// corrupt.
+    /// 0 disables backups.
+    pub backups: usize,
+
+    /// Per-tick usage history kept for analytics (SQLite backend only).
+    pub history: History,
 }
 
 impl Default for Persistence {
     fn default() -> Self {
         Self {
+            backend: PersistenceBackend::Sqlite,
             state_path: None,
             autosave_interval: None,
             save_on_shutdown: true,
+            backups: 2,
+            history: History::default(),
         }
     }
 }
@@ -0,0 +1,13 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannerKind {
+    /// Highest score first; skip maps that no longer fit.
+    #[default]
+    Greedy,
+    /// Highest score per KB first, with an optional 0/1-knapsack pass.
+    Density,
+}
@@ -0,0 +1,14 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+/// Where the machine currently draws power from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSource {
+    /// Mains power, or no power supply information (desktops, servers).
+    #[default]
+    Ac,
+    /// Running on battery.
+    Battery,
+}
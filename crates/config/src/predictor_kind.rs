@@ -0,0 +1,19 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PredictorKind {
+    /// Pairwise Markov chains plus historical run-time frequency.
+    #[default]
+    Markov,
+    /// Per-exe launch histograms by hour of week.
+    TimeOfDay,
+    /// Ordered launch sequences ("after A, B follows").
+    Sequence,
+    /// Run-time share damped by time since last seen running.
+    Frequency,
+    /// Weighted or noisy-OR ensemble of the other predictors.
+    Composite,
+}
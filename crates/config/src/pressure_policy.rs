@@ -0,0 +1,37 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+/// PSI thresholds (10 s averages, in percent) that scale the prefetch budget.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PressurePolicy {
+    /// Whether PSI readings adjust the budget at all.
+    pub enabled: bool,
+    /// Memory `some` above this shrinks the budget.
+    pub memory_some: f32,
+    /// Memory `full` above this zeros the budget.
+    pub memory_full: f32,
+    /// I/O `some` above this shrinks the budget.
+    pub io_some: f32,
+    /// I/O `full` above this zeros the budget.
+    pub io_full: f32,
+    /// Factor applied to the budget scale for each cycle under pressure.
+    pub shrink: f32,
+    /// Amount the budget scale recovers per cycle once pressure clears.
+    pub ramp_up: f32,
+}
+
+impl Default for PressurePolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            memory_some: 10.0,
+            memory_full: 5.0,
+            io_some: 40.0,
+            io_full: 20.0,
+            shrink: 0.5,
+            ramp_up: 0.25,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+#![forbid(unsafe_code)]
+
+use crate::memory_policy::MemoryPolicy;
+use crate::power_source::PowerSource;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::time::Duration;
+
+/// Power-source profiles layered over the base configuration.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Profiles {
+    /// How often `/sys/class/power_supply` is polled for a change of
+    /// power source.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub poll_interval: Duration,
+
+    /// Overrides applied on mains power.
+    pub ac: Option<Profile>,
+
+    /// Overrides applied on battery.
+    pub battery: Option<Profile>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+            ac: None,
+            battery: None,
+        }
+    }
+}
+
+impl Profiles {
+    /// Whether any profile is configured, i.e. power monitoring matters.
+    pub fn is_empty(&self) -> bool {
+        self.ac.is_none() && self.battery.is_none()
+    }
+
+    pub fn get(&self, source: PowerSource) -> Option<&Profile> {
+        match source {
+            PowerSource::Ac => self.ac.as_ref(),
+            PowerSource::Battery => self.battery.as_ref(),
+        }
+    }
+}
+
+/// Settings a profile may override; unset fields keep the base value.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct Profile {
+    /// Replaces `model.memory`.
+    pub memory: Option<MemoryPolicy>,
+
+    /// Replaces `system.prefetch_concurrency`.
+    pub prefetch_concurrency: Option<usize>,
+
+    /// Replaces `model.cycle`.
+    #[serde_as(as = "Option<serde_with::DurationSeconds>")]
+    pub cycle: Option<Duration>,
+}
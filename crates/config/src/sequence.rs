@@ -0,0 +1,44 @@
+#![forbid(unsafe_code)]
+
+use crate::decay::rate_for_half_life;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::time::Duration;
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Sequence {
+    /// Longest launch context (number of preceding launches) to model.
+    pub max_order: usize,
+
+    /// Maximum gap between consecutive launches of one sequence.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub gap: Duration,
+
+    /// Half-life of sequence counts.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub half_life: Duration,
+
+    /// Pseudo-count added to each context's total. Damps estimates from
+    /// rarely seen contexts.
+    pub prior: f32,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self {
+            max_order: 3,
+            gap: Duration::from_secs(60),
+            half_life: Duration::from_secs(14 * 24 * 60 * 60),
+            prior: 2.0,
+        }
+    }
+}
+
+impl Sequence {
+    /// Decay rate (1/sec) derived from `half_life`. Zero disables decay.
+    pub fn decay_factor(&self) -> f32 {
+        rate_for_half_life(self.half_life)
+    }
+}
@@ -1,6 +1,8 @@
 #![forbid(unsafe_code)]
 
+use crate::planner_kind::PlannerKind;
 use crate::sort_strategy::SortStrategy;
+use crate::throttle::Throttle;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use std::time::Duration;
@@ -35,11 +37,45 @@ pub struct System {
     /// Prefetch sort strategy.
     pub sortstrategy: SortStrategy,
 
+    /// Rank maps due in an earlier cycle first, by score within a cycle.
+    /// Maps without an ETA rank as if due at the end of the horizon.
+    pub prioritize_by_eta: bool,
+
+    /// Prefetch planner selection.
+    pub planner: PlannerKind,
+
+    /// Number of densest candidates the density planner optimises with an
+    /// exact 0/1-knapsack pass. 0 disables the pass; values above 256 are
+    /// clamped to 256.
+    pub knapsack_candidates: usize,
+
+    /// Probe page-cache residency while planning so the budget only counts
+    /// bytes that still need reading.
+    pub residency_aware: bool,
+
+    /// How long a map's probed residency is reused. Defaults to one model
+    /// cycle so candidates are probed at most once per tick.
+    #[serde_as(as = "Option<serde_with::DurationSeconds>")]
+    pub residency_cache_ttl: Option<Duration>,
+
+    /// How long a prefetched map is left out of later plans unless its
+    /// pages were evicted in the meantime. 0 disables the cooldown.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub prefetch_cooldown: Duration,
+
+    /// Dispatch throttling while executing a prefetch plan.
+    pub throttle: Throttle,
+
     /// Max number of concurrent prefetch workers. Default: 1.
     /// None (omitted from config) falls back to CPU core count.
     /// 0 disables prefetch entirely.
     pub prefetch_concurrency: Option<usize>,
 
+    /// Maps dispatched per chunk while executing a plan. Cancellation
+    /// (shutdown, reload, or the next cycle's plan) is checked between
+    /// chunks and before each map starts. 0 is treated as 1.
+    pub prefetch_chunk_size: usize,
+
     /// How long to cache admission rejections.
     #[serde_as(as = "serde_with::DurationSeconds")]
     pub policy_cache_ttl: Duration,
@@ -68,13 +104,17 @@ impl Default for System {
                 "!/run/".into(),
                 "/".into(),
             ],
-            exeprefix: vec![
-                "!/usr/sbin/".into(),
-                "!/usr/local/sbin/".into(),
-                "/".into(),
-            ],
+            exeprefix: vec!["!/usr/sbin/".into(), "!/usr/local/sbin/".into(), "/".into()],
             sortstrategy: SortStrategy::Block,
+            prioritize_by_eta: false,
+            planner: PlannerKind::default(),
+            knapsack_candidates: 0,
+            residency_aware: true,
+            residency_cache_ttl: None,
+            prefetch_cooldown: Duration::from_secs(300),
+            throttle: Throttle::default(),
             prefetch_concurrency: Some(1),
+            prefetch_chunk_size: 64,
             policy_cache_ttl: Duration::from_secs(300),
             policy_cache_capacity: 1024,
             fanotify: true,
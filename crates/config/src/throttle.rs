@@ -0,0 +1,45 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::time::Duration;
+
+/// Dispatch throttling applied while a prefetch plan executes.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Throttle {
+    /// Ceiling on prefetch reads in bytes per second. 0 = unlimited.
+    pub max_bytes_per_sec: u64,
+
+    /// Pause dispatch while `/proc/pressure/io` `some` (10 s average,
+    /// percent) is above this. 0 disables the check.
+    pub io_pressure: f32,
+
+    /// Pause dispatch while other disk traffic (from `/proc/diskstats`,
+    /// excluding our own reads) exceeds this many bytes per second.
+    /// 0 disables the check.
+    pub foreground_bytes_per_sec: u64,
+
+    /// How often I/O pressure and disk activity are sampled; at least
+    /// 50 ms.
+    #[serde_as(as = "serde_with::DurationMilliSeconds")]
+    pub check_interval: Duration,
+
+    /// Longest total pause per prefetch run before dispatch resumes
+    /// regardless of activity.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub max_pause: Duration,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self {
+            max_bytes_per_sec: 0,
+            io_pressure: 20.0,
+            foreground_bytes_per_sec: 64 * 1024 * 1024,
+            check_interval: Duration::from_millis(500),
+            max_pause: Duration::from_secs(10),
+        }
+    }
+}
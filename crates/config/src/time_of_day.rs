@@ -0,0 +1,46 @@
+#![forbid(unsafe_code)]
+
+use crate::decay::rate_for_half_life;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::time::Duration;
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TimeOfDay {
+    /// Half-life of launch histogram weights.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub half_life: Duration,
+
+    /// How far ahead a typical launch window counts as approaching.
+    #[serde_as(as = "serde_with::DurationSeconds")]
+    pub lookahead: Duration,
+
+    /// Pseudo-count added to an exe's total launch weight. Damps scores of
+    /// exes with only a handful of recorded launches.
+    pub prior: f32,
+
+    /// Fixed offset of local time from UTC, in seconds east, used to bucket
+    /// launches by hour of week. Unset follows the system time zone,
+    /// including daylight saving changes.
+    pub utc_offset: Option<i32>,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            half_life: Duration::from_secs(14 * 24 * 60 * 60),
+            lookahead: Duration::from_secs(60 * 60),
+            prior: 2.0,
+            utc_offset: None,
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// Decay rate (1/sec) derived from `half_life`. Zero disables decay.
+    pub fn decay_factor(&self) -> f32 {
+        rate_for_half_life(self.half_life)
+    }
+}
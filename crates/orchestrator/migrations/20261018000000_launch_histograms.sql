@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS launch_histograms (
+    exe_path TEXT NOT NULL PRIMARY KEY,
+    weights BLOB NOT NULL,
+    updated_at INTEGER NOT NULL
+);
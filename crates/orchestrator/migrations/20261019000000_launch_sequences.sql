@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS launch_sequences (
+    context BLOB NOT NULL PRIMARY KEY,
+    followers BLOB NOT NULL,
+    updated_at INTEGER NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE markovs ADD COLUMN state INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE markovs ADD COLUMN last_change_time INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE markovs ADD COLUMN state_last_left BLOB;
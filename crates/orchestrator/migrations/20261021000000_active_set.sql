@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS active_exes (
+    exe_path TEXT NOT NULL PRIMARY KEY,
+    last_active INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS markov_cold (
+    exe_a TEXT NOT NULL,
+    exe_b TEXT NOT NULL,
+    time_to_leave BLOB NOT NULL,
+    transition_prob BLOB NOT NULL,
+    both_running_time INTEGER NOT NULL,
+    PRIMARY KEY (exe_a, exe_b)
+);
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS usage_ticks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at INTEGER NOT NULL,
+    model_time INTEGER NOT NULL,
+    plan_maps INTEGER NOT NULL,
+    plan_bytes INTEGER NOT NULL,
+    budget_bytes INTEGER NOT NULL,
+    prefetched_maps INTEGER NOT NULL,
+    prefetched_bytes INTEGER NOT NULL,
+    mem_total_kb INTEGER,
+    mem_available_kb INTEGER,
+    mem_cached_kb INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS usage_ticks_recorded_at ON usage_ticks (recorded_at);
+
+CREATE TABLE IF NOT EXISTS usage_launches (
+    tick_id INTEGER NOT NULL,
+    exe_path TEXT NOT NULL,
+    predicted INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS usage_launches_tick_id ON usage_launches (tick_id);
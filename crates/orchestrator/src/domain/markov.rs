@@ -24,6 +24,17 @@ impl MarkovState {
     pub fn index(self) -> usize {
         self as usize
     }
+
+    /// Inverse of [`index`](Self::index).
+    pub fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(MarkovState::Neither),
+            1 => Some(MarkovState::AOnly),
+            2 => Some(MarkovState::BOnly),
+            3 => Some(MarkovState::Both),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for MarkovState {
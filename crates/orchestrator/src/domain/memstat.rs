@@ -1,6 +1,6 @@
 #![forbid(unsafe_code)]
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct MemStat {
     pub total: u64,
     pub available: u64,
@@ -8,4 +8,42 @@ pub struct MemStat {
     pub cached: u64,
     pub pagein: i64,
     pub pageout: i64,
+    /// Memory pressure from `/proc/pressure/memory`, if available.
+    pub memory_pressure: Option<Psi>,
+    /// I/O pressure from `/proc/pressure/io`, if available.
+    pub io_pressure: Option<Psi>,
+    /// Limits and usage of the daemon's cgroup v2, if available.
+    pub cgroup: Option<CgroupMemory>,
+}
+
+/// Pressure stall percentages (10 s averages) for one resource.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Psi {
+    /// Share of time at least one task was stalled.
+    pub some: f32,
+    /// Share of time all non-idle tasks were stalled.
+    pub full: f32,
+}
+
+/// Memory controller state of a cgroup v2, in KB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CgroupMemory {
+    /// Hard limit (`memory.max`), tightest along the hierarchy.
+    pub max: Option<u64>,
+    /// Reclaim threshold (`memory.high`), tightest along the hierarchy.
+    pub high: Option<u64>,
+    /// Room left before the tightest limit: the smallest `memory.max` or
+    /// `memory.high` minus that cgroup's own `memory.current` (which
+    /// covers its whole subtree), over every limited level.
+    pub headroom: Option<u64>,
+}
+
+impl CgroupMemory {
+    /// The lower of `max` and `high`, if either is set.
+    pub fn limit(&self) -> Option<u64> {
+        match (self.max, self.high) {
+            (Some(max), Some(high)) => Some(max.min(high)),
+            (max, high) => max.or(high),
+        }
+    }
 }
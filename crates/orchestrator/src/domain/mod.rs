@@ -10,4 +10,4 @@ pub use exe::Exe;
 pub use ids::{ExeId, ExeKey, MapId, MapKey};
 pub use map_segment::MapSegment;
 pub use markov::MarkovState;
-pub use memstat::MemStat;
+pub use memstat::{CgroupMemory, MemStat, Psi};
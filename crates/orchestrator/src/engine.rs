@@ -1,21 +1,32 @@
 #![forbid(unsafe_code)]
 
 use crate::clock::Clock;
-use crate::domain::{ExeKey, MapSegment, MarkovState, MemStat};
+use crate::domain::{ExeKey, MapId, MapSegment, MarkovState, MemStat};
 use crate::error::Error;
+use crate::metrics::{CpuTimes, Metrics, PhaseTimings, ResourceUsage, write_textfile};
 use crate::observation::{AdmissionPolicy, ModelDelta, ModelUpdater, ObservationEvent, Scanner};
 use crate::persistence::{
-    ExeMapRecord, ExeRecord, MapRecord, MarkovRecord, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta,
-    StateRepository, StateSnapshot, StoresSnapshot,
+    ActiveRecord, DeltaTracker, ExeMapRecord, ExeRecord, LaunchHistogramRecord,
+    LaunchSequenceRecord, LaunchUsage, MapRecord, MarkovRecord, SNAPSHOT_SCHEMA_VERSION,
+    SnapshotMeta, StateRepository, StateSnapshot, StoresSnapshot, UsageRecord, upgrade_snapshot,
 };
 use crate::prediction::{Prediction, Predictor};
-use crate::prefetch::{PrefetchPlanner, PrefetchReport, Prefetcher};
-use crate::stores::Stores;
+use crate::prefetch::{
+    Cooldown, PlanPreview, PrefetchPlanner, PrefetchReport, Prefetcher, PressureScale,
+};
+use crate::stores::{ColdEdge, LaunchHistogram, SequenceContext, Stores};
 use config::Config;
+use half::f16;
+use rustc_hash::FxHashSet;
+use std::path::PathBuf;
 use std::time::{Instant, SystemTime};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info, warn};
+use tracing::{Level, debug, info, trace, warn};
+
+/// Ticks of usage history queued before they are written without waiting
+/// for a save, bounding the queue when saves are rare or disabled.
+const MAX_PENDING_USAGE: usize = 1024;
 
 pub struct Services {
     pub scanner: Box<dyn Scanner + Send + Sync>,
@@ -50,6 +61,8 @@ pub struct TickReport {
     pub prediction: crate::prediction::PredictionSummary,
     pub prefetch: PrefetchReport,
     pub memstat: Option<MemStat>,
+    pub timings: PhaseTimings,
+    pub resources: ResourceUsage,
 }
 
 pub struct PreloadEngine {
@@ -58,6 +71,25 @@ pub struct PreloadEngine {
     stores: Stores,
     scan_id: u64,
     last_save: Instant,
+    /// What the repository holds, once known; saves then write only the
+    /// rows that changed since.
+    saved: Option<DeltaTracker>,
+    /// Maps of the previous plan, to tell which launches were prefetched
+    /// for in the usage history.
+    last_plan: FxHashSet<MapId>,
+    /// Usage history of the ticks since the last save, written with it.
+    pending_usage: Vec<UsageRecord>,
+    metrics: Metrics,
+    /// Phases of the tick in progress.
+    timings: PhaseTimings,
+    /// Prefetch budget scale under memory and I/O pressure, kept across
+    /// reloads.
+    pressure: PressureScale,
+    /// Maps prefetched recently, kept across reloads so a rebuilt planner
+    /// does not prefetch them all again.
+    cooldown: Cooldown,
+    /// Rendered metrics, refreshed after each tick while anyone listens.
+    metrics_feed: watch::Sender<String>,
 }
 
 impl PreloadEngine {
@@ -69,14 +101,23 @@ impl PreloadEngine {
             stores: Stores::default(),
             scan_id: 0,
             last_save: Instant::now(),
+            saved: None,
+            last_plan: FxHashSet::default(),
+            pending_usage: Vec::new(),
+            metrics: Metrics::default(),
+            timings: PhaseTimings::default(),
+            pressure: PressureScale::default(),
+            cooldown: Cooldown::default(),
+            metrics_feed: watch::Sender::new(String::new()),
         })
     }
 
     /// Load state from the configured repository and build the engine.
     pub async fn load(config: Config, services: Services) -> Result<Self, Error> {
         let snapshot = services.repo.load().await?;
-        let mut stores =
-            Self::stores_from_snapshot(snapshot, config.model.active_window.as_secs())?;
+        let saved = DeltaTracker::new(&snapshot);
+        let snapshot = upgrade_snapshot(snapshot)?;
+        let mut stores = Self::stores_from_snapshot(snapshot, config.model.cold_edges)?;
 
         // Purge maps whose paths are denied by the current admission policy.
         let denied_keys: Vec<_> = stores
@@ -89,7 +130,10 @@ impl PreloadEngine {
             for key in &denied_keys {
                 stores.remove_map_by_key(key);
             }
-            info!(removed = denied_keys.len(), "purged maps denied by prefix policy");
+            info!(
+                removed = denied_keys.len(),
+                "purged maps denied by prefix policy"
+            );
         }
 
         Ok(Self {
@@ -98,70 +142,82 @@ impl PreloadEngine {
             stores,
             scan_id: 0,
             last_save: Instant::now(),
+            saved: Some(saved),
+            last_plan: FxHashSet::default(),
+            pending_usage: Vec::new(),
+            metrics: Metrics::default(),
+            timings: PhaseTimings::default(),
+            pressure: PressureScale::default(),
+            cooldown: Cooldown::default(),
+            metrics_feed: watch::Sender::new(String::new()),
         })
     }
 
     /// Execute a single scan/update/predict/prefetch cycle without sleeping.
     pub async fn tick(&mut self) -> Result<TickReport, Error> {
-        self.scan_id = self.scan_id.saturating_add(1);
-        let now = self.stores.model_time;
-
-        let observation = if self.config.system.doscan {
-            self.services.scanner.scan(now, self.scan_id)?
-        } else {
-            vec![
-                ObservationEvent::ObsBegin {
-                    time: now,
-                    scan_id: self.scan_id,
-                },
-                ObservationEvent::ObsEnd {
-                    time: now,
-                    scan_id: self.scan_id,
-                    warnings: Vec::new(),
-                },
-            ]
-        };
-
-        let memstat = observation.iter().find_map(|event| match event {
-            ObservationEvent::MemStat { mem } => Some(*mem),
-            _ => None,
-        });
-
-        let model_delta = if self.config.system.doscan {
-            self.services.updater.apply(
-                &mut self.stores,
-                &observation,
-                self.services.admission.as_ref(),
-            )?
-        } else {
-            ModelDelta::default()
-        };
+        self.tick_until(&CancellationToken::new()).await
+    }
 
+    /// Like [`tick`](Self::tick), but prefetch execution stops early when
+    /// `cancel` fires or the plan is still running once the next cycle's
+    /// plan is due. The report records how much of the plan was done.
+    pub async fn tick_until(&mut self, cancel: &CancellationToken) -> Result<TickReport, Error> {
+        let started = Instant::now();
+        let cpu = CpuTimes::now();
+        self.timings = PhaseTimings::default();
+        // The first scan only establishes what is already running.
+        let running_observed = self.stores.running_observed;
+        let (model_delta, memstat) = self.observe()?;
+
+        let phase = Instant::now();
         let prediction = if self.config.system.dopredict {
             self.services.predictor.predict(&self.stores)
         } else {
             Prediction::default()
         };
+        self.timings.predict = phase.elapsed();
+        if !prediction.components.is_empty() && tracing::enabled!(Level::TRACE) {
+            self.trace_explain(&prediction);
+        }
 
+        let phase = Instant::now();
         let plan = if self.config.system.dopredict {
             if let Some(mem) = memstat {
-                self.services.planner.plan(&prediction, &self.stores, &mem)
+                self.pressure.observe(&self.config.model.pressure, &mem);
+                self.probe_residency(&prediction, &mem).await;
+                self.services.planner.plan(
+                    &prediction,
+                    &self.stores,
+                    &mem,
+                    self.pressure,
+                    &self.cooldown,
+                )
             } else {
-                crate::prefetch::PrefetchPlan {
-                    maps: Vec::new(),
-                    total_bytes: 0,
-                    budget_bytes: 0,
-                }
+                crate::prefetch::PrefetchPlan::default()
             }
         } else {
-            crate::prefetch::PrefetchPlan {
-                maps: Vec::new(),
-                total_bytes: 0,
-                budget_bytes: 0,
-            }
+            crate::prefetch::PrefetchPlan::default()
         };
-
-        let prefetch = self.services.prefetcher.execute(&plan, &self.stores).await;
+        self.timings.plan = phase.elapsed();
+
+        let phase = Instant::now();
+        let prefetch = self.execute_plan(&plan, cancel).await;
+        if let Some(record) = self
+            .services
+            .planner
+            .record_prefetch(&prefetch, &self.cooldown)
+            && let Err(err) = tokio::task::spawn_blocking(record).await
+        {
+            warn!(%err, "recording prefetch cooldown failed");
+        }
+        self.timings.prefetch = phase.elapsed();
+        if prefetch.cancelled {
+            debug!(
+                done = prefetch.num_maps,
+                skipped = prefetch.skipped,
+                "prefetch cancelled"
+            );
+        }
 
         // Remove maps whose files no longer exist on disk.
         if !prefetch.failures.is_empty() {
@@ -177,86 +233,374 @@ impl PreloadEngine {
             }
         }
 
+        if self.config.persistence.history.enabled {
+            let launches = if running_observed {
+                model_delta.running_now.as_slice()
+            } else {
+                &[]
+            };
+            self.record_usage(launches, &plan, &prefetch, memstat).await;
+        }
+
         // Advance model time by one cycle.
         self.stores.model_time = self
             .stores
             .model_time
             .saturating_add(self.config.model.cycle.as_secs());
 
+        self.autosave().await;
+
+        let resources = cpu.usage_since();
+        debug!(
+            scan_id = self.scan_id,
+            timings = ?self.timings,
+            ?resources,
+            "tick finished"
+        );
+        self.publish_metrics(started.elapsed(), &resources, &plan, &prefetch);
+
         Ok(TickReport {
             scan_id: self.scan_id,
             model_delta,
             prediction: prediction.summarize(),
             prefetch,
             memstat,
+            timings: self.timings,
+            resources,
         })
     }
 
-    /// Run ticks until the cancellation token is triggered. Handles autosave.
+    /// Save if the autosave interval has passed since the last save. A
+    /// failed save is logged and retried after another interval, so it
+    /// does not cost the tick its report and metrics.
+    async fn autosave(&mut self) {
+        let autosave = self
+            .config
+            .persistence
+            .autosave_interval
+            .unwrap_or(self.config.system.autosave);
+        if autosave.is_zero() || self.last_save.elapsed() < autosave {
+            return;
+        }
+
+        let phase = Instant::now();
+        if let Err(err) = self.save().await {
+            warn!(%err, "autosave failed");
+        }
+        self.last_save = Instant::now();
+        self.timings.save = phase.elapsed();
+    }
+
+    /// Fold this tick into the metrics, then write the textfile and feed
+    /// the endpoint if either is in use.
+    fn publish_metrics(
+        &mut self,
+        elapsed: std::time::Duration,
+        resources: &ResourceUsage,
+        plan: &crate::prefetch::PrefetchPlan,
+        prefetch: &PrefetchReport,
+    ) {
+        self.metrics.record_tick(
+            elapsed,
+            &self.timings,
+            resources,
+            &self.stores,
+            plan,
+            prefetch,
+        );
+        self.metrics.admission = self.services.admission.stats();
+        self.metrics.fanotify = self.services.scanner.fanotify_stats();
+
+        let textfile = self.config.metrics.textfile.as_deref();
+        if textfile.is_none() && self.metrics_feed.receiver_count() == 0 {
+            return;
+        }
+        let text = self.metrics.render();
+        if let Some(path) = textfile
+            && let Err(err) = write_textfile(path, &text)
+        {
+            warn!(%err, path = %path.display(), "failed to write metrics textfile");
+        }
+        self.metrics_feed.send_replace(text);
+    }
+
+    /// Queue this tick for the usage history, written with the next save or
+    /// once [`MAX_PENDING_USAGE`] ticks are queued.
+    async fn record_usage(
+        &mut self,
+        launches: &[ExeKey],
+        plan: &crate::prefetch::PrefetchPlan,
+        prefetch: &PrefetchReport,
+        memstat: Option<MemStat>,
+    ) {
+        let launches = launches
+            .iter()
+            .map(|key| {
+                let predicted = self.stores.exes.id_by_key(key).is_some_and(|exe_id| {
+                    self.stores
+                        .exe_maps
+                        .maps_for_exe(exe_id)
+                        .any(|map_id| self.last_plan.contains(&map_id))
+                });
+                LaunchUsage {
+                    exe_path: key.path().to_path_buf(),
+                    predicted,
+                }
+            })
+            .collect();
+        let recorded_at = self.stores.wall_time.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+        });
+        let record = UsageRecord {
+            recorded_at,
+            model_time: self.stores.model_time,
+            launches,
+            plan_maps: plan.maps.len(),
+            plan_bytes: plan.total_bytes,
+            budget_bytes: plan.budget_bytes,
+            prefetched_maps: prefetch.num_maps,
+            prefetched_bytes: prefetch.read_bytes,
+            memstat,
+        };
+        self.last_plan = plan.maps.iter().copied().collect();
+
+        self.pending_usage.push(record);
+        if self.pending_usage.len() >= MAX_PENDING_USAGE {
+            self.flush_usage().await;
+        }
+    }
+
+    /// Write the usage history queued since the last save. Failures are
+    /// logged only, and the queued ticks dropped.
+    pub async fn flush_usage(&mut self) {
+        if self.pending_usage.is_empty() {
+            return;
+        }
+        let records = std::mem::take(&mut self.pending_usage);
+        let retention = self.config.persistence.history.retention;
+        if let Err(err) = self.services.repo.record_usage(&records, retention).await {
+            warn!(%err, ticks = records.len(), "failed to record usage history");
+        }
+    }
+
+    /// Scan once and fold the observation into the model. Returns the model
+    /// delta and the memory stats sampled by the scan; a no-op when
+    /// scanning is disabled.
+    pub fn observe(&mut self) -> Result<(ModelDelta, Option<MemStat>), Error> {
+        self.scan_id = self.scan_id.saturating_add(1);
+        let now = self.stores.model_time;
+
+        let phase = Instant::now();
+        let drained = self.drain_time();
+        let observation = if self.config.system.doscan {
+            self.services.scanner.scan(now, self.scan_id)?
+        } else {
+            vec![
+                ObservationEvent::ObsBegin {
+                    time: now,
+                    scan_id: self.scan_id,
+                },
+                ObservationEvent::ObsEnd {
+                    time: now,
+                    scan_id: self.scan_id,
+                    warnings: Vec::new(),
+                },
+            ]
+        };
+
+        let memstat = observation.iter().find_map(|event| match event {
+            ObservationEvent::MemStat { mem } => Some(*mem),
+            _ => None,
+        });
+
+        self.timings.fanotify = self.drain_time().saturating_sub(drained);
+        self.timings.scan = phase.elapsed().saturating_sub(self.timings.fanotify);
+
+        let phase = Instant::now();
+        let model_delta = if self.config.system.doscan {
+            self.services.updater.apply(
+                &mut self.stores,
+                &observation,
+                self.services.admission.as_ref(),
+            )?
+        } else {
+            ModelDelta::default()
+        };
+        self.timings.update = phase.elapsed();
+
+        Ok((model_delta, memstat))
+    }
+
+    /// Total time the scanner has spent draining fanotify events.
+    fn drain_time(&self) -> std::time::Duration {
+        self.services
+            .scanner
+            .fanotify_stats()
+            .map(|stats| stats.drain_time)
+            .unwrap_or_default()
+    }
+
+    /// Run the planner's page-cache probes on the blocking pool, so
+    /// planning itself never waits on mincore or cachestat.
+    async fn probe_residency(&self, prediction: &Prediction, mem: &MemStat) {
+        let probes =
+            self.services
+                .planner
+                .residency_probes(prediction, &self.stores, mem, self.pressure);
+        if let Some(probes) = probes
+            && let Err(err) = tokio::task::spawn_blocking(probes).await
+        {
+            warn!(%err, "residency probes failed");
+        }
+    }
+
+    /// Predict and plan against the current model without prefetching,
+    /// returning the plan as it would be executed.
+    pub async fn preview(&self, memstat: &MemStat) -> PlanPreview {
+        let prediction = self.services.predictor.predict(&self.stores);
+        self.probe_residency(&prediction, memstat).await;
+        let plan = self.services.planner.plan(
+            &prediction,
+            &self.stores,
+            memstat,
+            self.pressure,
+            &self.cooldown,
+        );
+        PlanPreview::new(&plan, &prediction, &self.stores)
+    }
+
+    /// Execute `plan`, cancelling it once the next cycle's plan is due so a
+    /// long-running plan never delays a fresher one.
+    async fn execute_plan(
+        &self,
+        plan: &crate::prefetch::PrefetchPlan,
+        cancel: &CancellationToken,
+    ) -> PrefetchReport {
+        let cancel = cancel.child_token();
+        let execute = self
+            .services
+            .prefetcher
+            .execute(plan, &self.stores, &cancel);
+        tokio::pin!(execute);
+
+        let cycle = self.config.model.cycle;
+        if cycle.is_zero() {
+            return execute.await;
+        }
+        tokio::select! {
+            report = &mut execute => report,
+            _ = self.services.clock.sleep(cycle) => {
+                debug!("prefetch superseded by next cycle");
+                cancel.cancel();
+                execute.await
+            }
+        }
+    }
+
+    /// Run ticks until the cancellation token is triggered.
     pub async fn run_until(
         &mut self,
         cancel: CancellationToken,
         mut control_rx: mpsc::UnboundedReceiver<ControlEvent>,
     ) -> Result<(), Error> {
         loop {
-            let tick_start = self.services.clock.now();
-            let mut did_tick = false;
-            tokio::select! {
-                _ = cancel.cancelled() => {
-                    if self.config.persistence.save_on_shutdown {
-                        let _ = self.save().await;
-                    }
-                    info!("shutdown requested");
-                    break;
-                }
-                Some(event) = control_rx.recv() => {
-                    self.handle_control(event).await?;
-                }
-                result = self.tick() => {
-                    result?;
-                    did_tick = true;
-                }
+            if cancel.is_cancelled() {
+                self.shutdown().await;
+                break;
             }
-
-            let autosave = self
-                .config
-                .persistence
-                .autosave_interval
-                .unwrap_or(self.config.system.autosave);
-
-            if autosave.as_secs() > 0 {
-                let elapsed = self.last_save.elapsed();
-                if elapsed >= autosave {
-                    self.save().await?;
-                    self.last_save = Instant::now();
-                }
+            while let Ok(event) = control_rx.try_recv() {
+                self.handle_control(event).await?;
             }
 
-            if did_tick {
-                let elapsed = tick_start.elapsed();
-                if elapsed < self.config.model.cycle {
-                    let sleep_for = self.config.model.cycle - elapsed;
+            let tick_start = self.services.clock.now();
+
+            // Keep the tick running while control events arrive so in-flight
+            // prefetch winds down cleanly; a reload or shutdown cancels it and
+            // events are applied once it returns.
+            let tick_cancel = cancel.child_token();
+            let mut deferred = Vec::new();
+            let result = {
+                let tick = self.tick_until(&tick_cancel);
+                tokio::pin!(tick);
+                loop {
                     tokio::select! {
-                        _ = cancel.cancelled() => {
-                            if self.config.persistence.save_on_shutdown {
-                                let _ = self.save().await;
+                        result = &mut tick => break result,
+                        Some(event) = control_rx.recv() => {
+                            if matches!(event, ControlEvent::Reload(_)) {
+                                tick_cancel.cancel();
                             }
-                            info!("shutdown requested");
-                            break;
+                            deferred.push(event);
                         }
-                        _ = self.services.clock.sleep(sleep_for) => {}
                     }
                 }
+            };
+            result?;
+            for event in deferred {
+                self.handle_control(event).await?;
+            }
+            if cancel.is_cancelled() {
+                self.shutdown().await;
+                break;
+            }
+
+            let elapsed = tick_start.elapsed();
+            if elapsed < self.config.model.cycle {
+                let sleep_for = self.config.model.cycle - elapsed;
+                tokio::select! {
+                    _ = cancel.cancelled() => {
+                        self.shutdown().await;
+                        break;
+                    }
+                    _ = self.services.clock.sleep(sleep_for) => {}
+                }
             }
         }
 
         Ok(())
     }
 
-    /// Persist current state via the configured repository.
-    pub async fn save(&self) -> Result<(), Error> {
+    async fn shutdown(&mut self) {
+        if self.config.persistence.save_on_shutdown {
+            let _ = self.save().await;
+        } else {
+            self.flush_usage().await;
+        }
+        info!("shutdown requested");
+    }
+
+    /// Persist current state via the configured repository, with the usage
+    /// history queued since the last save. After the first save (or a load)
+    /// only rows changed since are written.
+    pub async fn save(&mut self) -> Result<(), Error> {
+        self.flush_usage().await;
         let snapshot = Self::snapshot_from_stores(&self.stores);
-        self.services.repo.save(&snapshot).await
+        let saved = match &self.saved {
+            Some(tracker) => {
+                let (delta, saved) = tracker.diff(&snapshot);
+                self.services.repo.save_delta(&snapshot, &delta).await?;
+                debug!(rows = delta.len(), "state delta saved");
+                saved
+            }
+            None => {
+                self.services.repo.save(&snapshot).await?;
+                DeltaTracker::new(&snapshot)
+            }
+        };
+        self.saved = Some(saved);
+        Ok(())
+    }
+
+    /// Counters and gauges accumulated over the ticks run so far.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Receive the metrics in text exposition format after every tick.
+    pub fn subscribe_metrics(&self) -> watch::Receiver<String> {
+        self.metrics_feed.send_replace(self.metrics.render());
+        self.metrics_feed.subscribe()
     }
 
     /// Read-only access to in-memory stores (useful for tests).
@@ -268,7 +612,7 @@ impl PreloadEngine {
         match event {
             ControlEvent::Reload(bundle) => {
                 self.apply_reload(*bundle);
-                info!("config reloaded");
+                info!(profile = ?self.config.active_profile, "config reloaded");
             }
             ControlEvent::DumpStatus => {
                 self.dump_status();
@@ -291,6 +635,22 @@ impl PreloadEngine {
             );
             bundle.config.persistence.state_path = self.config.persistence.state_path.clone();
         }
+        if bundle.config.persistence.backend != self.config.persistence.backend {
+            warn!(
+                current = ?self.config.persistence.backend,
+                requested = ?bundle.config.persistence.backend,
+                "ignoring persistence backend change during reload"
+            );
+            bundle.config.persistence.backend = self.config.persistence.backend;
+        }
+
+        if !bundle.config.model.cold_edges && !self.stores.cold.is_empty() {
+            info!(
+                edges = self.stores.cold.len(),
+                "cold edges turned off; dropping dormant edges"
+            );
+            self.stores.cold.clear();
+        }
 
         self.config = bundle.config;
         self.services.admission = bundle.admission;
@@ -300,6 +660,28 @@ impl PreloadEngine {
         self.services.prefetcher = bundle.prefetcher;
     }
 
+    /// Trace the per-component breakdown of the highest-scored exes.
+    fn trace_explain(&self, prediction: &Prediction) {
+        const TOP: usize = 5;
+        let mut ranked: Vec<_> = prediction
+            .exe_scores
+            .iter()
+            .map(|(id, score)| (*id, score.to_f32()))
+            .collect();
+        ranked.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        for (exe_id, score) in ranked.into_iter().take(TOP) {
+            let Some(exe) = self.stores.exes.get(exe_id) else {
+                continue;
+            };
+            trace!(
+                exe = %exe.key.path().display(),
+                score,
+                components = ?prediction.explain(exe_id),
+                "prediction explain"
+            );
+        }
+    }
+
     fn dump_status(&self) {
         let exe_count = self.stores.exes.iter().count();
         let map_count = self.stores.maps.iter().count();
@@ -307,6 +689,7 @@ impl PreloadEngine {
         let active_count = self.stores.active.exes().len();
 
         info!(?self.config, "current config");
+        info!(profile = ?self.config.active_profile, "active power profile");
         info!(
             exe_count,
             map_count,
@@ -318,6 +701,31 @@ impl PreloadEngine {
         if let Some(stats) = self.services.admission.stats() {
             info!(?stats, "admission policy stats");
         }
+
+        let metrics = &self.metrics;
+        if metrics.ticks > 0 {
+            let ticks = u32::try_from(metrics.ticks).unwrap_or(u32::MAX);
+            let total = metrics.phase_time.total().as_secs_f64().max(f64::EPSILON);
+            let phases = metrics
+                .phase_time
+                .phases()
+                .iter()
+                .map(|(phase, time)| {
+                    let share = time.as_secs_f64() / total * 100.0;
+                    format!("{phase}={:?} ({share:.0}%)", *time / ticks)
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            info!(
+                ticks = metrics.ticks,
+                mean_tick = ?(metrics.tick_time / ticks),
+                user_cpu = ?metrics.user_cpu,
+                system_cpu = ?metrics.system_cpu,
+                rss_bytes = metrics.rss_bytes,
+                %phases,
+                "tick time summary"
+            );
+        }
     }
 
     fn snapshot_from_stores(stores: &Stores) -> StoresSnapshot {
@@ -367,13 +775,78 @@ impl PreloadEngine {
                 time_to_leave: edge.time_to_leave_f32(),
                 transition_prob: edge.transition_prob_f32(),
                 both_running_time: edge.both_running_time,
+                state: edge.state.index() as u8,
+                last_change_time: edge.last_change_time,
+                state_last_left: *edge.state_last_left,
+            });
+        }
+
+        let mut launch_histograms = Vec::new();
+        for (exe_id, hist) in stores.launches.iter() {
+            let Some(exe) = stores.exes.get(exe_id) else {
+                continue;
+            };
+            launch_histograms.push(LaunchHistogramRecord {
+                exe_path: exe.key.path().to_path_buf(),
+                weights: hist.weights,
+                updated_at: hist.updated_at,
+            });
+        }
+
+        let exe_path = |id| stores.exes.get(id).map(|exe| exe.key.path().to_path_buf());
+        let mut launch_sequences = Vec::new();
+        for (context, seq) in stores.sequences.iter() {
+            let Some(context) = context
+                .iter()
+                .map(|&id| exe_path(id))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let followers = seq
+                .next
+                .iter()
+                .filter_map(|(&id, &weight)| Some((exe_path(id)?, weight)))
+                .collect();
+            launch_sequences.push(LaunchSequenceRecord {
+                context,
+                followers,
+                updated_at: seq.updated_at,
+            });
+        }
+
+        let mut active_exes = Vec::new();
+        for (exe_id, last_active) in stores.active.iter() {
+            let Some(exe_path) = exe_path(exe_id) else {
+                continue;
+            };
+            active_exes.push(ActiveRecord {
+                exe_path,
+                last_active,
+            });
+        }
+
+        let mut cold_markov_edges = Vec::new();
+        for (key, edge) in stores.cold.iter() {
+            let (Some(exe_a), Some(exe_b)) = (exe_path(key.a()), exe_path(key.b())) else {
+                continue;
+            };
+            cold_markov_edges.push(MarkovRecord {
+                exe_a,
+                exe_b,
+                time_to_leave: edge.time_to_leave.map(f16::to_f32),
+                transition_prob: edge.transition_prob.map(|row| row.map(f16::to_f32)),
+                both_running_time: edge.both_running_time,
+                state: 0,
+                last_change_time: 0,
+                state_last_left: [0; 4],
             });
         }
 
         StoresSnapshot {
             meta: SnapshotMeta {
                 schema_version: SNAPSHOT_SCHEMA_VERSION,
-                app_version: None,
+                app_version: Some(env!("CARGO_PKG_VERSION").to_owned()),
                 created_at: Some(SystemTime::now()),
             },
             state: StateSnapshot {
@@ -383,11 +856,15 @@ impl PreloadEngine {
                 maps,
                 exe_maps,
                 markov_edges,
+                launch_histograms,
+                launch_sequences,
+                active_exes,
+                cold_markov_edges,
             },
         }
     }
 
-    fn stores_from_snapshot(snapshot: StoresSnapshot, active_window: u64) -> Result<Stores, Error> {
+    fn stores_from_snapshot(snapshot: StoresSnapshot, cold_edges: bool) -> Result<Stores, Error> {
         let mut stores = Stores {
             model_time: snapshot.state.model_time,
             last_accounting_time: snapshot.state.last_accounting_time,
@@ -408,53 +885,132 @@ impl PreloadEngine {
             }
         }
 
-        // Rebuild active set based on last_seen_time and window.
-        for (exe_id, exe) in stores.exes.iter() {
-            if let Some(last_seen) = exe.last_seen_time
-                && stores.model_time.saturating_sub(last_seen) <= active_window
-            {
-                stores.active.update([exe_id], stores.model_time);
+        // Rows referring to exes or maps that are gone are dropped rather
+        // than failing the whole load.
+        let mut dangling = 0usize;
+        let exe_id_for = |stores: &Stores, path: PathBuf| stores.exes.id_by_key(&ExeKey::new(path));
+
+        for record in snapshot.state.active_exes {
+            match exe_id_for(&stores, record.exe_path) {
+                Some(exe_id) => stores.active.insert(exe_id, record.last_active),
+                None => dangling += 1,
             }
         }
 
         for record in snapshot.state.exe_maps {
-            let exe_key = ExeKey::new(record.exe_path);
-            let map_key = record.map_key;
-            let exe_id = stores
-                .exes
-                .id_by_key(&exe_key)
-                .ok_or_else(|| Error::ExeMissing(exe_key.path().to_path_buf()))?;
-            let map_id = stores
-                .maps
-                .id_by_key(&map_key)
-                .ok_or_else(|| Error::MapMissing(map_key.path.to_path_buf()))?;
+            let exe_id = exe_id_for(&stores, record.exe_path);
+            let map_id = stores.maps.id_by_key(&record.map_key);
+            let (Some(exe_id), Some(map_id)) = (exe_id, map_id) else {
+                dangling += 1;
+                continue;
+            };
             stores.attach_map(exe_id, map_id);
         }
 
         for record in snapshot.state.markov_edges {
-            let exe_a_key = ExeKey::new(record.exe_a);
-            let exe_b_key = ExeKey::new(record.exe_b);
-            let a = stores
-                .exes
-                .id_by_key(&exe_a_key)
-                .ok_or_else(|| Error::ExeMissing(exe_a_key.path().to_path_buf()))?;
-            let b = stores
-                .exes
-                .id_by_key(&exe_b_key)
-                .ok_or_else(|| Error::ExeMissing(exe_b_key.path().to_path_buf()))?;
-            let state = MarkovState::Neither;
+            let a = exe_id_for(&stores, record.exe_a.clone());
+            let b = exe_id_for(&stores, record.exe_b.clone());
+            let (Some(a), Some(b)) = (a, b) else {
+                dangling += 1;
+                continue;
+            };
             let key = crate::stores::EdgeKey::new(a, b);
-            if stores.ensure_markov_edge(a, b, stores.model_time, state)
+            // Exe ids are handed out afresh on load, so the pair may now be
+            // ordered the other way round.
+            let record = if key.a() == a {
+                record
+            } else {
+                record.reversed()
+            };
+            let state =
+                MarkovState::from_index(record.state as usize).unwrap_or(MarkovState::Neither);
+            if stores.ensure_markov_edge(a, b, record.last_change_time, state)
                 && let Some(mut edge) = stores.markov.get_mut(key)
             {
                 edge.set_time_to_leave_f32(record.time_to_leave);
                 edge.set_transition_prob_f32(record.transition_prob);
                 *edge.both_running_time = record.both_running_time;
+                *edge.state_last_left = record.state_last_left;
             }
         }
 
-        let active = stores.active.exes();
-        stores.markov.prune_inactive(&active);
+        // Cold edges are discarded once the option is turned off.
+        let cold_markov_edges = if cold_edges {
+            snapshot.state.cold_markov_edges
+        } else {
+            Vec::new()
+        };
+        for record in cold_markov_edges {
+            let a = exe_id_for(&stores, record.exe_a.clone());
+            let b = exe_id_for(&stores, record.exe_b.clone());
+            let (Some(a), Some(b)) = (a, b) else {
+                dangling += 1;
+                continue;
+            };
+            let key = crate::stores::EdgeKey::new(a, b);
+            // A pair both hot and cold (e.g. after a merge) keeps the hot edge.
+            if stores.markov.contains(key) {
+                continue;
+            }
+            let record = if key.a() == a {
+                record
+            } else {
+                record.reversed()
+            };
+            stores.cold.insert(
+                key,
+                ColdEdge {
+                    time_to_leave: record.time_to_leave.map(f16::from_f32),
+                    transition_prob: record.transition_prob.map(|row| row.map(f16::from_f32)),
+                    both_running_time: record.both_running_time,
+                },
+            );
+        }
+
+        for record in snapshot.state.launch_histograms {
+            let Some(exe_id) = exe_id_for(&stores, record.exe_path) else {
+                dangling += 1;
+                continue;
+            };
+            stores.launches.insert(
+                exe_id,
+                LaunchHistogram {
+                    weights: record.weights,
+                    updated_at: record.updated_at,
+                },
+            );
+        }
+
+        for record in snapshot.state.launch_sequences {
+            let context = record
+                .context
+                .into_iter()
+                .map(|path| exe_id_for(&stores, path))
+                .collect::<Option<Vec<_>>>();
+            let Some(context) = context else {
+                dangling += 1;
+                continue;
+            };
+            let mut seq = SequenceContext::new(record.updated_at);
+            for (path, weight) in record.followers {
+                match exe_id_for(&stores, path) {
+                    Some(exe_id) => {
+                        seq.next.insert(exe_id, weight);
+                    }
+                    None => dangling += 1,
+                }
+            }
+            stores.sequences.insert(context, seq);
+        }
+
+        if dangling > 0 {
+            warn!(
+                dangling,
+                "dropped snapshot rows referring to missing exes or maps"
+            );
+        }
+
+        stores.retire_inactive_edges(cold_edges);
 
         Ok(stores)
     }
@@ -468,7 +1024,10 @@ mod tests {
     use crate::observation::{ModelUpdater, Observation, ObservationEvent, Scanner};
     use crate::persistence::NoopRepository;
     use crate::prediction::{Prediction, Predictor};
-    use crate::prefetch::{PrefetchPlan, PrefetchPlanner, PrefetchReport, Prefetcher};
+    use crate::prefetch::{
+        GreedyPrefetchPlanner, PrefetchPlan, PrefetchPlanner, PrefetchReport, Prefetcher,
+        PressureScale,
+    };
     use crate::stores::EdgeKey;
     use async_trait::async_trait;
     use proptest::prelude::*;
@@ -505,6 +1064,9 @@ mod tests {
                         cached: 1,
                         pagein: 0,
                         pageout: 0,
+                        memory_pressure: None,
+                        io_pressure: None,
+                        cgroup: None,
                     },
                 },
                 ObservationEvent::ObsEnd {
@@ -550,7 +1112,7 @@ mod tests {
     }
 
     impl Predictor for Recording {
-        fn predict(&self, _stores: &Stores) -> Prediction {
+        fn score_exes(&self, _stores: &Stores) -> Prediction {
             self.record();
             Prediction::default()
         }
@@ -562,19 +1124,22 @@ mod tests {
             _prediction: &Prediction,
             _stores: &Stores,
             _memstat: &MemStat,
+            _pressure: PressureScale,
+            _cooldown: &Cooldown,
         ) -> PrefetchPlan {
             self.record();
-            PrefetchPlan {
-                maps: Vec::new(),
-                total_bytes: 0,
-                budget_bytes: 0,
-            }
+            PrefetchPlan::default()
         }
     }
 
     #[async_trait]
     impl Prefetcher for Recording {
-        async fn execute(&self, _plan: &PrefetchPlan, _stores: &Stores) -> PrefetchReport {
+        async fn execute(
+            &self,
+            _plan: &PrefetchPlan,
+            _stores: &Stores,
+            _cancel: &CancellationToken,
+        ) -> PrefetchReport {
             self.record();
             PrefetchReport::default()
         }
@@ -613,6 +1178,7 @@ mod tests {
                     id
                 })
                 .collect();
+            stores.active.update(exe_ids.iter().copied(), model_time);
 
             let map_ids: Vec<_> = (0..map_count)
                 .map(|i| {
@@ -651,7 +1217,7 @@ mod tests {
             }
 
             let snapshot = PreloadEngine::snapshot_from_stores(&stores);
-            let restored = PreloadEngine::stores_from_snapshot(snapshot.clone(), 1_000_000)
+            let restored = PreloadEngine::stores_from_snapshot(snapshot.clone(), false)
                 .expect("rehydrate failed");
 
             let exe_set: HashSet<_> = snapshot
@@ -746,6 +1312,194 @@ mod tests {
         }
     }
 
+    /// Drive the `/bin/a`–`/bin/b` edge to the state given from `/bin/a`'s side.
+    fn step_edge(stores: &mut Stores, a_running: bool, b_running: bool, now: u64) {
+        let a = stores.exes.id_by_key(&ExeKey::new("/bin/a")).unwrap();
+        let b = stores.exes.id_by_key(&ExeKey::new("/bin/b")).unwrap();
+        let key = EdgeKey::new(a, b);
+        let state = if key.a() == a {
+            MarkovState::from_running(a_running, b_running)
+        } else {
+            MarkovState::from_running(b_running, a_running)
+        };
+        stores
+            .markov
+            .get_mut(key)
+            .unwrap()
+            .update_state(state, now, 0.01);
+    }
+
+    /// The edge's persisted form, seen from `/bin/a`.
+    fn edge_record(stores: &Stores) -> MarkovRecord {
+        let record = PreloadEngine::snapshot_from_stores(stores)
+            .state
+            .markov_edges
+            .pop()
+            .unwrap();
+        if record.exe_a == Path::new("/bin/a") {
+            record
+        } else {
+            record.reversed()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn markov_edge_state_survives_snapshot_roundtrip(
+            before in prop::collection::vec((any::<bool>(), any::<bool>(), 1u64..500), 0..12),
+            after in prop::collection::vec((any::<bool>(), any::<bool>(), 1u64..500), 1..12),
+            reorder in any::<bool>(),
+        ) {
+            let mut stores = Stores::default();
+            let a = stores.ensure_exe(ExeKey::new("/bin/a"));
+            let b = stores.ensure_exe(ExeKey::new("/bin/b"));
+            stores.ensure_markov_edge(a, b, 0, MarkovState::Neither);
+            stores.active.update([a, b], 0);
+            let mut now = 0;
+            for (a_running, b_running, dt) in before {
+                now += dt;
+                step_edge(&mut stores, a_running, b_running, now);
+            }
+            stores.model_time = now;
+            for id in [a, b] {
+                stores.exes.get_mut(id).unwrap().last_seen_time = Some(now);
+            }
+
+            let mut snapshot = PreloadEngine::snapshot_from_stores(&stores);
+            // Reversing the exes hands out ids in the other order on load.
+            if reorder {
+                snapshot.state.exes.reverse();
+            }
+            let mut restored = PreloadEngine::stores_from_snapshot(snapshot, false)
+                .expect("rehydrate failed");
+
+            for (a_running, b_running, dt) in after {
+                now += dt;
+                step_edge(&mut stores, a_running, b_running, now);
+                step_edge(&mut restored, a_running, b_running, now);
+            }
+            let (original, restored) = (edge_record(&stores), edge_record(&restored));
+            prop_assert_eq!(original.state, restored.state);
+            prop_assert_eq!(original.last_change_time, restored.last_change_time);
+            prop_assert_eq!(original.state_last_left, restored.state_last_left);
+            prop_assert_eq!(original.time_to_leave, restored.time_to_leave);
+            prop_assert_eq!(original.transition_prob, restored.transition_prob);
+        }
+    }
+
+    #[test]
+    fn active_set_is_restored_exactly() {
+        let mut stores = Stores {
+            model_time: 1_000,
+            ..Default::default()
+        };
+        let a = stores.ensure_exe(ExeKey::new("/bin/a"));
+        let b = stores.ensure_exe(ExeKey::new("/bin/b"));
+        let idle = stores.ensure_exe(ExeKey::new("/bin/idle"));
+        stores.exes.get_mut(idle).unwrap().last_seen_time = Some(1_000);
+        stores.active.update([a], 10);
+        stores.active.update([b], 900);
+        stores.ensure_markov_edge(a, b, 900, MarkovState::Neither);
+
+        let snapshot = PreloadEngine::snapshot_from_stores(&stores);
+        let restored = PreloadEngine::stores_from_snapshot(snapshot, false).unwrap();
+
+        let active: HashMap<_, _> = restored
+            .active
+            .iter()
+            .map(|(id, last)| {
+                (
+                    restored.exes.get(id).unwrap().key.path().to_path_buf(),
+                    last,
+                )
+            })
+            .collect();
+        let expected = HashMap::from([("/bin/a".into(), 10), ("/bin/b".into(), 900)]);
+        assert_eq!(active, expected);
+        assert_eq!(restored.markov.iter().count(), 1);
+    }
+
+    #[test]
+    fn cold_edges_are_kept_and_revived() {
+        let mut stores = Stores::default();
+        let a = stores.ensure_exe(ExeKey::new("/bin/a"));
+        let b = stores.ensure_exe(ExeKey::new("/bin/b"));
+        stores.active.update([a, b], 0);
+        stores.ensure_markov_edge(a, b, 0, MarkovState::Neither);
+        {
+            let mut edge = stores.markov.get_mut(EdgeKey::new(a, b)).unwrap();
+            edge.set_time_to_leave_f32([1.0, 2.0, 3.0, 4.0]);
+            *edge.both_running_time = 42;
+        }
+
+        stores.active.prune(100, 10);
+        stores.retire_inactive_edges(true);
+        assert_eq!(stores.markov.iter().count(), 0);
+        assert_eq!(stores.cold.len(), 1);
+
+        let snapshot = PreloadEngine::snapshot_from_stores(&stores);
+        assert!(snapshot.state.markov_edges.is_empty());
+        let dropped = PreloadEngine::stores_from_snapshot(snapshot.clone(), false).unwrap();
+        assert!(dropped.cold.is_empty());
+        let mut restored = PreloadEngine::stores_from_snapshot(snapshot, true).unwrap();
+        assert_eq!(restored.cold.len(), 1);
+
+        let a = restored.exes.id_by_key(&ExeKey::new("/bin/a")).unwrap();
+        let b = restored.exes.id_by_key(&ExeKey::new("/bin/b")).unwrap();
+        restored.active.update([a, b], 100);
+        assert!(restored.ensure_markov_edge(a, b, 100, MarkovState::Neither));
+        assert!(restored.cold.is_empty());
+        let (_, edge) = restored.markov.iter().next().unwrap();
+        // "Neither" and "both" do not depend on which exe is edge side A.
+        let time_to_leave = edge.time_to_leave_f32();
+        assert_eq!((time_to_leave[0], time_to_leave[3]), (1.0, 4.0));
+        assert_eq!(edge.both_running_time, 42);
+        assert_eq!(edge.last_change_time, 100);
+    }
+
+    #[test]
+    fn dangling_snapshot_rows_are_dropped() {
+        let mut stores = Stores::default();
+        let app = stores.ensure_exe(ExeKey::new("/bin/app"));
+        let map = stores.ensure_map(MapSegment::new("/lib/libapp.so", 0, 4096, 0));
+        stores.attach_map(app, map);
+        let mut snapshot = PreloadEngine::snapshot_from_stores(&stores);
+
+        let gone = std::path::PathBuf::from("/bin/gone");
+        snapshot.state.exe_maps.push(ExeMapRecord {
+            exe_path: gone.clone(),
+            map_key: MapKey::new("/lib/libapp.so", 0, 4096),
+            prob: 1.0,
+        });
+        snapshot.state.exe_maps.push(ExeMapRecord {
+            exe_path: "/bin/app".into(),
+            map_key: MapKey::new("/lib/gone.so", 0, 4096),
+            prob: 1.0,
+        });
+        snapshot.state.markov_edges.push(MarkovRecord {
+            exe_a: "/bin/app".into(),
+            exe_b: gone.clone(),
+            time_to_leave: [0.0; 4],
+            transition_prob: [[0.0; 4]; 4],
+            both_running_time: 0,
+            state: 0,
+            last_change_time: 0,
+            state_last_left: [0; 4],
+        });
+        snapshot.state.launch_sequences.push(LaunchSequenceRecord {
+            context: vec![gone],
+            followers: Vec::new(),
+            updated_at: 0,
+        });
+
+        let restored = PreloadEngine::stores_from_snapshot(snapshot, false).unwrap();
+        let app = restored.exes.id_by_key(&ExeKey::new("/bin/app")).unwrap();
+        assert_eq!(restored.exes.iter().count(), 1);
+        assert_eq!(restored.exe_maps.maps_for_exe(app).count(), 1);
+        assert_eq!(restored.markov.iter().count(), 0);
+        assert!(restored.sequences.is_empty());
+    }
+
     #[tokio::test]
     async fn reload_swaps_runtime_services() {
         let mut config = Config::default();
@@ -830,6 +1584,216 @@ mod tests {
         assert_eq!(prefetcher_hits.load(Ordering::SeqCst), 2);
     }
 
+    #[derive(Debug, Default)]
+    struct PressuredScanner;
+
+    impl Scanner for PressuredScanner {
+        fn scan(&mut self, time: u64, scan_id: u64) -> Result<Observation, Error> {
+            let mut observation = StaticScanner.scan(time, scan_id)?;
+            for event in &mut observation {
+                if let ObservationEvent::MemStat { mem } = event {
+                    mem.memory_pressure = Some(crate::domain::Psi {
+                        some: 90.0,
+                        full: 90.0,
+                    });
+                }
+            }
+            Ok(observation)
+        }
+    }
+
+    #[tokio::test]
+    async fn pressure_scale_survives_reload() {
+        let mut config = Config::default();
+        config.system.dopredict = true;
+        let recording = || Recording {
+            id: 1,
+            hits: Arc::new(AtomicU32::new(0)),
+        };
+        let services = Services {
+            scanner: Box::new(PressuredScanner),
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(recording()),
+            prefetcher: Box::new(recording()),
+            repo: Box::new(NoopRepository),
+            clock: Box::new(crate::clock::SystemClock),
+        };
+        let mut engine = PreloadEngine::new(config.clone(), services)
+            .await
+            .expect("engine");
+        engine.tick().await.expect("tick");
+        assert_eq!(engine.pressure.get(), 0.0);
+
+        engine.apply_reload(ReloadBundle {
+            config,
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(recording()),
+            prefetcher: Box::new(recording()),
+        });
+        assert_eq!(engine.pressure.get(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn reload_keeps_the_state_path_and_backend() {
+        let mut config = Config::default();
+        config.persistence.state_path = Some(PathBuf::from("/var/lib/preload/state.db"));
+        let recording = || Recording {
+            id: 1,
+            hits: Arc::new(AtomicU32::new(0)),
+        };
+        let services = Services {
+            scanner: Box::new(StaticScanner),
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+            prefetcher: Box::new(recording()),
+            repo: Box::new(NoopRepository),
+            clock: Box::new(crate::clock::SystemClock),
+        };
+        let mut engine = PreloadEngine::new(config.clone(), services)
+            .await
+            .expect("engine");
+
+        let mut reloaded = config.clone();
+        reloaded.persistence.state_path = Some(PathBuf::from("/tmp/state.rkyv"));
+        reloaded.persistence.backend = config::PersistenceBackend::Rkyv;
+        engine.apply_reload(ReloadBundle {
+            config: reloaded,
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+            prefetcher: Box::new(recording()),
+        });
+
+        assert_eq!(
+            engine.config.persistence.state_path,
+            config.persistence.state_path
+        );
+        assert_eq!(
+            engine.config.persistence.backend,
+            config::PersistenceBackend::Sqlite
+        );
+    }
+
+    #[tokio::test]
+    async fn prefetch_cooldown_survives_reload() {
+        let mut config = Config::default();
+        config.system.residency_aware = false;
+        config.model.memory.memtotal = 0;
+        config.model.memory.memavailable = 100;
+        let recording = || Recording {
+            id: 1,
+            hits: Arc::new(AtomicU32::new(0)),
+        };
+        let services = Services {
+            scanner: Box::new(StaticScanner),
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+            prefetcher: Box::new(recording()),
+            repo: Box::new(NoopRepository),
+            clock: Box::new(crate::clock::SystemClock),
+        };
+        let mut engine = PreloadEngine::new(config.clone(), services)
+            .await
+            .expect("engine");
+        let map = MapSegment::new("/usr/lib/libfoo.so", 0, 4096, 0);
+        let map_id = engine.stores.ensure_map(map.clone());
+        let report = PrefetchReport {
+            prefetched: vec![map.key()],
+            ..Default::default()
+        };
+        engine
+            .services
+            .planner
+            .record_prefetch(&report, &engine.cooldown)
+            .expect("cooldown recorded")();
+
+        engine.apply_reload(ReloadBundle {
+            config: config.clone(),
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+            prefetcher: Box::new(recording()),
+        });
+
+        let mut prediction = Prediction::default();
+        prediction
+            .map_scores
+            .insert(map_id, half::f16::from_f32(0.9));
+        let mem = MemStat {
+            total: 1024,
+            available: 1024,
+            free: 1024,
+            cached: 0,
+            pagein: 0,
+            pageout: 0,
+            memory_pressure: None,
+            io_pressure: None,
+            cgroup: None,
+        };
+        let plan = engine.services.planner.plan(
+            &prediction,
+            &engine.stores,
+            &mem,
+            engine.pressure,
+            &engine.cooldown,
+        );
+        assert!(plan.maps.is_empty());
+        assert_eq!(plan.cooldown_skipped, 1);
+    }
+
+    #[tokio::test]
+    async fn reload_without_cold_edges_drops_them() {
+        let mut config = Config::default();
+        config.model.cold_edges = true;
+        let recording = || Recording {
+            id: 1,
+            hits: Arc::new(AtomicU32::new(0)),
+        };
+        let services = Services {
+            scanner: Box::new(StaticScanner),
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(recording()),
+            prefetcher: Box::new(recording()),
+            repo: Box::new(NoopRepository),
+            clock: Box::new(crate::clock::SystemClock),
+        };
+        let mut engine = PreloadEngine::new(config.clone(), services)
+            .await
+            .expect("engine");
+        let a = engine.stores.ensure_exe(ExeKey::new("/bin/a"));
+        let b = engine.stores.ensure_exe(ExeKey::new("/bin/b"));
+        engine.stores.active.update([a, b], 0);
+        engine
+            .stores
+            .ensure_markov_edge(a, b, 0, MarkovState::Neither);
+        engine.stores.active.prune(100, 10);
+        engine.stores.retire_inactive_edges(true);
+        assert_eq!(engine.stores.cold.len(), 1);
+
+        config.model.cold_edges = false;
+        engine.apply_reload(ReloadBundle {
+            config,
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(recording()),
+            prefetcher: Box::new(recording()),
+        });
+        assert!(engine.stores.cold.is_empty());
+    }
+
     fn edge_strategy() -> impl Strategy<Value = (u8, u8, [f32; 4], [[f32; 4]; 4], u64)> {
         (
             0u8..16,
@@ -858,6 +1822,63 @@ mod tests {
         }
     }
 
+    /// Records the size of every usage history batch written.
+    #[derive(Debug, Default)]
+    struct UsageRepo {
+        batches: Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait]
+    impl StateRepository for UsageRepo {
+        async fn load(&self) -> Result<StoresSnapshot, Error> {
+            NoopRepository.load().await
+        }
+        async fn save(&self, _snapshot: &StoresSnapshot) -> Result<(), Error> {
+            Ok(())
+        }
+        async fn record_usage(
+            &self,
+            records: &[UsageRecord],
+            _retention: Duration,
+        ) -> Result<(), Error> {
+            self.batches.lock().unwrap().push(records.len());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn usage_history_is_written_with_the_save() {
+        let mut config = Config::default();
+        config.persistence.history.enabled = true;
+        config.persistence.autosave_interval = Some(Duration::ZERO);
+        let recording = || Recording {
+            id: 1,
+            hits: Arc::new(AtomicU32::new(0)),
+        };
+        let repo = UsageRepo::default();
+        let batches = repo.batches.clone();
+        let services = Services {
+            scanner: Box::new(StaticScanner),
+            admission: Box::new(recording()),
+            updater: Box::new(recording()),
+            predictor: Box::new(recording()),
+            planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+            prefetcher: Box::new(recording()),
+            repo: Box::new(repo),
+            clock: Box::new(crate::clock::SystemClock),
+        };
+        let mut engine = PreloadEngine::new(config, services).await.expect("engine");
+
+        for _ in 0..3 {
+            engine.tick().await.expect("tick");
+        }
+        assert!(batches.lock().unwrap().is_empty());
+
+        engine.save().await.expect("save");
+        engine.save().await.expect("save");
+        assert_eq!(*batches.lock().unwrap(), vec![3]);
+    }
+
     /// A repository that returns a fixed snapshot.
     struct FixedRepo(StoresSnapshot);
 
@@ -873,18 +1894,13 @@ mod tests {
 
     #[tokio::test]
     async fn load_purges_maps_denied_by_policy() {
+        use crate::domain::MapKey;
         use crate::persistence::{
-            ExeRecord, MapRecord, ExeMapRecord, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta,
-            StateSnapshot, StoresSnapshot,
+            ExeMapRecord, ExeRecord, MapRecord, SnapshotMeta, StateSnapshot, StoresSnapshot,
         };
-        use crate::domain::MapKey;
 
         let snapshot = StoresSnapshot {
-            meta: SnapshotMeta {
-                schema_version: SNAPSHOT_SCHEMA_VERSION,
-                app_version: None,
-                created_at: None,
-            },
+            meta: SnapshotMeta::default(),
             state: StateSnapshot {
                 model_time: 100,
                 last_accounting_time: 100,
@@ -919,7 +1935,7 @@ mod tests {
                         prob: 1.0,
                     },
                 ],
-                markov_edges: Vec::new(),
+                ..StateSnapshot::default()
             },
         };
 
@@ -971,11 +1987,7 @@ mod tests {
         // Verify the exe-map link for the denied map was also cleaned up.
         let exe_key = ExeKey::new("/usr/bin/app");
         let exe_id = engine.stores.exes.id_by_key(&exe_key).unwrap();
-        let linked_maps: Vec<_> = engine
-            .stores
-            .exe_maps
-            .maps_for_exe(exe_id)
-            .collect();
+        let linked_maps: Vec<_> = engine.stores.exe_maps.maps_for_exe(exe_id).collect();
         assert_eq!(linked_maps.len(), 1, "only allowed map link should remain");
     }
 }
@@ -16,6 +16,18 @@ pub enum Error {
     #[error("sqlx error: {0}")]
     Sqlx(#[from] sqlx::Error),
 
+    #[error("corrupt state database: {0}")]
+    CorruptState(String),
+
+    #[error("state schema version {found} is newer than supported version {supported}")]
+    NewerSchema { found: u32, supported: u32 },
+
+    #[error("state database {} lacks migration {missing}; start the daemon once to migrate it", path.display())]
+    UnmigratedState { path: PathBuf, missing: i64 },
+
+    #[error("unsupported export: format {format:?} version {version}")]
+    UnsupportedExport { format: String, version: u32 },
+
     #[error("serialization error: {0}")]
     RkyvSerialize(String),
 
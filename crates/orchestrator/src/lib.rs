@@ -5,6 +5,7 @@ pub mod domain;
 pub mod engine;
 pub mod error;
 pub(crate) mod math;
+pub mod metrics;
 pub mod observation;
 pub mod persistence;
 pub mod prediction;
@@ -12,17 +13,21 @@ pub mod prefetch;
 pub mod stores;
 
 pub use engine::{ControlEvent, PreloadEngine, ReloadBundle, Services, TickReport};
+pub use metrics::{Metrics, PhaseTimings};
 pub use observation::{
     AdmissionDecision, AdmissionPolicy, AdmissionPolicyStats, CandidateExe, Completeness,
-    DefaultAdmissionPolicy, DefaultModelUpdater, FanotifyWatcher, ModelDelta, ModelUpdater,
-    Observation, ObservationEvent, ProcfsScanner, RejectReason, ScanWarning, Scanner,
+    DefaultAdmissionPolicy, DefaultModelUpdater, FanotifyStats, FanotifyWatcher, ModelDelta,
+    ModelUpdater, Observation, ObservationEvent, ProcfsScanner, RejectReason, ScanWarning, Scanner,
 };
 pub use persistence::{NoopRepository, SqliteRepository, StateRepository, StoresSnapshot};
-pub use prediction::{MarkovPredictor, Prediction, PredictionSummary, Predictor};
+pub use prediction::{
+    ComponentScores, CompositePredictor, FrequencyPredictor, MarkovPredictor, Prediction,
+    PredictionSummary, Predictor, SequencePredictor, TimeOfDayPredictor,
+};
 pub use prefetch::{
-    GreedyPrefetchPlanner, MadvisePrefetcher, NoopPrefetcher, PosixFadvisePrefetcher,
-    PrefetchPlan, PrefetchPlanner, PrefetchReport, Prefetcher, ReadPrefetcher,
-    ReadaheadPrefetcher,
+    DensityPrefetchPlanner, GreedyPrefetchPlanner, MadvisePrefetcher, NoopPrefetcher, PlanEntry,
+    PlanPreview, PosixFadvisePrefetcher, PrefetchPlan, PrefetchPlanner, PrefetchReport, Prefetcher,
+    ReadPrefetcher, ReadaheadPrefetcher, ThrottleSettings,
 };
 
 pub use clock::{Clock, SystemClock};
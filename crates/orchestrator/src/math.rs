@@ -19,7 +19,7 @@ pub fn fast_exp_neg(x: f32) -> f32 {
         return 0.0;
     }
 
-    const LOG2E: f32 = 1.442_695_04;
+    const LOG2E: f32 = std::f32::consts::LOG2_E;
     const LN2_HI: f32 = 0.693_145_75;
     const LN2_LO: f32 = 1.428_606_8e-6;
 
@@ -29,7 +29,8 @@ pub fn fast_exp_neg(x: f32) -> f32 {
     let r = x - n * LN2_HI - n * LN2_LO;
 
     // exp(r) ≈ 1 + r + r²/2 + r³/6 + r⁴/24 + r⁵/120  (Horner form)
-    let p = 1.0 + r * (1.0 + r * (0.5 + r * (1.666_666_7e-1 + r * (4.166_666_8e-2 + r * 8.333_334e-3))));
+    let p = 1.0
+        + r * (1.0 + r * (0.5 + r * (1.666_666_7e-1 + r * (4.166_666_8e-2 + r * 8.333_334e-3))));
 
     // Reconstruct: 2^n via IEEE 754 bit manipulation (safe, no UB)
     let ni = n as i32;
@@ -0,0 +1,12 @@
+#![forbid(unsafe_code)]
+
+mod registry;
+mod resources;
+mod server;
+mod textfile;
+
+pub use registry::{Metrics, PhaseTimings};
+pub(crate) use resources::CpuTimes;
+pub use resources::ResourceUsage;
+pub use server::serve;
+pub(crate) use textfile::write_textfile;
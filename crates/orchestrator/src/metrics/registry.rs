@@ -0,0 +1,320 @@
+#![forbid(unsafe_code)]
+
+use crate::metrics::ResourceUsage;
+use crate::observation::{AdmissionPolicyStats, FanotifyStats};
+use crate::prefetch::{PrefetchPlan, PrefetchReport};
+use crate::stores::Stores;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Time spent in each phase of one tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTimings {
+    /// Procfs scan, excluding the fanotify drain.
+    pub scan: Duration,
+    /// Draining events buffered by the fanotify watcher.
+    pub fanotify: Duration,
+    pub update: Duration,
+    pub predict: Duration,
+    pub plan: Duration,
+    pub prefetch: Duration,
+    /// Autosave, when due this tick.
+    pub save: Duration,
+}
+
+impl PhaseTimings {
+    /// Phase names paired with their durations, in tick order.
+    pub fn phases(&self) -> [(&'static str, Duration); 7] {
+        [
+            ("scan", self.scan),
+            ("fanotify", self.fanotify),
+            ("update", self.update),
+            ("predict", self.predict),
+            ("plan", self.plan),
+            ("prefetch", self.prefetch),
+            ("save", self.save),
+        ]
+    }
+
+    /// Sum of all phases.
+    pub fn total(&self) -> Duration {
+        self.phases().iter().map(|(_, time)| *time).sum()
+    }
+
+    fn accumulate(&mut self, other: &PhaseTimings) {
+        self.scan += other.scan;
+        self.fanotify += other.fanotify;
+        self.update += other.update;
+        self.predict += other.predict;
+        self.plan += other.plan;
+        self.prefetch += other.prefetch;
+        self.save += other.save;
+    }
+}
+
+/// Counters and gauges kept by the engine. Counters accumulate since the
+/// engine started; gauges describe the latest tick.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub ticks: u64,
+    /// Total wall time spent in ticks.
+    pub tick_time: Duration,
+    /// Total wall time spent in each phase.
+    pub phase_time: PhaseTimings,
+    /// Total CPU time spent in ticks.
+    pub user_cpu: Duration,
+    pub system_cpu: Duration,
+    pub rss_bytes: u64,
+    pub exes: usize,
+    pub maps: usize,
+    pub markov_edges: usize,
+    pub active_exes: usize,
+    pub plan_maps: usize,
+    pub plan_bytes: u64,
+    pub budget_bytes: u64,
+    pub prefetched_bytes: u64,
+    pub prefetch_failures: u64,
+    /// Latest admission policy stats, whose counters are cumulative.
+    pub admission: Option<AdmissionPolicyStats>,
+    pub fanotify: Option<FanotifyStats>,
+}
+
+impl Metrics {
+    pub(crate) fn record_tick(
+        &mut self,
+        elapsed: Duration,
+        timings: &PhaseTimings,
+        resources: &ResourceUsage,
+        stores: &Stores,
+        plan: &PrefetchPlan,
+        prefetch: &PrefetchReport,
+    ) {
+        self.ticks += 1;
+        self.tick_time += elapsed;
+        self.phase_time.accumulate(timings);
+        self.user_cpu += resources.user_cpu;
+        self.system_cpu += resources.system_cpu;
+        self.rss_bytes = resources.rss_bytes;
+        self.exes = stores.exes.iter().count();
+        self.maps = stores.maps.iter().count();
+        self.markov_edges = stores.markov.iter().count();
+        self.active_exes = stores.active.exes().len();
+        self.plan_maps = plan.maps.len();
+        self.plan_bytes = plan.total_bytes;
+        self.budget_bytes = plan.budget_bytes;
+        self.prefetched_bytes += prefetch.read_bytes;
+        self.prefetch_failures += prefetch.failures.len() as u64;
+    }
+
+    /// Render in the Prometheus text exposition format (version 0.0.4),
+    /// as read by Prometheus and node_exporter's textfile collector.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        counter(
+            &mut out,
+            "preload_ticks_total",
+            "Ticks completed.",
+            self.ticks,
+        );
+        family(
+            &mut out,
+            "preload_tick_duration_seconds",
+            "summary",
+            "Wall time spent in ticks.",
+        );
+        sample(
+            &mut out,
+            "preload_tick_duration_seconds_sum",
+            "",
+            self.tick_time.as_secs_f64(),
+        );
+        sample(
+            &mut out,
+            "preload_tick_duration_seconds_count",
+            "",
+            self.ticks,
+        );
+        family(
+            &mut out,
+            "preload_phase_duration_seconds_total",
+            "counter",
+            "Wall time spent in each tick phase.",
+        );
+        for (phase, time) in self.phase_time.phases() {
+            sample(
+                &mut out,
+                "preload_phase_duration_seconds_total",
+                &format!("{{phase=\"{phase}\"}}"),
+                time.as_secs_f64(),
+            );
+        }
+        family(
+            &mut out,
+            "preload_cpu_seconds_total",
+            "counter",
+            "CPU time spent in ticks.",
+        );
+        for (mode, time) in [("user", self.user_cpu), ("system", self.system_cpu)] {
+            sample(
+                &mut out,
+                "preload_cpu_seconds_total",
+                &format!("{{mode=\"{mode}\"}}"),
+                time.as_secs_f64(),
+            );
+        }
+        gauge(
+            &mut out,
+            "preload_resident_memory_bytes",
+            "Resident set size after the latest tick.",
+            self.rss_bytes,
+        );
+
+        gauge(
+            &mut out,
+            "preload_exes",
+            "Exes tracked by the model.",
+            self.exes,
+        );
+        gauge(
+            &mut out,
+            "preload_maps",
+            "Maps tracked by the model.",
+            self.maps,
+        );
+        gauge(
+            &mut out,
+            "preload_markov_edges",
+            "Markov edges between exes.",
+            self.markov_edges,
+        );
+        gauge(
+            &mut out,
+            "preload_active_exes",
+            "Exes in the active set.",
+            self.active_exes,
+        );
+        gauge(
+            &mut out,
+            "preload_plan_maps",
+            "Maps in the latest prefetch plan.",
+            self.plan_maps,
+        );
+        gauge(
+            &mut out,
+            "preload_plan_bytes",
+            "Bytes charged by the latest prefetch plan.",
+            self.plan_bytes,
+        );
+        gauge(
+            &mut out,
+            "preload_plan_budget_bytes",
+            "Budget of the latest prefetch plan.",
+            self.budget_bytes,
+        );
+        counter(
+            &mut out,
+            "preload_prefetched_bytes_total",
+            "Bytes issued for reading by prefetch.",
+            self.prefetched_bytes,
+        );
+        counter(
+            &mut out,
+            "preload_prefetch_failures_total",
+            "Maps that failed to prefetch.",
+            self.prefetch_failures,
+        );
+
+        if let Some(stats) = &self.admission {
+            counter(
+                &mut out,
+                "preload_admission_cache_hits_total",
+                "Admission decisions answered from the rejection cache.",
+                stats.cache_hits,
+            );
+            counter(
+                &mut out,
+                "preload_admission_cache_misses_total",
+                "Admission decisions that missed the rejection cache.",
+                stats.cache_misses,
+            );
+        }
+        if let Some(stats) = &self.fanotify {
+            counter(
+                &mut out,
+                "preload_fanotify_events_total",
+                "Events read from the fanotify queue.",
+                stats.events,
+            );
+            counter(
+                &mut out,
+                "preload_fanotify_overflows_total",
+                "Fanotify queue overflows, each dropping events.",
+                stats.overflows,
+            );
+        }
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "{name}{labels} {value}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    family(out, name, "counter", help);
+    sample(out, name, "", value);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    family(out, name, "gauge", help);
+    sample(out, name, "", value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_lists_every_family_once() {
+        let metrics = Metrics {
+            ticks: 2,
+            tick_time: Duration::from_millis(1500),
+            phase_time: PhaseTimings {
+                scan: Duration::from_millis(250),
+                ..PhaseTimings::default()
+            },
+            fanotify: Some(FanotifyStats {
+                events: 7,
+                overflows: 1,
+                ..FanotifyStats::default()
+            }),
+            ..Metrics::default()
+        };
+        let text = metrics.render();
+
+        assert!(text.contains("preload_ticks_total 2\n"));
+        assert!(text.contains("preload_tick_duration_seconds_sum 1.5\n"));
+        assert!(text.contains("preload_tick_duration_seconds_count 2\n"));
+        assert!(text.contains("preload_phase_duration_seconds_total{phase=\"scan\"} 0.25\n"));
+        assert!(text.contains("preload_fanotify_overflows_total 1\n"));
+        assert!(!text.contains("preload_admission_cache_hits_total"));
+
+        let mut names: Vec<_> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE "))
+            .map(|line| line.split(' ').next().unwrap())
+            .collect();
+        let count = names.len();
+        names.dedup();
+        assert_eq!(names.len(), count);
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            let (_, value) = line.rsplit_once(' ').unwrap();
+            assert!(value.parse::<f64>().is_ok(), "{line}");
+        }
+    }
+}
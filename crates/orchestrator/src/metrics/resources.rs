@@ -0,0 +1,57 @@
+#![forbid(unsafe_code)]
+
+use nix::sys::resource::{UsageWho, getrusage};
+use nix::sys::time::TimeVal;
+use procfs::page_size;
+use procfs::process::Process;
+use std::time::Duration;
+
+/// CPU time and memory used by the daemon during one tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// User CPU time spent during the tick, across all threads.
+    pub user_cpu: Duration,
+    /// System CPU time spent during the tick, across all threads.
+    pub system_cpu: Duration,
+    /// Resident set size at the end of the tick.
+    pub rss_bytes: u64,
+}
+
+/// Cumulative CPU time of the process, from `getrusage(RUSAGE_SELF)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CpuTimes {
+    user: Duration,
+    system: Duration,
+}
+
+impl CpuTimes {
+    pub(crate) fn now() -> Self {
+        match getrusage(UsageWho::RUSAGE_SELF) {
+            Ok(usage) => Self {
+                user: duration(usage.user_time()),
+                system: duration(usage.system_time()),
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Usage between `self` and now, with the current resident set size.
+    pub(crate) fn usage_since(&self) -> ResourceUsage {
+        let now = Self::now();
+        ResourceUsage {
+            user_cpu: now.user.saturating_sub(self.user),
+            system_cpu: now.system.saturating_sub(self.system),
+            rss_bytes: resident_bytes().unwrap_or(0),
+        }
+    }
+}
+
+fn duration(time: TimeVal) -> Duration {
+    Duration::from_secs(time.tv_sec().max(0) as u64)
+        + Duration::from_micros(time.tv_usec().max(0) as u64)
+}
+
+fn resident_bytes() -> Option<u64> {
+    let statm = Process::myself().ok()?.statm().ok()?;
+    Some(statm.resident * page_size())
+}
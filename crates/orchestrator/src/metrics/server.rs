@@ -0,0 +1,117 @@
+#![forbid(unsafe_code)]
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// Longest request head read before answering.
+const MAX_REQUEST: usize = 8 * 1024;
+/// Longest wait for the request head; slow or idle clients are dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Answer HTTP `GET` requests on `listener` with the latest text from
+/// `feed`, on any path, until `cancel` fires.
+pub async fn serve(
+    listener: TcpListener,
+    feed: watch::Receiver<String>,
+    cancel: CancellationToken,
+) {
+    loop {
+        let stream = tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    warn!(%err, "metrics accept failed");
+                    continue;
+                }
+            },
+        };
+        let body = feed.borrow().clone();
+        tokio::spawn(async move {
+            if let Err(err) = respond(stream, &body).await {
+                debug!(%err, "metrics request failed");
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, body: &str) -> std::io::Result<()> {
+    let request = match timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(request) => request?,
+        Err(elapsed) => return Err(elapsed.into()),
+    };
+    let Some(request) = request else {
+        return Ok(());
+    };
+
+    let response = if request.starts_with(b"GET ") {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\nContent-Length: 0\r\n\
+         Connection: close\r\n\r\n"
+            .to_string()
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read up to the end of the request head; None if the client closed the
+/// connection or sent more than [`MAX_REQUEST`] bytes first.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Vec<u8>>> {
+    let mut request = Vec::with_capacity(1024);
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    Ok(Some(request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_latest_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (feed, rx) = watch::channel(String::from("preload_ticks_total 1\n"));
+        let cancel = CancellationToken::new();
+        let server = tokio::spawn(serve(listener, rx, cancel.clone()));
+
+        let response = request(addr, "GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\npreload_ticks_total 1\n"));
+
+        feed.send_replace(String::from("preload_ticks_total 2\n"));
+        let response = request(addr, "GET / HTTP/1.0\r\n\r\n").await;
+        assert!(response.ends_with("preload_ticks_total 2\n"));
+
+        let response = request(addr, "POST / HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 405"));
+
+        cancel.cancel();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,15 @@
+#![forbid(unsafe_code)]
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Replace `path` with `contents` via a temporary sibling, so collectors
+/// never read a partly written file. The temporary name does not end in
+/// `.prom` and is ignored by node_exporter.
+pub(crate) fn write_textfile(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
@@ -0,0 +1,135 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::CgroupMemory;
+use std::path::Path;
+
+/// Mount point of the cgroup v2 unified hierarchy.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Read the memory limits and usage of our own cgroup. Returns `None` when
+/// not running under cgroup v2 or the memory controller is unavailable.
+pub(crate) fn read_cgroup_memory() -> Option<CgroupMemory> {
+    let membership = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let relative = unified_path(&membership)?;
+    read_from(Path::new(CGROUP_ROOT), relative)
+}
+
+/// The cgroup v2 path from `/proc/self/cgroup` (the `0::<path>` entry).
+fn unified_path(membership: &str) -> Option<&str> {
+    membership
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(str::trim)
+}
+
+/// Read the tightest `memory.max` and `memory.high` along the path from
+/// `relative` up to `root`, since any ancestor's limit also applies to us,
+/// and the headroom left under them. An ancestor's limit covers its whole
+/// subtree, so each level's headroom is its limit minus its own
+/// `memory.current`. Values are in KB.
+fn read_from(root: &Path, relative: &str) -> Option<CgroupMemory> {
+    let leaf = root.join(relative.trim_start_matches('/'));
+    let leaf_current = read_value(&leaf.join("memory.current"))??;
+
+    let mut max = None;
+    let mut high = None;
+    let mut headroom = None;
+    let mut dir = leaf.as_path();
+    while dir.starts_with(root) && dir != root {
+        let level_max = read_value(&dir.join("memory.max")).flatten();
+        let level_high = read_value(&dir.join("memory.high")).flatten();
+        if let Some(limit) = tighter(level_max, level_high) {
+            // A level's usage is at least that of the leaf below it.
+            let current = read_value(&dir.join("memory.current"))
+                .flatten()
+                .unwrap_or(leaf_current)
+                .max(leaf_current);
+            headroom = tighter(headroom, Some(limit.saturating_sub(current)));
+        }
+        max = tighter(max, level_max);
+        high = tighter(high, level_high);
+        dir = dir.parent()?;
+    }
+
+    Some(CgroupMemory {
+        max: max.map(|bytes| bytes / 1024),
+        high: high.map(|bytes| bytes / 1024),
+        headroom: headroom.map(|bytes| bytes / 1024),
+    })
+}
+
+/// Parse a cgroup memory file: `Some(None)` for `max` (unlimited),
+/// `None` if the file is missing or malformed.
+fn read_value(path: &Path) -> Option<Option<u64>> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    match raw.trim() {
+        "max" => Some(None),
+        value => value.parse().ok().map(Some),
+    }
+}
+
+fn tighter(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn unified_path_finds_v2_entry() {
+        let membership = "12:cpu:/legacy\n0::/system.slice/preload-ng.service\n";
+        assert_eq!(
+            unified_path(membership),
+            Some("/system.slice/preload-ng.service")
+        );
+        assert_eq!(unified_path("3:memory:/legacy\n"), None);
+    }
+
+    #[test]
+    fn reads_tightest_limit_along_path() {
+        let root = tempdir().unwrap();
+        let slice = root.path().join("system.slice");
+        let service = slice.join("preload-ng.service");
+        std::fs::create_dir_all(&service).unwrap();
+        std::fs::write(slice.join("memory.max"), "1048576\n").unwrap();
+        std::fs::write(slice.join("memory.high"), "max\n").unwrap();
+        std::fs::write(service.join("memory.max"), "max\n").unwrap();
+        std::fs::write(service.join("memory.high"), "2097152\n").unwrap();
+        std::fs::write(service.join("memory.current"), "262144\n").unwrap();
+
+        // Without the slice's own usage, only ours counts against it.
+        let memory = read_from(root.path(), "/system.slice/preload-ng.service").unwrap();
+        assert_eq!(
+            memory,
+            CgroupMemory {
+                max: Some(1024),
+                high: Some(2048),
+                headroom: Some(768),
+            }
+        );
+
+        // Siblings in the slice use up most of its limit.
+        std::fs::write(slice.join("memory.current"), "917504\n").unwrap();
+        let memory = read_from(root.path(), "/system.slice/preload-ng.service").unwrap();
+        assert_eq!(
+            memory,
+            CgroupMemory {
+                max: Some(1024),
+                high: Some(2048),
+                headroom: Some(128),
+            }
+        );
+        assert_eq!(memory.limit(), Some(1024));
+    }
+
+    #[test]
+    fn missing_controller_yields_none() {
+        let root = tempdir().unwrap();
+        assert_eq!(read_from(root.path(), "/"), None);
+    }
+}
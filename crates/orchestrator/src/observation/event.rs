@@ -3,6 +3,7 @@
 use crate::domain::{MapSegment, MemStat};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 #[derive(Debug, Clone)]
 pub enum ObservationEvent {
@@ -21,6 +22,10 @@ pub enum ObservationEvent {
     MemStat {
         mem: MemStat,
     },
+    /// Wall-clock time at which the scan was taken.
+    WallTime {
+        time: SystemTime,
+    },
     ObsEnd {
         time: u64,
         scan_id: u64,
@@ -7,9 +7,10 @@ use rustc_hash::FxHashMap;
 use std::os::fd::AsRawFd;
 use std::os::linux::fs::MetadataExt;
 use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tracing::{info, trace, warn};
 
 const SKIP_PREFIXES: &[&str] = &[
@@ -35,8 +36,27 @@ struct EventBuffer {
     exes: FxHashMap<Arc<Path>, u32>,
 }
 
+#[derive(Default)]
+struct Counters {
+    events: AtomicU64,
+    overflows: AtomicU64,
+    drain_nanos: AtomicU64,
+}
+
+/// Cumulative counters since the watcher started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FanotifyStats {
+    /// Events read from the fanotify queue, including ignored ones.
+    pub events: u64,
+    /// Queue overflow notifications; the kernel dropped events each time.
+    pub overflows: u64,
+    /// Time spent in [`FanotifyWatcher::drain`].
+    pub drain_time: Duration,
+}
+
 pub struct FanotifyWatcher {
     buffer: Arc<Mutex<EventBuffer>>,
+    counters: Arc<Counters>,
     stop: Arc<AtomicBool>,
     handle: Option<JoinHandle<()>>,
 }
@@ -73,14 +93,16 @@ impl FanotifyWatcher {
         }
 
         let buffer = Arc::new(Mutex::new(EventBuffer::default()));
+        let counters = Arc::new(Counters::default());
         let stop = Arc::new(AtomicBool::new(false));
 
         let handle = {
             let buffer = Arc::clone(&buffer);
+            let counters = Arc::clone(&counters);
             let stop = Arc::clone(&stop);
             match std::thread::Builder::new()
                 .name("fanotify-reader".into())
-                .spawn(move || Self::reader_loop(fan, buffer, stop))
+                .spawn(move || Self::reader_loop(fan, buffer, counters, stop))
             {
                 Ok(h) => h,
                 Err(err) => {
@@ -93,6 +115,7 @@ impl FanotifyWatcher {
         info!("fanotify watcher started");
         Some(Arc::new(Self {
             buffer,
+            counters,
             stop,
             handle: Some(handle),
         }))
@@ -101,6 +124,7 @@ impl FanotifyWatcher {
     fn reader_loop(
         fan: Fanotify,
         buffer: Arc<Mutex<EventBuffer>>,
+        counters: Arc<Counters>,
         stop: Arc<AtomicBool>,
     ) {
         use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
@@ -131,8 +155,12 @@ impl FanotifyWatcher {
                 }
             };
 
+            counters
+                .events
+                .fetch_add(events.len() as u64, Ordering::Relaxed);
             for event in &events {
                 let Some(fd) = event.fd() else {
+                    counters.overflows.fetch_add(1, Ordering::Relaxed);
                     continue; // queue overflow
                 };
 
@@ -156,7 +184,10 @@ impl FanotifyWatcher {
                     Some(s) => s,
                     None => continue,
                 };
-                if SKIP_PREFIXES.iter().any(|prefix| path_str.starts_with(prefix)) {
+                if SKIP_PREFIXES
+                    .iter()
+                    .any(|prefix| path_str.starts_with(prefix))
+                {
                     continue;
                 }
 
@@ -198,6 +229,7 @@ impl FanotifyWatcher {
     }
 
     pub fn drain(&self, time: u64) -> Vec<ObservationEvent> {
+        let started = Instant::now();
         let buf = {
             let mut guard = match self.buffer.lock() {
                 Ok(b) => b,
@@ -222,8 +254,20 @@ impl FanotifyWatcher {
             });
         }
 
+        let elapsed = started.elapsed().as_nanos() as u64;
+        self.counters
+            .drain_nanos
+            .fetch_add(elapsed, Ordering::Relaxed);
         events
     }
+
+    pub fn stats(&self) -> FanotifyStats {
+        FanotifyStats {
+            events: self.counters.events.load(Ordering::Relaxed),
+            overflows: self.counters.overflows.load(Ordering::Relaxed),
+            drain_time: Duration::from_nanos(self.counters.drain_nanos.load(Ordering::Relaxed)),
+        }
+    }
 }
 
 impl std::fmt::Debug for FanotifyWatcher {
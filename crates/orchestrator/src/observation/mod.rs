@@ -1,9 +1,11 @@
 #![forbid(unsafe_code)]
 
 mod admission;
+mod cgroup;
 mod event;
 pub mod fanotify_watcher;
 mod model_updater;
+mod power_supply;
 mod procfs_scanner;
 
 pub use admission::{
@@ -11,8 +13,9 @@ pub use admission::{
     RejectReason,
 };
 pub use event::{Observation, ObservationEvent, ScanWarning};
+pub use fanotify_watcher::{FanotifyStats, FanotifyWatcher};
 pub use model_updater::{DefaultModelUpdater, ModelDelta, ModelUpdater};
-pub use fanotify_watcher::FanotifyWatcher;
+pub use power_supply::read_power_source;
 pub use procfs_scanner::ProcfsScanner;
 
 use crate::error::Error;
@@ -20,6 +23,10 @@ use crate::error::Error;
 pub trait Scanner: Send + Sync {
     /// Scan the system and return an ordered observation event stream.
     fn scan(&mut self, time: u64, scan_id: u64) -> Result<Observation, Error>;
+    /// Optional fanotify counters for diagnostics.
+    fn fanotify_stats(&self) -> Option<FanotifyStats> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
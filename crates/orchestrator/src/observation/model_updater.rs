@@ -10,7 +10,9 @@ use config::Config;
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::path::Path;
 use std::sync::Arc;
-use tracing::{debug, trace};
+use std::time::SystemTime;
+use tracing::{debug, trace, warn};
+use tz::TimeZone;
 
 #[derive(Debug, Default, Clone)]
 pub struct ModelDelta {
@@ -35,16 +37,50 @@ pub trait ModelUpdater: Send + Sync {
 #[derive(Debug, Clone)]
 pub struct DefaultModelUpdater {
     active_window: u64,
+    cold_edges: bool,
     decay: f32,
+    launch_decay: f32,
+    /// Zone whose local hours bucket launch histograms.
+    zone: TimeZone,
+    sequence_order: usize,
+    sequence_gap: u64,
+    sequence_decay: f32,
 }
 
 impl DefaultModelUpdater {
     pub fn new(config: &Config) -> Self {
         Self {
             active_window: config.model.active_window.as_secs(),
+            cold_edges: config.model.cold_edges,
             decay: config.model.decay_factor(),
+            launch_decay: config.model.time_of_day.decay_factor(),
+            zone: launch_zone(config.model.time_of_day.utc_offset),
+            sequence_order: config.model.sequence.max_order,
+            sequence_gap: config.model.sequence.gap.as_secs(),
+            sequence_decay: config.model.sequence.decay_factor(),
         }
     }
+
+    fn utc_offset_at(&self, unix_secs: u64) -> i32 {
+        self.zone
+            .find_local_time_type(unix_secs as i64)
+            .map(|local| local.ut_offset())
+            .unwrap_or(0)
+    }
+}
+
+/// The configured fixed offset, else the system time zone, else UTC.
+fn launch_zone(utc_offset: Option<i32>) -> TimeZone {
+    if let Some(offset) = utc_offset {
+        return TimeZone::fixed(offset).unwrap_or_else(|err| {
+            warn!(offset, %err, "invalid time_of_day.utc_offset; using UTC");
+            TimeZone::utc()
+        });
+    }
+    TimeZone::local().unwrap_or_else(|err| {
+        warn!(%err, "failed to load the local time zone; using UTC");
+        TimeZone::utc()
+    })
 }
 
 impl ModelUpdater for DefaultModelUpdater {
@@ -57,9 +93,11 @@ impl ModelUpdater for DefaultModelUpdater {
         let hint = observation.len() / 2;
         let mut candidates: FxHashMap<Arc<Path>, CandidateExe> =
             FxHashMap::with_capacity_and_hasher(hint, Default::default());
-        let mut running_paths: FxHashSet<Arc<Path>> =
-            FxHashSet::with_capacity_and_hasher(hint, Default::default());
+        // Running exes with the lowest pid seen for each, to order launches.
+        let mut running_paths: FxHashMap<Arc<Path>, u32> =
+            FxHashMap::with_capacity_and_hasher(hint, Default::default());
         let mut now = stores.model_time;
+        let mut wall_time = None;
 
         for event in observation {
             match event {
@@ -67,7 +105,10 @@ impl ModelUpdater for DefaultModelUpdater {
                     now = *time;
                 }
                 ObservationEvent::ExeSeen { path, pid } => {
-                    running_paths.insert(path.clone());
+                    running_paths
+                        .entry(path.clone())
+                        .and_modify(|lowest| *lowest = (*lowest).min(*pid))
+                        .or_insert(*pid);
                     candidates
                         .entry(path.clone())
                         .or_insert_with(|| CandidateExe::new(path.clone(), *pid));
@@ -83,6 +124,12 @@ impl ModelUpdater for DefaultModelUpdater {
                         candidate.rejected_maps.push(map.path.clone());
                     }
                 }
+                ObservationEvent::WallTime { time } => {
+                    wall_time = time
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .ok()
+                        .map(|d| d.as_secs());
+                }
                 ObservationEvent::MemStat { .. } => {}
                 ObservationEvent::ObsEnd { .. } => {}
             }
@@ -124,7 +171,7 @@ impl ModelUpdater for DefaultModelUpdater {
                         stores.attach_map(exe_id, map_id);
                     }
 
-                    if running_paths.contains(&candidate.path) {
+                    if running_paths.contains_key(&candidate.path) {
                         active_exe_ids.insert(exe_id);
                     }
                 }
@@ -132,14 +179,17 @@ impl ModelUpdater for DefaultModelUpdater {
         }
 
         // Update running flags and transitions.
+        let mut launched = Vec::new();
         let exe_ids: Vec<_> = stores.exes.iter().map(|(id, _)| id).collect();
         for exe_id in exe_ids {
             if let Some(exe_mut) = stores.exes.get_mut(exe_id) {
-                let is_running = running_paths.contains(exe_mut.key.path());
+                let pid = running_paths.get(exe_mut.key.path()).copied();
+                let is_running = pid.is_some();
                 if exe_mut.running != is_running {
                     exe_mut.change_time = now;
-                    if is_running {
+                    if let Some(pid) = pid {
                         delta.running_now.push(exe_mut.key.clone());
+                        launched.push((pid, exe_id));
                     } else {
                         delta.stopped_now.push(exe_mut.key.clone());
                     }
@@ -151,11 +201,42 @@ impl ModelUpdater for DefaultModelUpdater {
             }
         }
 
+        // Record launches by hour of week and in launch order. Exes launched
+        // within one scan are ordered by pid, which follows start order until
+        // pids wrap. The first scan after startup only establishes which exes
+        // are already running.
+        launched.sort_unstable();
+        if stores.running_observed && !launched.is_empty() {
+            for &(_, exe_id) in &launched {
+                stores.sequences.record(
+                    exe_id,
+                    now,
+                    self.sequence_order,
+                    self.sequence_gap,
+                    self.sequence_decay,
+                );
+            }
+            stores.sequences.prune(now, self.sequence_decay);
+            if let Some(wall) = wall_time {
+                let utc_offset = self.utc_offset_at(wall);
+                for &(_, exe_id) in &launched {
+                    stores
+                        .launches
+                        .record(exe_id, wall, utc_offset, self.launch_decay);
+                }
+            }
+        }
+        if let Some(wall) = wall_time {
+            stores.wall_time = Some(wall);
+            stores.utc_offset = self.utc_offset_at(wall);
+        }
+        stores.running_observed = true;
+
         // Update active set (lazy Markov edges).
         stores.active.update(active_exe_ids.iter().copied(), now);
         let _removed = stores.active.prune(now, self.active_window);
+        stores.retire_inactive_edges(self.cold_edges);
         let active = stores.active.exes();
-        stores.markov.prune_inactive(&active);
 
         // Ensure edges among active exes.
         let active_vec: Vec<_> = active.iter().copied().collect();
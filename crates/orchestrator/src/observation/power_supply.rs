@@ -0,0 +1,91 @@
+#![forbid(unsafe_code)]
+
+use config::PowerSource;
+use std::path::Path;
+
+/// Sysfs class directory listing power supplies.
+const POWER_SUPPLY_ROOT: &str = "/sys/class/power_supply";
+
+/// Detect the current power source from `/sys/class/power_supply`. Falls
+/// back to [`PowerSource::Ac`] when nothing can be read.
+pub fn read_power_source() -> PowerSource {
+    read_from(Path::new(POWER_SUPPLY_ROOT))
+}
+
+/// On battery only if no external supply is online and at least one
+/// battery is present and discharging.
+fn read_from(root: &Path) -> PowerSource {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return PowerSource::Ac;
+    };
+
+    let mut discharging = false;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        let attr = |name: &str| {
+            std::fs::read_to_string(dir.join(name))
+                .map(|value| value.trim().to_owned())
+                .unwrap_or_default()
+        };
+        match attr("type").as_str() {
+            // Peripheral batteries (mice, headsets) report scope "Device".
+            "Battery" if attr("scope") != "Device" && attr("status") == "Discharging" => {
+                discharging = true;
+            }
+            "Mains" | "USB" if attr("online") == "1" => return PowerSource::Ac,
+            _ => {}
+        }
+    }
+
+    if discharging {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn supply(root: &Path, name: &str, attrs: &[(&str, &str)]) {
+        let dir = root.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (key, value) in attrs {
+            std::fs::write(dir.join(key), format!("{value}\n")).unwrap();
+        }
+    }
+
+    #[test]
+    fn detects_battery_and_mains() {
+        let root = tempdir().unwrap();
+        assert_eq!(read_from(root.path()), PowerSource::Ac);
+
+        supply(
+            root.path(),
+            "BAT0",
+            &[("type", "Battery"), ("status", "Discharging")],
+        );
+        supply(root.path(), "AC", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(read_from(root.path()), PowerSource::Battery);
+
+        supply(root.path(), "AC", &[("type", "Mains"), ("online", "1")]);
+        assert_eq!(read_from(root.path()), PowerSource::Ac);
+    }
+
+    #[test]
+    fn ignores_peripheral_batteries() {
+        let root = tempdir().unwrap();
+        supply(
+            root.path(),
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+            ],
+        );
+        assert_eq!(read_from(root.path()), PowerSource::Ac);
+    }
+}
@@ -1,14 +1,16 @@
 #![forbid(unsafe_code)]
 
-use crate::domain::{MapSegment, MemStat};
+use crate::domain::{MapSegment, MemStat, Psi};
 use crate::error::Error;
-use crate::observation::fanotify_watcher::FanotifyWatcher;
+use crate::observation::cgroup::read_cgroup_memory;
+use crate::observation::fanotify_watcher::{FanotifyStats, FanotifyWatcher};
 use crate::observation::{Observation, ObservationEvent, ScanWarning, Scanner};
 use procfs::process::MMapPath;
-use procfs::{Current, Meminfo, page_size, vmstat};
+use procfs::{Current, IoPressure, Meminfo, MemoryPressure, page_size, vmstat};
 use rustc_hash::FxHashMap;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tracing::{debug, trace};
 
 /// How often (in scan cycles) to re-scan maps of already-known processes.
@@ -74,7 +76,8 @@ impl ProcfsScanner {
         Some(Arc::from(Path::new(trimmed)))
     }
 
-    fn read_memstat() -> Result<MemStat, Error> {
+    /// Sample system memory stats the way a scan does.
+    pub fn read_memstat() -> Result<MemStat, Error> {
         let mem = Meminfo::current()?;
         let vm = vmstat()?;
         let page = page_size() as i64;
@@ -88,6 +91,15 @@ impl ProcfsScanner {
             cached: mem.cached / 1024,
             pagein,
             pageout,
+            memory_pressure: MemoryPressure::current().ok().map(|p| Psi {
+                some: p.some.avg10,
+                full: p.full.avg10,
+            }),
+            io_pressure: IoPressure::current().ok().map(|p| Psi {
+                some: p.some.avg10,
+                full: p.full.avg10,
+            }),
+            cgroup: read_cgroup_memory(),
         })
     }
 
@@ -113,7 +125,9 @@ impl ProcfsScanner {
                     };
                     let (start, end) = map.address;
                     let length = end.saturating_sub(start);
-                    let device = ((map.dev.0 as u64) << 20) | (map.dev.1 as u64);
+                    // Encoded like `st_dev`, so segments fanotify finds by
+                    // `stat` carry the same device.
+                    let device = nix::sys::stat::makedev(map.dev.0 as u64, map.dev.1 as u64);
                     let inode = map.inode;
                     cached_maps.push(CachedMap {
                         path: path.clone(),
@@ -167,6 +181,9 @@ impl Scanner for ProcfsScanner {
         let mut events = Vec::new();
         let mut warnings = Vec::new();
         events.push(ObservationEvent::ObsBegin { time, scan_id });
+        events.push(ObservationEvent::WallTime {
+            time: SystemTime::now(),
+        });
 
         // Track which PIDs are seen this cycle.
         let mut seen_pids =
@@ -190,9 +207,7 @@ impl Scanner for ProcfsScanner {
 
             // Check cache for this PID.
             let cached = self.cache.get(&pid);
-            let is_same_process = cached
-                .map(|c| c.starttime == starttime)
-                .unwrap_or(false);
+            let is_same_process = cached.map(|c| c.starttime == starttime).unwrap_or(false);
 
             if is_same_process {
                 let cached = cached.unwrap();
@@ -205,13 +220,17 @@ impl Scanner for ProcfsScanner {
                 // Rescan maps periodically.
                 let cycles_since = self.scan_count.saturating_sub(cached.last_map_scan);
                 if cycles_since >= self.map_rescan_interval {
-                    let maps = Self::scan_maps(&process, &exe_path, time, &mut events, &mut warnings);
-                    seen_pids.insert(pid, CachedProcess {
-                        starttime,
-                        exe_path,
-                        maps,
-                        last_map_scan: self.scan_count,
-                    });
+                    let maps =
+                        Self::scan_maps(&process, &exe_path, time, &mut events, &mut warnings);
+                    seen_pids.insert(
+                        pid,
+                        CachedProcess {
+                            starttime,
+                            exe_path,
+                            maps,
+                            last_map_scan: self.scan_count,
+                        },
+                    );
                 } else {
                     Self::emit_cached_maps(&exe_path, &cached.maps, time, &mut events);
                     seen_pids.insert(pid, cached.clone());
@@ -235,12 +254,15 @@ impl Scanner for ProcfsScanner {
                 });
 
                 let maps = Self::scan_maps(&process, &exe_path, time, &mut events, &mut warnings);
-                seen_pids.insert(pid, CachedProcess {
-                    starttime,
-                    exe_path,
-                    maps,
-                    last_map_scan: self.scan_count,
-                });
+                seen_pids.insert(
+                    pid,
+                    CachedProcess {
+                        starttime,
+                        exe_path,
+                        maps,
+                        last_map_scan: self.scan_count,
+                    },
+                );
             }
         }
 
@@ -250,8 +272,14 @@ impl Scanner for ProcfsScanner {
         // Drain fanotify events (file-open monitoring).
         if let Some(watcher) = &self.fanotify {
             let fan_events = watcher.drain(time);
-            let fan_exes = fan_events.iter().filter(|e| matches!(e, ObservationEvent::ExeSeen { .. })).count();
-            let fan_maps = fan_events.iter().filter(|e| matches!(e, ObservationEvent::MapSeen { .. })).count();
+            let fan_exes = fan_events
+                .iter()
+                .filter(|e| matches!(e, ObservationEvent::ExeSeen { .. }))
+                .count();
+            let fan_maps = fan_events
+                .iter()
+                .filter(|e| matches!(e, ObservationEvent::MapSeen { .. }))
+                .count();
             debug!(fan_exes, fan_maps, "fanotify drain");
             events.extend(fan_events);
         }
@@ -266,7 +294,16 @@ impl Scanner for ProcfsScanner {
             warnings,
         });
 
-        trace!(scan_id, event_count = events.len(), cached_pids = self.cache.len(), "observation collected");
+        trace!(
+            scan_id,
+            event_count = events.len(),
+            cached_pids = self.cache.len(),
+            "observation collected"
+        );
         Ok(events)
     }
+
+    fn fanotify_stats(&self) -> Option<FanotifyStats> {
+        self.fanotify.as_ref().map(|watcher| watcher.stats())
+    }
 }
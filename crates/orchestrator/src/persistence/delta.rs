@@ -0,0 +1,300 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::MapKey;
+use crate::persistence::{
+    ActiveRecord, ExeMapRecord, ExeRecord, LaunchHistogramRecord, LaunchSequenceRecord, MapRecord,
+    MarkovRecord, StoresSnapshot,
+};
+use rustc_hash::FxHashMap;
+use std::hash::Hash;
+use std::path::PathBuf;
+
+/// A persisted row: its primary key and everything else.
+pub trait Row: Clone {
+    type Key: Clone + Eq + Hash;
+
+    fn key(&self) -> Self::Key;
+    /// Whether the fields outside the key equal those of `saved`, the row
+    /// last saved under the same key. Floats compare bitwise.
+    fn unchanged(&self, saved: &Self) -> bool;
+}
+
+/// Rows of one table to upsert and keys to delete.
+#[derive(Debug, Clone)]
+pub struct TableDelta<R: Row> {
+    pub upserted: Vec<R>,
+    pub removed: Vec<R::Key>,
+}
+
+impl<R: Row> Default for TableDelta<R> {
+    fn default() -> Self {
+        Self {
+            upserted: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl<R: Row> TableDelta<R> {
+    pub fn is_empty(&self) -> bool {
+        self.upserted.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.upserted.len() + self.removed.len()
+    }
+}
+
+/// Rows changed since the last save. Applied on top of the previously
+/// saved state it reproduces the snapshot it was computed from.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDelta {
+    pub exes: TableDelta<ExeRecord>,
+    pub maps: TableDelta<MapRecord>,
+    pub exe_maps: TableDelta<ExeMapRecord>,
+    pub markov_edges: TableDelta<MarkovRecord>,
+    pub launch_histograms: TableDelta<LaunchHistogramRecord>,
+    pub launch_sequences: TableDelta<LaunchSequenceRecord>,
+    pub active_exes: TableDelta<ActiveRecord>,
+    pub cold_markov_edges: TableDelta<MarkovRecord>,
+}
+
+impl SnapshotDelta {
+    /// Number of rows to write or delete.
+    pub fn len(&self) -> usize {
+        self.exes.len()
+            + self.maps.len()
+            + self.exe_maps.len()
+            + self.markov_edges.len()
+            + self.launch_histograms.len()
+            + self.launch_sequences.len()
+            + self.active_exes.len()
+            + self.cold_markov_edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+type Saved<R> = FxHashMap<<R as Row>::Key, R>;
+
+/// Copies of the rows a repository holds, used to work out which rows
+/// changed between saves.
+#[derive(Debug, Default)]
+pub struct DeltaTracker {
+    exes: Saved<ExeRecord>,
+    maps: Saved<MapRecord>,
+    exe_maps: Saved<ExeMapRecord>,
+    markov_edges: Saved<MarkovRecord>,
+    launch_histograms: Saved<LaunchHistogramRecord>,
+    launch_sequences: Saved<LaunchSequenceRecord>,
+    active_exes: Saved<ActiveRecord>,
+    cold_markov_edges: Saved<MarkovRecord>,
+}
+
+impl DeltaTracker {
+    /// Track `snapshot` as what the repository currently holds.
+    pub fn new(snapshot: &StoresSnapshot) -> Self {
+        Self::default().diff(snapshot).1
+    }
+
+    /// Rows of `snapshot` that differ from the tracked state, and the
+    /// tracker to adopt once the delta has been saved.
+    pub fn diff(&self, snapshot: &StoresSnapshot) -> (SnapshotDelta, DeltaTracker) {
+        let state = &snapshot.state;
+        let (exes, exes_seen) = diff_table(&self.exes, &state.exes);
+        let (maps, maps_seen) = diff_table(&self.maps, &state.maps);
+        let (exe_maps, exe_maps_seen) = diff_table(&self.exe_maps, &state.exe_maps);
+        let (markov_edges, markov_seen) = diff_table(&self.markov_edges, &state.markov_edges);
+        let (launch_histograms, histograms_seen) =
+            diff_table(&self.launch_histograms, &state.launch_histograms);
+        let (launch_sequences, sequences_seen) =
+            diff_table(&self.launch_sequences, &state.launch_sequences);
+        let (active_exes, active_seen) = diff_table(&self.active_exes, &state.active_exes);
+        let (cold_markov_edges, cold_seen) =
+            diff_table(&self.cold_markov_edges, &state.cold_markov_edges);
+
+        let delta = SnapshotDelta {
+            exes,
+            maps,
+            exe_maps,
+            markov_edges,
+            launch_histograms,
+            launch_sequences,
+            active_exes,
+            cold_markov_edges,
+        };
+        let tracker = DeltaTracker {
+            exes: exes_seen,
+            maps: maps_seen,
+            exe_maps: exe_maps_seen,
+            markov_edges: markov_seen,
+            launch_histograms: histograms_seen,
+            launch_sequences: sequences_seen,
+            active_exes: active_seen,
+            cold_markov_edges: cold_seen,
+        };
+        (delta, tracker)
+    }
+}
+
+fn diff_table<R: Row>(saved: &Saved<R>, rows: &[R]) -> (TableDelta<R>, Saved<R>) {
+    let mut delta = TableDelta::default();
+    let mut seen = FxHashMap::with_capacity_and_hasher(rows.len(), Default::default());
+    for row in rows {
+        let key = row.key();
+        if !saved.get(&key).is_some_and(|old| row.unchanged(old)) {
+            delta.upserted.push(row.clone());
+        }
+        seen.insert(key, row.clone());
+    }
+    delta.removed = saved
+        .keys()
+        .filter(|key| !seen.contains_key(*key))
+        .cloned()
+        .collect();
+    (delta, seen)
+}
+
+fn same_f32s(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.to_bits() == b.to_bits())
+}
+
+impl Row for ExeRecord {
+    type Key = PathBuf;
+
+    fn key(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn unchanged(&self, saved: &Self) -> bool {
+        self.total_running_time == saved.total_running_time
+            && self.last_seen_time == saved.last_seen_time
+    }
+}
+
+impl Row for MapRecord {
+    type Key = MapKey;
+
+    fn key(&self) -> MapKey {
+        MapKey::new(self.path.clone(), self.offset, self.length)
+    }
+
+    fn unchanged(&self, saved: &Self) -> bool {
+        self.update_time == saved.update_time
+    }
+}
+
+impl Row for ExeMapRecord {
+    type Key = (PathBuf, MapKey);
+
+    fn key(&self) -> Self::Key {
+        (self.exe_path.clone(), self.map_key.clone())
+    }
+
+    fn unchanged(&self, saved: &Self) -> bool {
+        self.prob.to_bits() == saved.prob.to_bits()
+    }
+}
+
+impl Row for MarkovRecord {
+    type Key = (PathBuf, PathBuf);
+
+    fn key(&self) -> Self::Key {
+        (self.exe_a.clone(), self.exe_b.clone())
+    }
+
+    fn unchanged(&self, saved: &Self) -> bool {
+        same_f32s(&self.time_to_leave, &saved.time_to_leave)
+            && same_f32s(
+                self.transition_prob.as_flattened(),
+                saved.transition_prob.as_flattened(),
+            )
+            && self.both_running_time == saved.both_running_time
+            && self.state == saved.state
+            && self.last_change_time == saved.last_change_time
+            && self.state_last_left == saved.state_last_left
+    }
+}
+
+impl Row for LaunchHistogramRecord {
+    type Key = PathBuf;
+
+    fn key(&self) -> PathBuf {
+        self.exe_path.clone()
+    }
+
+    fn unchanged(&self, saved: &Self) -> bool {
+        same_f32s(&self.weights, &saved.weights) && self.updated_at == saved.updated_at
+    }
+}
+
+impl Row for LaunchSequenceRecord {
+    type Key = Vec<PathBuf>;
+
+    fn key(&self) -> Vec<PathBuf> {
+        self.context.clone()
+    }
+
+    fn unchanged(&self, saved: &Self) -> bool {
+        self.followers.len() == saved.followers.len()
+            && self.followers.iter().zip(&saved.followers).all(
+                |((path, weight), (old_path, old_weight))| {
+                    path == old_path && weight.to_bits() == old_weight.to_bits()
+                },
+            )
+            && self.updated_at == saved.updated_at
+    }
+}
+
+impl Row for ActiveRecord {
+    type Key = PathBuf;
+
+    fn key(&self) -> PathBuf {
+        self.exe_path.clone()
+    }
+
+    fn unchanged(&self, saved: &Self) -> bool {
+        self.last_active == saved.last_active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::{SnapshotMeta, StateSnapshot};
+
+    fn snapshot(exes: &[(&str, u64)]) -> StoresSnapshot {
+        StoresSnapshot {
+            meta: SnapshotMeta::default(),
+            state: StateSnapshot {
+                model_time: 0,
+                last_accounting_time: 0,
+                exes: exes
+                    .iter()
+                    .map(|(path, time)| ExeRecord {
+                        path: PathBuf::from(path),
+                        total_running_time: *time,
+                        last_seen_time: None,
+                    })
+                    .collect(),
+                ..StateSnapshot::default()
+            },
+        }
+    }
+
+    #[test]
+    fn diff_reports_changed_added_and_removed_rows() {
+        let tracker = DeltaTracker::new(&snapshot(&[("/a", 1), ("/b", 2), ("/c", 3)]));
+        let (delta, tracker) = tracker.diff(&snapshot(&[("/a", 1), ("/b", 5), ("/d", 0)]));
+
+        let upserted: Vec<_> = delta.exes.upserted.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(upserted, vec![PathBuf::from("/b"), PathBuf::from("/d")]);
+        assert_eq!(delta.exes.removed, vec![PathBuf::from("/c")]);
+        assert_eq!(delta.len(), 3);
+
+        let (delta, _) = tracker.diff(&snapshot(&[("/a", 1), ("/b", 5), ("/d", 0)]));
+        assert!(delta.is_empty());
+    }
+}
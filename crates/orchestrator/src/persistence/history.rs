@@ -0,0 +1,193 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::MemStat;
+use crate::error::Error;
+use crate::persistence::SqliteRepository;
+use serde::Serialize;
+use sqlx::SqliteConnection;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::trace;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Summary of one tick for the usage history.
+#[derive(Debug, Clone, Default)]
+pub struct UsageRecord {
+    /// Wall-clock time of the tick, in seconds since the Unix epoch.
+    pub recorded_at: u64,
+    pub model_time: u64,
+    /// Exes that started running this tick.
+    pub launches: Vec<LaunchUsage>,
+    pub plan_maps: usize,
+    pub plan_bytes: u64,
+    pub budget_bytes: u64,
+    pub prefetched_maps: usize,
+    /// Bytes actually issued for reading (`PrefetchReport::read_bytes`).
+    pub prefetched_bytes: u64,
+    pub memstat: Option<MemStat>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchUsage {
+    pub exe_path: PathBuf,
+    /// Whether the previous tick's plan included any of the exe's maps.
+    pub predicted: bool,
+}
+
+/// Launches of one exe over a period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppUsage {
+    pub exe_path: PathBuf,
+    pub launches: u64,
+    /// Launches the previous tick's plan had prefetched for.
+    pub predicted: u64,
+}
+
+/// Totals for one UTC day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DayUsage {
+    /// Start of the day, in seconds since the Unix epoch.
+    pub day: u64,
+    pub ticks: u64,
+    pub launches: u64,
+    pub predicted: u64,
+    pub prefetched_bytes: u64,
+}
+
+impl SqliteRepository {
+    /// Append `records` in one transaction and drop entries older than
+    /// `retention` before the newest of them, so the history holds a
+    /// rolling window.
+    pub(crate) async fn append_usage(
+        &self,
+        records: &[UsageRecord],
+        retention: Duration,
+    ) -> Result<(), Error> {
+        let Some(newest) = records.iter().map(|record| record.recorded_at).max() else {
+            return Ok(());
+        };
+        let mut tx = self.pool().begin().await?;
+        for record in records {
+            Self::insert_usage(&mut tx, record).await?;
+        }
+
+        let cutoff = newest.saturating_sub(retention.as_secs()) as i64;
+        sqlx::query!(
+            "DELETE FROM usage_launches WHERE tick_id IN \
+             (SELECT id FROM usage_ticks WHERE recorded_at < ?)",
+            cutoff
+        )
+        .execute(&mut *tx)
+        .await?;
+        let expired = sqlx::query!("DELETE FROM usage_ticks WHERE recorded_at < ?", cutoff)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        trace!(ticks = records.len(), expired, "usage history appended");
+        Ok(())
+    }
+
+    async fn insert_usage(conn: &mut SqliteConnection, record: &UsageRecord) -> Result<(), Error> {
+        let recorded_at = record.recorded_at as i64;
+        let model_time = record.model_time as i64;
+        let plan_maps = record.plan_maps as i64;
+        let plan_bytes = record.plan_bytes as i64;
+        let budget_bytes = record.budget_bytes as i64;
+        let prefetched_maps = record.prefetched_maps as i64;
+        let prefetched_bytes = record.prefetched_bytes as i64;
+        let mem_total_kb = record.memstat.map(|mem| mem.total as i64);
+        let mem_available_kb = record.memstat.map(|mem| mem.available as i64);
+        let mem_cached_kb = record.memstat.map(|mem| mem.cached as i64);
+        let tick_id = sqlx::query!(
+            "INSERT INTO usage_ticks (recorded_at, model_time, plan_maps, plan_bytes, \
+             budget_bytes, prefetched_maps, prefetched_bytes, mem_total_kb, mem_available_kb, \
+             mem_cached_kb) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            recorded_at,
+            model_time,
+            plan_maps,
+            plan_bytes,
+            budget_bytes,
+            prefetched_maps,
+            prefetched_bytes,
+            mem_total_kb,
+            mem_available_kb,
+            mem_cached_kb
+        )
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+        for launch in &record.launches {
+            let exe_path = launch.exe_path.to_string_lossy().to_string();
+            sqlx::query!(
+                "INSERT INTO usage_launches (tick_id, exe_path, predicted) VALUES (?, ?, ?)",
+                tick_id,
+                exe_path,
+                launch.predicted
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Exes launched at or after `since` (Unix seconds), most launches
+    /// first.
+    pub async fn usage_by_app(&self, since: u64, limit: usize) -> Result<Vec<AppUsage>, Error> {
+        let since = since as i64;
+        let limit = limit as i64;
+        let rows = sqlx::query!(
+            "SELECT l.exe_path as \"exe_path!\", COUNT(*) as \"launches!: i64\", \
+             SUM(l.predicted) as \"predicted!: i64\" \
+             FROM usage_launches l JOIN usage_ticks t ON t.id = l.tick_id \
+             WHERE t.recorded_at >= ? \
+             GROUP BY l.exe_path ORDER BY 2 DESC, l.exe_path LIMIT ?",
+            since,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| AppUsage {
+                exe_path: PathBuf::from(row.exe_path),
+                launches: row.launches as u64,
+                predicted: row.predicted as u64,
+            })
+            .collect())
+    }
+
+    /// Per-day totals at or after `since` (Unix seconds), oldest day first.
+    pub async fn usage_by_day(&self, since: u64) -> Result<Vec<DayUsage>, Error> {
+        let since = since as i64;
+        let seconds_per_day = SECONDS_PER_DAY as i64;
+        let rows = sqlx::query!(
+            "SELECT day as \"day!: i64\", COUNT(*) as \"ticks!: i64\", \
+             SUM(launches) as \"launches!: i64\", SUM(predicted) as \"predicted!: i64\", \
+             SUM(prefetched_bytes) as \"prefetched_bytes!: i64\" \
+             FROM (SELECT t.recorded_at / ? AS day, t.prefetched_bytes, \
+                   (SELECT COUNT(*) FROM usage_launches l WHERE l.tick_id = t.id) AS launches, \
+                   (SELECT COALESCE(SUM(l.predicted), 0) FROM usage_launches l \
+                    WHERE l.tick_id = t.id) AS predicted \
+                   FROM usage_ticks t WHERE t.recorded_at >= ?) \
+             GROUP BY day ORDER BY day",
+            seconds_per_day,
+            since
+        )
+        .fetch_all(self.pool())
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| DayUsage {
+                day: row.day as u64 * SECONDS_PER_DAY,
+                ticks: row.ticks as u64,
+                launches: row.launches as u64,
+                predicted: row.predicted as u64,
+                prefetched_bytes: row.prefetched_bytes as u64,
+            })
+            .collect())
+    }
+}
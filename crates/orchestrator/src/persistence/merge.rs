@@ -0,0 +1,458 @@
+#![forbid(unsafe_code)]
+
+use crate::persistence::{
+    ActiveRecord, ExeMapRecord, ExeRecord, LaunchHistogramRecord, LaunchSequenceRecord, MapRecord,
+    MarkovRecord, Row, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta, StateSnapshot, StoresSnapshot,
+};
+use crate::stores::LaunchHistogram;
+use rustc_hash::FxHashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// How [`merge_snapshots`] weighs the two inputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MergeOptions {
+    /// Model seconds after which an exe's running time counts half, measured
+    /// from when it was last seen to its snapshot's model time. Applies to
+    /// exe running times and Markov `both_running_time`. 0 sums them as is.
+    pub half_life: u64,
+    /// Per-second decay rate of launch histograms, as configured in
+    /// `[model.time_of_day]`. A histogram in both inputs is decayed to the
+    /// later of its two stamps before the weights are summed. 0 sums them
+    /// as is.
+    pub histogram_decay: f32,
+}
+
+/// Combine two models, e.g. from different machines, into one.
+///
+/// Each snapshot's model-time stamps are first shifted so both end at the
+/// later `model_time`; ages relative to "now" are preserved. Rows are then
+/// unioned by key. Shared exes sum their (decayed) running time, Markov edge
+/// statistics are averaged weighted by `both_running_time`, exe-map
+/// probabilities by exe running time, histogram weights are decayed to a
+/// common time and summed, and sequence weights are summed.
+pub fn merge_snapshots(
+    left: StoresSnapshot,
+    right: StoresSnapshot,
+    options: &MergeOptions,
+) -> StoresSnapshot {
+    let model_time = left.state.model_time.max(right.state.model_time);
+    let left = Side::new(left.state, model_time, options);
+    let right = Side::new(right.state, model_time, options);
+
+    let exe_maps = merge_exe_maps(&left, &right);
+    let markov_edges = merge_rows(left.markov_edges(), right.markov_edges(), merge_markov);
+    let cold_markov_edges = merge_rows(left.cold_edges(), right.cold_edges(), merge_markov);
+    let (left, right) = (left.state, right.state);
+
+    StoresSnapshot {
+        meta: SnapshotMeta {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            app_version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+            created_at: Some(SystemTime::now()),
+        },
+        state: StateSnapshot {
+            model_time,
+            last_accounting_time: left.last_accounting_time.max(right.last_accounting_time),
+            exes: merge_rows(left.exes, right.exes, |a, b| ExeRecord {
+                path: a.path,
+                total_running_time: a.total_running_time.saturating_add(b.total_running_time),
+                last_seen_time: a.last_seen_time.max(b.last_seen_time),
+            }),
+            maps: merge_rows(left.maps, right.maps, |a, b| MapRecord {
+                update_time: a.update_time.max(b.update_time),
+                ..a
+            }),
+            exe_maps,
+            markov_edges,
+            launch_histograms: merge_rows(
+                left.launch_histograms,
+                right.launch_histograms,
+                |a, b| merge_histogram(a, b, options.histogram_decay),
+            ),
+            launch_sequences: merge_rows(
+                left.launch_sequences,
+                right.launch_sequences,
+                merge_sequence,
+            ),
+            active_exes: merge_rows(left.active_exes, right.active_exes, |a, b| ActiveRecord {
+                last_active: a.last_active.max(b.last_active),
+                ..a
+            }),
+            cold_markov_edges,
+        },
+    }
+}
+
+/// `state` with its model-time stamps shifted to end at `model_time` and
+/// its edges oriented, as [`merge_snapshots`] prepares each input, but
+/// without decay. Lets rows from two snapshots be compared or replaced by
+/// key.
+pub(crate) fn rebased(state: StateSnapshot, model_time: u64) -> StateSnapshot {
+    let side = Side::new(state, model_time, &MergeOptions::default());
+    let markov_edges = side.markov_edges();
+    let cold_markov_edges = side.cold_edges();
+    StateSnapshot {
+        markov_edges,
+        cold_markov_edges,
+        ..side.state
+    }
+}
+
+/// One input, rebased onto the merged model time and decayed.
+struct Side {
+    state: StateSnapshot,
+    /// Decayed running time of each exe, used to weigh its exe-map rows.
+    weights: FxHashMap<PathBuf, f64>,
+}
+
+impl Side {
+    fn new(mut state: StateSnapshot, model_time: u64, options: &MergeOptions) -> Self {
+        let shift = model_time - state.model_time;
+        let decay: FxHashMap<PathBuf, f64> = state
+            .exes
+            .iter()
+            .map(|exe| {
+                let age = exe
+                    .last_seen_time
+                    .map_or(0, |seen| state.model_time.saturating_sub(seen));
+                (exe.path.clone(), decay_factor(age, options.half_life))
+            })
+            .collect();
+
+        state.model_time = model_time;
+        state.last_accounting_time = state.last_accounting_time.saturating_add(shift);
+        for exe in &mut state.exes {
+            exe.total_running_time = scale(exe.total_running_time, decay[&exe.path]);
+            exe.last_seen_time = exe.last_seen_time.map(|seen| seen.saturating_add(shift));
+        }
+        for map in &mut state.maps {
+            map.update_time = map.update_time.saturating_add(shift);
+        }
+        let edge_decay = |edge: &MarkovRecord| {
+            decay
+                .get(&edge.exe_a)
+                .copied()
+                .unwrap_or(1.0)
+                .min(decay.get(&edge.exe_b).copied().unwrap_or(1.0))
+        };
+        for edge in &mut state.cold_markov_edges {
+            edge.both_running_time = scale(edge.both_running_time, edge_decay(edge));
+        }
+        for edge in &mut state.markov_edges {
+            edge.both_running_time = scale(edge.both_running_time, edge_decay(edge));
+            edge.last_change_time = edge.last_change_time.saturating_add(shift);
+            for left in &mut edge.state_last_left {
+                *left = left.saturating_add(shift);
+            }
+        }
+        for active in &mut state.active_exes {
+            active.last_active = active.last_active.saturating_add(shift);
+        }
+        // Histogram stamps are wall-clock seconds and need no rebasing.
+        for sequence in &mut state.launch_sequences {
+            sequence.updated_at = sequence.updated_at.saturating_add(shift);
+        }
+        let weights = state
+            .exes
+            .iter()
+            .map(|exe| (exe.path.clone(), exe.total_running_time as f64))
+            .collect();
+        Self { state, weights }
+    }
+
+    fn markov_edges(&self) -> Vec<MarkovRecord> {
+        self.state
+            .markov_edges
+            .iter()
+            .cloned()
+            .map(oriented)
+            .collect()
+    }
+
+    fn cold_edges(&self) -> Vec<MarkovRecord> {
+        self.state
+            .cold_markov_edges
+            .iter()
+            .cloned()
+            .map(oriented)
+            .collect()
+    }
+}
+
+fn decay_factor(age: u64, half_life: u64) -> f64 {
+    if half_life == 0 {
+        1.0
+    } else {
+        0.5f64.powf(age as f64 / half_life as f64)
+    }
+}
+
+fn scale(value: u64, factor: f64) -> u64 {
+    (value as f64 * factor).round() as u64
+}
+
+/// Weighted mean of `a` and `b`; the plain mean when both weights are zero.
+fn blend(a: f32, wa: f64, b: f32, wb: f64) -> f32 {
+    if wa + wb <= 0.0 {
+        (a + b) / 2.0
+    } else {
+        ((a as f64 * wa + b as f64 * wb) / (wa + wb)) as f32
+    }
+}
+
+/// Union `left` and `right` by key, combining rows present in both.
+pub(crate) fn merge_rows<R: Row>(
+    left: Vec<R>,
+    right: Vec<R>,
+    mut combine: impl FnMut(R, R) -> R,
+) -> Vec<R> {
+    let mut index: FxHashMap<R::Key, usize> = FxHashMap::default();
+    let mut rows: Vec<Option<R>> = Vec::with_capacity(left.len() + right.len());
+    for row in left.into_iter().chain(right) {
+        match index.get(&row.key()) {
+            Some(&at) => {
+                let existing = rows[at].take().expect("row present");
+                rows[at] = Some(combine(existing, row));
+            }
+            None => {
+                index.insert(row.key(), rows.len());
+                rows.push(Some(row));
+            }
+        }
+    }
+    rows.into_iter().flatten().collect()
+}
+
+fn merge_exe_maps(left: &Side, right: &Side) -> Vec<ExeMapRecord> {
+    let weighted = |side: &Side| -> Vec<(ExeMapRecord, f64)> {
+        side.state
+            .exe_maps
+            .iter()
+            .map(|row| {
+                let weight = side.weights.get(&row.exe_path).copied().unwrap_or(0.0);
+                (row.clone(), weight)
+            })
+            .collect()
+    };
+    let mut index: FxHashMap<_, usize> = FxHashMap::default();
+    let mut rows: Vec<(ExeMapRecord, f64)> = Vec::new();
+    for (row, weight) in weighted(left).into_iter().chain(weighted(right)) {
+        match index.get(&row.key()) {
+            Some(&at) => {
+                let (existing, existing_weight) = &mut rows[at];
+                existing.prob = blend(existing.prob, *existing_weight, row.prob, weight);
+                *existing_weight += weight;
+            }
+            None => {
+                index.insert(row.key(), rows.len());
+                rows.push((row, weight));
+            }
+        }
+    }
+    rows.into_iter().map(|(row, _)| row).collect()
+}
+
+/// Edges are keyed by the exe pair in whichever order the writing machine
+/// happened to use. Put the lexically smaller path first so both inputs
+/// agree.
+fn oriented(edge: MarkovRecord) -> MarkovRecord {
+    if edge.exe_a <= edge.exe_b {
+        edge
+    } else {
+        edge.reversed()
+    }
+}
+
+fn merge_markov(a: MarkovRecord, b: MarkovRecord) -> MarkovRecord {
+    let (wa, wb) = (a.both_running_time as f64, b.both_running_time as f64);
+    let mut merged = a.clone();
+    for state in 0..4 {
+        merged.time_to_leave[state] = blend(a.time_to_leave[state], wa, b.time_to_leave[state], wb);
+        for to in 0..4 {
+            merged.transition_prob[state][to] = blend(
+                a.transition_prob[state][to],
+                wa,
+                b.transition_prob[state][to],
+                wb,
+            );
+        }
+    }
+    merged.both_running_time = a.both_running_time.saturating_add(b.both_running_time);
+    // The pair's current state comes from whichever machine saw it change
+    // last.
+    if b.last_change_time > a.last_change_time {
+        merged.state = b.state;
+        merged.last_change_time = b.last_change_time;
+    }
+    for (left, right) in merged.state_last_left.iter_mut().zip(b.state_last_left) {
+        *left = (*left).max(right);
+    }
+    merged
+}
+
+fn merge_histogram(
+    a: LaunchHistogramRecord,
+    b: LaunchHistogramRecord,
+    decay: f32,
+) -> LaunchHistogramRecord {
+    let now = a.updated_at.max(b.updated_at);
+    let decayed = |record: LaunchHistogramRecord| {
+        let mut histogram = LaunchHistogram {
+            weights: record.weights,
+            updated_at: record.updated_at,
+        };
+        histogram.decay_to(now, decay);
+        histogram.weights
+    };
+    let mut weights = decayed(b);
+    for (weight, other) in weights.iter_mut().zip(decayed(a.clone())) {
+        *weight += other;
+    }
+    LaunchHistogramRecord {
+        weights,
+        updated_at: now,
+        ..a
+    }
+}
+
+fn merge_sequence(mut a: LaunchSequenceRecord, b: LaunchSequenceRecord) -> LaunchSequenceRecord {
+    for (follower, weight) in b.followers {
+        match a.followers.iter_mut().find(|(path, _)| *path == follower) {
+            Some((_, existing)) => *existing += weight,
+            None => a.followers.push((follower, weight)),
+        }
+    }
+    a.updated_at = a.updated_at.max(b.updated_at);
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stores::HOURS_PER_WEEK;
+
+    fn snapshot(model_time: u64) -> StoresSnapshot {
+        StoresSnapshot {
+            meta: SnapshotMeta::default(),
+            state: StateSnapshot {
+                model_time,
+                last_accounting_time: model_time,
+                ..StateSnapshot::default()
+            },
+        }
+    }
+
+    fn exe(path: &str, total_running_time: u64, last_seen_time: u64) -> ExeRecord {
+        ExeRecord {
+            path: PathBuf::from(path),
+            total_running_time,
+            last_seen_time: Some(last_seen_time),
+        }
+    }
+
+    fn edge(a: &str, b: &str, only_a_leave: f32, both_running_time: u64) -> MarkovRecord {
+        MarkovRecord {
+            exe_a: PathBuf::from(a),
+            exe_b: PathBuf::from(b),
+            time_to_leave: [0.0, only_a_leave, 0.0, 0.0],
+            transition_prob: [[0.0; 4]; 4],
+            both_running_time,
+            state: 0,
+            last_change_time: 0,
+            state_last_left: [0; 4],
+        }
+    }
+
+    #[test]
+    fn exes_are_unioned_and_rebased_onto_the_later_model_time() {
+        let mut left = snapshot(1_000);
+        left.state.exes = vec![exe("/a", 100, 900), exe("/b", 10, 1_000)];
+        let mut right = snapshot(100);
+        right.state.exes = vec![exe("/b", 5, 50), exe("/c", 7, 100)];
+
+        let merged = merge_snapshots(left, right, &MergeOptions::default());
+        let exes: Vec<_> = merged
+            .state
+            .exes
+            .iter()
+            .map(|e| {
+                (
+                    e.path.to_str().unwrap(),
+                    e.total_running_time,
+                    e.last_seen_time,
+                )
+            })
+            .collect();
+        assert_eq!(
+            exes,
+            vec![
+                ("/a", 100, Some(900)),
+                ("/b", 15, Some(1_000)),
+                ("/c", 7, Some(1_000)),
+            ]
+        );
+        assert_eq!(merged.state.model_time, 1_000);
+        assert_eq!(merged.state.last_accounting_time, 1_000);
+    }
+
+    #[test]
+    fn half_life_discounts_exes_not_seen_recently() {
+        let mut left = snapshot(1_000);
+        left.state.exes = vec![exe("/a", 100, 1_000), exe("/b", 100, 800)];
+
+        let options = MergeOptions {
+            half_life: 100,
+            ..MergeOptions::default()
+        };
+        let merged = merge_snapshots(left, snapshot(0), &options);
+        let times: Vec<_> = merged
+            .state
+            .exes
+            .iter()
+            .map(|e| e.total_running_time)
+            .collect();
+        assert_eq!(times, vec![100, 25]);
+    }
+
+    #[test]
+    fn markov_edges_are_weighted_by_both_running_time_and_oriented() {
+        let mut left = snapshot(10);
+        left.state.markov_edges = vec![edge("/a", "/b", 10.0, 300)];
+        // Same pair written in the other order: its "only B" is our "only A".
+        let mut right = snapshot(10);
+        let mut reversed = edge("/b", "/a", 0.0, 100);
+        reversed.time_to_leave[2] = 50.0;
+        right.state.markov_edges = vec![reversed];
+
+        let merged = merge_snapshots(left, right, &MergeOptions::default());
+        assert_eq!(merged.state.markov_edges.len(), 1);
+        let edge = &merged.state.markov_edges[0];
+        assert_eq!(edge.exe_a, PathBuf::from("/a"));
+        assert_eq!(edge.both_running_time, 400);
+        assert!((edge.time_to_leave[1] - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn histograms_are_decayed_to_a_common_time_before_summing() {
+        let histogram = |updated_at: u64| LaunchHistogramRecord {
+            exe_path: PathBuf::from("/a"),
+            weights: [4.0; HOURS_PER_WEEK],
+            updated_at,
+        };
+        let mut left = snapshot(10);
+        left.state.launch_histograms = vec![histogram(1_000)];
+        let mut right = snapshot(10);
+        right.state.launch_histograms = vec![histogram(1_000 + 3_600)];
+
+        let options = MergeOptions {
+            histogram_decay: std::f32::consts::LN_2 / 3_600.0,
+            ..MergeOptions::default()
+        };
+        let merged = merge_snapshots(left, right, &options);
+        let histogram = &merged.state.launch_histograms[0];
+        assert_eq!(histogram.updated_at, 4_600);
+        // The older side lost half its weight over the hour between them.
+        assert!((histogram.weights[0] - 6.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,183 @@
+#![forbid(unsafe_code)]
+
+use crate::error::Error;
+use crate::persistence::{ActiveRecord, SNAPSHOT_SCHEMA_VERSION, StateSnapshot, StoresSnapshot};
+use tracing::{info, warn};
+
+/// A data migration from one schema version to the next.
+type Migration = fn(&mut StateSnapshot);
+
+/// Data migrations, indexed by the version they upgrade from minus one.
+/// Table changes are SQL migrations; these only rewrite row contents.
+const MIGRATIONS: &[Migration] = &[
+    // 1 -> 2: launch histograms added; nothing to rewrite.
+    |_| {},
+    // 2 -> 3: launch sequences added; nothing to rewrite.
+    |_| {},
+    // 3 -> 4: Markov edge state persisted. Start unknown edges the way
+    // loading used to: no exe running, last change at the model time.
+    |state| {
+        let now = state.model_time;
+        for edge in &mut state.markov_edges {
+            edge.state = 0;
+            edge.last_change_time = now;
+            edge.state_last_left = [now; 4];
+        }
+    },
+    // 4 -> 5: active set persisted. Treat every exe seen so far as active
+    // since it was last seen; the window prunes the rest on the next tick.
+    |state| {
+        state.active_exes = state
+            .exes
+            .iter()
+            .filter_map(|exe| {
+                Some(ActiveRecord {
+                    exe_path: exe.path.clone(),
+                    last_active: exe.last_seen_time?,
+                })
+            })
+            .collect();
+    },
+];
+
+/// Bring a snapshot written by an older schema version up to
+/// [`SNAPSHOT_SCHEMA_VERSION`]. Snapshots from newer versions are refused;
+/// ones written by a newer release at the same schema load with a warning.
+pub fn upgrade_snapshot(mut snapshot: StoresSnapshot) -> Result<StoresSnapshot, Error> {
+    let found = snapshot.meta.schema_version;
+    let app_version = snapshot.meta.app_version.as_deref();
+    if written_by_newer_release(app_version) {
+        warn!(
+            written_by = app_version,
+            running = env!("CARGO_PKG_VERSION"),
+            "state written by a newer release of the same schema"
+        );
+    }
+    if found > SNAPSHOT_SCHEMA_VERSION {
+        return Err(Error::NewerSchema {
+            found,
+            supported: SNAPSHOT_SCHEMA_VERSION,
+        });
+    }
+    if found == SNAPSHOT_SCHEMA_VERSION {
+        return Ok(snapshot);
+    }
+
+    for migration in &MIGRATIONS[found.max(1) as usize - 1..] {
+        migration(&mut snapshot.state);
+    }
+    info!(
+        from = found,
+        to = SNAPSHOT_SCHEMA_VERSION,
+        app_version = ?snapshot.meta.app_version,
+        "upgraded state snapshot"
+    );
+    snapshot.meta.schema_version = SNAPSHOT_SCHEMA_VERSION;
+    Ok(snapshot)
+}
+
+/// Whether `app_version` names a later release than this build. Versions
+/// that do not parse as `major.minor.patch` are not compared.
+fn written_by_newer_release(app_version: Option<&str>) -> bool {
+    let release = |version: &str| -> Option<[u64; 3]> {
+        let core = version.split(['-', '+']).next()?;
+        let mut parts = core.split('.').map(|part| part.parse().ok());
+        let release = [parts.next()??, parts.next()??, parts.next()??];
+        parts.next().is_none().then_some(release)
+    };
+    match (
+        app_version.and_then(release),
+        release(env!("CARGO_PKG_VERSION")),
+    ) {
+        (Some(stored), Some(running)) => stored > running,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::{ExeRecord, MarkovRecord, SnapshotMeta};
+    use std::path::PathBuf;
+
+    fn snapshot(schema_version: u32) -> StoresSnapshot {
+        StoresSnapshot {
+            meta: SnapshotMeta {
+                schema_version,
+                app_version: None,
+                created_at: None,
+            },
+            state: StateSnapshot {
+                model_time: 7,
+                last_accounting_time: 0,
+                ..StateSnapshot::default()
+            },
+        }
+    }
+
+    #[test]
+    fn every_older_version_has_a_migration_path() {
+        assert_eq!(MIGRATIONS.len() as u32, SNAPSHOT_SCHEMA_VERSION - 1);
+        for version in 0..=SNAPSHOT_SCHEMA_VERSION {
+            let upgraded = upgrade_snapshot(snapshot(version)).unwrap();
+            assert_eq!(upgraded.meta.schema_version, SNAPSHOT_SCHEMA_VERSION);
+            assert_eq!(upgraded.state.model_time, 7);
+        }
+    }
+
+    #[test]
+    fn markov_edges_from_version_3_start_at_model_time() {
+        let mut old = snapshot(3);
+        old.state.markov_edges.push(MarkovRecord {
+            exe_a: PathBuf::from("/bin/a"),
+            exe_b: PathBuf::from("/bin/b"),
+            time_to_leave: [0.0; 4],
+            transition_prob: [[0.0; 4]; 4],
+            both_running_time: 0,
+            state: 0,
+            last_change_time: 0,
+            state_last_left: [0; 4],
+        });
+        let upgraded = upgrade_snapshot(old).unwrap();
+        let edge = &upgraded.state.markov_edges[0];
+        assert_eq!(edge.last_change_time, 7);
+        assert_eq!(edge.state_last_left, [7; 4]);
+    }
+
+    #[test]
+    fn exes_from_version_4_are_active_since_last_seen() {
+        let mut old = snapshot(4);
+        for (path, last_seen_time) in [("/bin/a", Some(5)), ("/bin/b", None)] {
+            old.state.exes.push(ExeRecord {
+                path: PathBuf::from(path),
+                total_running_time: 0,
+                last_seen_time,
+            });
+        }
+        let upgraded = upgrade_snapshot(old).unwrap();
+        let active = &upgraded.state.active_exes;
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].exe_path, PathBuf::from("/bin/a"));
+        assert_eq!(active[0].last_active, 5);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let err = upgrade_snapshot(snapshot(SNAPSHOT_SCHEMA_VERSION + 1)).unwrap_err();
+        assert!(matches!(err, Error::NewerSchema { .. }));
+    }
+
+    #[test]
+    fn newer_releases_at_the_same_schema_are_noticed() {
+        let mut newer = snapshot(SNAPSHOT_SCHEMA_VERSION);
+        newer.meta.app_version = Some("999.0.0".to_owned());
+        assert!(written_by_newer_release(newer.meta.app_version.as_deref()));
+        // Still loads: the schema is the same.
+        assert!(upgrade_snapshot(newer).is_ok());
+
+        for same_or_older in [Some(env!("CARGO_PKG_VERSION")), Some("0.0.1"), None] {
+            assert!(!written_by_newer_release(same_or_older));
+        }
+        assert!(!written_by_newer_release(Some("not a version")));
+    }
+}
@@ -1,10 +1,24 @@
 #![forbid(unsafe_code)]
 
+mod delta;
+mod history;
+mod merge;
+mod migrate;
+mod portable;
+mod recovery;
 mod repo;
+mod rkyv_file;
 mod snapshot;
 
+pub use delta::{DeltaTracker, Row, SnapshotDelta, TableDelta};
+pub use history::{AppUsage, DayUsage, LaunchUsage, UsageRecord};
+pub use merge::{MergeOptions, merge_snapshots};
+pub use migrate::upgrade_snapshot;
+pub use portable::{PORTABLE_FORMAT, PORTABLE_VERSION, PortableSnapshot, overlay_snapshot};
+pub use recovery::Recovery;
 pub use repo::{NoopRepository, SqliteRepository, StateRepository};
+pub use rkyv_file::RkyvFileRepository;
 pub use snapshot::{
-    ExeMapRecord, ExeRecord, MapRecord, MarkovRecord, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta,
-    StateSnapshot, StoresSnapshot,
+    ActiveRecord, ExeMapRecord, ExeRecord, LaunchHistogramRecord, LaunchSequenceRecord, MapRecord,
+    MarkovRecord, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta, StateSnapshot, StoresSnapshot,
 };
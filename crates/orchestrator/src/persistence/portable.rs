@@ -0,0 +1,191 @@
+#![forbid(unsafe_code)]
+
+use crate::error::Error;
+use crate::persistence::merge::{merge_rows, rebased};
+use crate::persistence::{StateSnapshot, StoresSnapshot, upgrade_snapshot};
+use serde::{Deserialize, Serialize};
+
+/// Identifies an exported model regardless of encoding.
+pub const PORTABLE_FORMAT: &str = "preload-ng-state";
+
+/// Version of the export envelope. The snapshot inside carries its own
+/// schema version.
+pub const PORTABLE_VERSION: u32 = 1;
+
+/// A learned model in a storage-independent form, for moving state between
+/// machines. Serialized as JSON or CBOR by the CLI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortableSnapshot {
+    pub format: String,
+    pub version: u32,
+    pub snapshot: StoresSnapshot,
+}
+
+impl PortableSnapshot {
+    pub fn new(snapshot: StoresSnapshot) -> Self {
+        Self {
+            format: PORTABLE_FORMAT.to_owned(),
+            version: PORTABLE_VERSION,
+            snapshot,
+        }
+    }
+
+    /// Validate the envelope and upgrade the snapshot to the current
+    /// schema version.
+    pub fn into_snapshot(self) -> Result<StoresSnapshot, Error> {
+        if self.format != PORTABLE_FORMAT || self.version > PORTABLE_VERSION {
+            return Err(Error::UnsupportedExport {
+                format: self.format,
+                version: self.version,
+            });
+        }
+        upgrade_snapshot(self.snapshot)
+    }
+}
+
+/// Lay `imported` over `base`: imported rows replace base rows with the same
+/// key, other base rows are kept. Both sides are first rebased onto the
+/// later model time and their Markov edges oriented, as for
+/// [`merge_snapshots`](crate::persistence::merge_snapshots), so stamps stay
+/// comparable and a pair written in either order is one row.
+pub fn overlay_snapshot(base: StoresSnapshot, imported: StoresSnapshot) -> StoresSnapshot {
+    let StoresSnapshot { meta, state } = imported;
+    let model_time = base.state.model_time.max(state.model_time);
+    let base = rebased(base.state, model_time);
+    let state = rebased(state, model_time);
+    StoresSnapshot {
+        meta,
+        state: StateSnapshot {
+            model_time,
+            last_accounting_time: base.last_accounting_time.max(state.last_accounting_time),
+            exes: merge_rows(base.exes, state.exes, replace),
+            maps: merge_rows(base.maps, state.maps, replace),
+            exe_maps: merge_rows(base.exe_maps, state.exe_maps, replace),
+            markov_edges: merge_rows(base.markov_edges, state.markov_edges, replace),
+            launch_histograms: merge_rows(base.launch_histograms, state.launch_histograms, replace),
+            launch_sequences: merge_rows(base.launch_sequences, state.launch_sequences, replace),
+            active_exes: merge_rows(base.active_exes, state.active_exes, replace),
+            cold_markov_edges: merge_rows(base.cold_markov_edges, state.cold_markov_edges, replace),
+        },
+    }
+}
+
+fn replace<R>(_: R, imported: R) -> R {
+    imported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::{ActiveRecord, ExeRecord, MarkovRecord, SnapshotMeta};
+    use std::path::PathBuf;
+
+    fn snapshot(model_time: u64, exes: &[(&str, u64)]) -> StoresSnapshot {
+        StoresSnapshot {
+            meta: SnapshotMeta::default(),
+            state: StateSnapshot {
+                model_time,
+                last_accounting_time: 0,
+                exes: exes
+                    .iter()
+                    .map(|(path, time)| ExeRecord {
+                        path: PathBuf::from(path),
+                        total_running_time: *time,
+                        last_seen_time: None,
+                    })
+                    .collect(),
+                ..StateSnapshot::default()
+            },
+        }
+    }
+
+    #[test]
+    fn overlay_prefers_imported_rows_and_keeps_the_rest() {
+        let merged = overlay_snapshot(
+            snapshot(10, &[("/a", 1), ("/b", 2)]),
+            snapshot(5, &[("/b", 9), ("/c", 3)]),
+        );
+        let exes: Vec<_> = merged
+            .state
+            .exes
+            .iter()
+            .map(|e| (e.path.to_str().unwrap(), e.total_running_time))
+            .collect();
+        assert_eq!(exes, vec![("/a", 1), ("/b", 9), ("/c", 3)]);
+        assert_eq!(merged.state.model_time, 10);
+    }
+
+    fn edge(a: &str, b: &str, time_to_leave: [f32; 4], last_change_time: u64) -> MarkovRecord {
+        MarkovRecord {
+            exe_a: PathBuf::from(a),
+            exe_b: PathBuf::from(b),
+            time_to_leave,
+            transition_prob: [[0.0; 4]; 4],
+            both_running_time: 0,
+            state: 1,
+            last_change_time,
+            state_last_left: [0, last_change_time, 0, 0],
+        }
+    }
+
+    #[test]
+    fn overlay_orients_edges_and_rebases_onto_the_later_model_time() {
+        let mut base = snapshot(100, &[]);
+        base.state.exes = vec![ExeRecord {
+            path: PathBuf::from("/a"),
+            total_running_time: 50,
+            last_seen_time: Some(90),
+        }];
+        base.state.markov_edges = vec![edge("/a", "/b", [0.0, 1.0, 0.0, 0.0], 80)];
+        base.state.cold_markov_edges = vec![edge("/a", "/c", [0.0, 1.0, 0.0, 0.0], 0)];
+        base.state.active_exes = vec![ActiveRecord {
+            exe_path: PathBuf::from("/a"),
+            last_active: 90,
+        }];
+        // The same pairs written by a machine that ordered them the other
+        // way, with its clock further along.
+        let mut imported = snapshot(1_000, &[]);
+        imported.state.markov_edges = vec![edge("/b", "/a", [0.0, 7.0, 0.0, 0.0], 950)];
+        imported.state.cold_markov_edges = vec![edge("/c", "/a", [0.0, 7.0, 0.0, 0.0], 0)];
+
+        let merged = overlay_snapshot(base, imported).state;
+        assert_eq!(merged.model_time, 1_000);
+        assert_eq!(merged.exes[0].last_seen_time, Some(990));
+        assert_eq!(merged.active_exes[0].last_active, 990);
+
+        assert_eq!(merged.markov_edges.len(), 1);
+        let edge = &merged.markov_edges[0];
+        assert_eq!(edge.exe_a, PathBuf::from("/a"));
+        // Imported as (/b, /a): its "only /b running" state and leave time
+        // are state 2 of the oriented pair.
+        assert_eq!(edge.time_to_leave, [0.0, 0.0, 7.0, 0.0]);
+        assert_eq!(edge.state, 2);
+        assert_eq!(edge.last_change_time, 950);
+        assert_eq!(edge.state_last_left, [0, 0, 950, 0]);
+
+        assert_eq!(merged.cold_markov_edges.len(), 1);
+        assert_eq!(merged.cold_markov_edges[0].exe_a, PathBuf::from("/a"));
+        assert_eq!(merged.cold_markov_edges[0].time_to_leave[2], 7.0);
+    }
+
+    #[test]
+    fn overlay_rebases_imported_stamps_onto_a_later_base() {
+        let base = snapshot(1_000, &[("/a", 1)]);
+        let mut imported = snapshot(100, &[]);
+        imported.state.markov_edges = vec![edge("/a", "/b", [0.0; 4], 80)];
+
+        let merged = overlay_snapshot(base, imported).state;
+        assert_eq!(merged.model_time, 1_000);
+        assert_eq!(merged.markov_edges[0].last_change_time, 980);
+    }
+
+    #[test]
+    fn foreign_envelopes_are_rejected() {
+        let mut portable = PortableSnapshot::new(snapshot(0, &[]));
+        portable.version = PORTABLE_VERSION + 1;
+        assert!(matches!(
+            portable.into_snapshot(),
+            Err(Error::UnsupportedExport { .. })
+        ));
+    }
+}
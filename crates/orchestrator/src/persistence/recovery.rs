@@ -0,0 +1,211 @@
+#![forbid(unsafe_code)]
+
+use crate::error::Error;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Backups are refreshed at most this often.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// SQLite primary result codes for a damaged or foreign database file.
+const SQLITE_CORRUPT: i32 = 11;
+const SQLITE_NOTADB: i32 = 26;
+
+/// How a state database was opened.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Recovery {
+    /// The database passed its integrity check.
+    #[default]
+    Clean,
+    /// The database was unusable (corrupt, or from a newer schema version)
+    /// and moved to `quarantined`; the newest valid backup was restored in
+    /// its place.
+    RestoredBackup {
+        backup: PathBuf,
+        quarantined: PathBuf,
+    },
+    /// The database and all backups were unusable; an empty one was
+    /// created. The old database was moved to `quarantined`.
+    Reset { quarantined: PathBuf },
+}
+
+/// Whether `err` shows the stored state itself is unusable (corrupt,
+/// undecodable, or from a newer schema) rather than a failure to reach it,
+/// such as a full disk, a read-only mount, a lock, or missing permissions.
+pub(crate) fn is_unusable(err: &Error) -> bool {
+    match err {
+        Error::CorruptState(_) | Error::RkyvDeserialize(_) | Error::NewerSchema { .. } => true,
+        Error::Sqlx(err) => is_corrupt_database(err),
+        _ => false,
+    }
+}
+
+fn is_corrupt_database(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(err) => err
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, SQLITE_CORRUPT | SQLITE_NOTADB)),
+        sqlx::Error::Migrate(err) => match err.as_ref() {
+            sqlx::migrate::MigrateError::Execute(err)
+            | sqlx::migrate::MigrateError::ExecuteMigration(err, _) => is_corrupt_database(err),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether the newest backup of `path` is missing or older than a day.
+pub(crate) fn backup_due(path: &Path) -> bool {
+    std::fs::metadata(backup_path(path, 1))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_none_or(|age| age >= BACKUP_INTERVAL)
+}
+
+/// `path` with `suffix` appended to its file name.
+pub(crate) fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Backup slot `index` (1 = newest) of the database at `path`.
+pub(crate) fn backup_path(path: &Path, index: usize) -> PathBuf {
+    sibling(path, &format!(".bak.{index}"))
+}
+
+/// Existing backups of `path`, newest first. Every slot on disk counts,
+/// so one missing slot does not hide the older backups behind it.
+pub(crate) fn existing_backups(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let prefix = format!("{}.bak.", name.to_string_lossy());
+    let mut slots: Vec<usize> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?.strip_prefix(&prefix)?.parse().ok()
+        })
+        .filter(|&index| index > 0)
+        .collect();
+    slots.sort_unstable();
+    slots
+        .into_iter()
+        .map(|index| backup_path(path, index))
+        .collect()
+}
+
+/// Shift backups one slot older, keeping at most `keep`, and move `fresh`
+/// into the newest slot.
+pub(crate) fn rotate_backups(path: &Path, fresh: &Path, keep: usize) -> io::Result<()> {
+    remove_if_exists(&backup_path(path, keep))?;
+    for index in (1..keep).rev() {
+        let from = backup_path(path, index);
+        if from.exists() {
+            std::fs::rename(&from, backup_path(path, index + 1))?;
+        }
+    }
+    std::fs::rename(fresh, backup_path(path, 1))
+}
+
+/// Move the database at `path` (with its WAL and shared-memory files) to
+/// `path` + `suffix`, returning where it went. If an earlier failure is
+/// already quarantined there, a counter is appended (`.corrupt.1`,
+/// `.corrupt.2`, ...) so no quarantined database is ever overwritten.
+pub(crate) fn quarantine(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let base = sibling(path, suffix);
+    let target = std::iter::once(base.clone())
+        .chain((1..).map(|count| sibling(&base, &format!(".{count}"))))
+        .find(|target| {
+            ["", "-wal", "-shm"]
+                .iter()
+                .all(|suffix| !sibling(target, suffix).exists())
+        })
+        .expect("unbounded counter always finds a free name");
+    for suffix in ["", "-wal", "-shm"] {
+        let from = sibling(path, suffix);
+        let to = sibling(&target, suffix);
+        if from.exists() {
+            std::fs::rename(from, to)?;
+        }
+    }
+    Ok(target)
+}
+
+/// Remove the database at `path` with its WAL and shared-memory files.
+pub(crate) fn remove_database(path: &Path) -> io::Result<()> {
+    for suffix in ["", "-wal", "-shm"] {
+        remove_if_exists(&sibling(path, suffix))?;
+    }
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn rotation_keeps_newest_backups() {
+        let dir = tempdir().unwrap();
+        let db = dir.path().join("state.db");
+        for generation in 0..4 {
+            let fresh = sibling(&db, ".bak.tmp");
+            std::fs::write(&fresh, generation.to_string()).unwrap();
+            rotate_backups(&db, &fresh, 2).unwrap();
+        }
+
+        let backups = existing_backups(&db);
+        assert_eq!(backups.len(), 2);
+        assert_eq!(std::fs::read_to_string(&backups[0]).unwrap(), "3");
+        assert_eq!(std::fs::read_to_string(&backups[1]).unwrap(), "2");
+    }
+
+    #[test]
+    fn missing_slot_does_not_hide_older_backups() {
+        let dir = tempdir().unwrap();
+        let db = dir.path().join("state.db");
+        std::fs::write(backup_path(&db, 1), "1").unwrap();
+        std::fs::write(backup_path(&db, 3), "3").unwrap();
+        std::fs::write(sibling(&db, ".bak.tmp"), "tmp").unwrap();
+
+        assert_eq!(
+            existing_backups(&db),
+            vec![backup_path(&db, 1), backup_path(&db, 3)]
+        );
+    }
+
+    #[test]
+    fn repeated_quarantine_keeps_every_copy() {
+        let dir = tempdir().unwrap();
+        let db = dir.path().join("state.db");
+        std::fs::write(&db, "first").unwrap();
+        let first = quarantine(&db, ".corrupt").unwrap();
+        std::fs::write(&db, "second").unwrap();
+        let second = quarantine(&db, ".corrupt").unwrap();
+
+        assert_eq!(first, dir.path().join("state.db.corrupt"));
+        assert_eq!(second, dir.path().join("state.db.corrupt.1"));
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "second");
+        assert!(!db.exists());
+    }
+}
@@ -1,16 +1,23 @@
 #![forbid(unsafe_code)]
 
 use crate::error::Error;
+use crate::persistence::recovery::{
+    Recovery, backup_due, existing_backups, is_unusable, quarantine, remove_database,
+    rotate_backups, sibling,
+};
 use crate::persistence::{
-    ExeMapRecord, ExeRecord, MapRecord, MarkovRecord, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta,
-    StateSnapshot, StoresSnapshot,
+    ActiveRecord, ExeMapRecord, ExeRecord, LaunchHistogramRecord, LaunchSequenceRecord, MapRecord,
+    MarkovRecord, SNAPSHOT_SCHEMA_VERSION, SnapshotDelta, SnapshotMeta, StateSnapshot,
+    StoresSnapshot, UsageRecord,
 };
+use crate::stores::HOURS_PER_WEEK;
 use async_trait::async_trait;
-use sqlx::SqlitePool;
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
-use std::path::PathBuf;
-use std::time::SystemTime;
-use tracing::debug;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
 
 #[async_trait]
 pub trait StateRepository: Send + Sync {
@@ -18,6 +25,25 @@ pub trait StateRepository: Send + Sync {
     async fn load(&self) -> Result<StoresSnapshot, Error>;
     /// Persist a snapshot.
     async fn save(&self, snapshot: &StoresSnapshot) -> Result<(), Error>;
+    /// Persist only the rows in `delta`, the changes from the previously
+    /// saved state to `snapshot`. Defaults to writing the full snapshot.
+    async fn save_delta(
+        &self,
+        snapshot: &StoresSnapshot,
+        _delta: &SnapshotDelta,
+    ) -> Result<(), Error> {
+        self.save(snapshot).await
+    }
+    /// Append the summaries of ticks since the last call to the usage
+    /// history, keeping `retention` worth of entries. Defaults to keeping
+    /// no history.
+    async fn record_usage(
+        &self,
+        _records: &[UsageRecord],
+        _retention: Duration,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default)]
@@ -26,21 +52,7 @@ pub struct NoopRepository;
 #[async_trait]
 impl StateRepository for NoopRepository {
     async fn load(&self) -> Result<StoresSnapshot, Error> {
-        Ok(StoresSnapshot {
-            meta: SnapshotMeta {
-                schema_version: SNAPSHOT_SCHEMA_VERSION,
-                app_version: None,
-                created_at: None,
-            },
-            state: StateSnapshot {
-                model_time: 0,
-                last_accounting_time: 0,
-                exes: Vec::new(),
-                maps: Vec::new(),
-                exe_maps: Vec::new(),
-                markov_edges: Vec::new(),
-            },
-        })
+        Ok(StoresSnapshot::default())
     }
 
     async fn save(&self, _snapshot: &StoresSnapshot) -> Result<(), Error> {
@@ -52,17 +64,80 @@ impl StateRepository for NoopRepository {
 pub struct SqliteRepository {
     path: PathBuf,
     pool: SqlitePool,
+    backups: usize,
+    recovery: Recovery,
+    /// Snapshot decoded while verifying the database, handed to the first
+    /// load so opening does not decode every row twice.
+    preloaded: Arc<Mutex<Option<StoresSnapshot>>>,
 }
 
 impl SqliteRepository {
     /// Create a repository backed by a SQLite database file.
+    ///
+    /// A database that fails to open, migrate, pass `PRAGMA quick_check`,
+    /// or decode, or that was written by a newer schema version, is moved
+    /// aside and replaced by the newest backup that passes, or by an empty
+    /// database; see [`recovery`](Self::recovery). Errors that do not show
+    /// the database itself is damaged, such as a full disk, a read-only
+    /// mount, a busy lock, or missing permissions, are returned as is.
     pub async fn new(path: PathBuf) -> Result<Self, Error> {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
+        match Self::open_checked(&path).await {
+            Ok(repo) => Ok(repo),
+            Err(err) if is_unusable(&err) => Self::recover(path, err).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Create a new, migrated database at `path`, failing if one (or its
+    /// WAL or shared-memory file) already exists there. Unlike
+    /// [`new`](Self::new), an existing file is never opened, recovered or
+    /// replaced, so the result holds no state and no usage history.
+    pub async fn create(path: &Path) -> Result<Self, Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if let Some(existing) = ["", "-wal", "-shm"]
+            .into_iter()
+            .map(|suffix| sibling(path, suffix))
+            .find(|file| file.exists())
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", existing.display()),
+            )
+            .into());
+        }
+        Self::open_checked(path).await
+    }
+
+    /// Keep `backups` rotated copies of the database, refreshed by a save
+    /// once the newest copy is a day old. 0 disables backups.
+    pub fn with_backups(mut self, backups: usize) -> Self {
+        self.backups = backups;
+        self
+    }
+
+    /// What had to be done to open the database.
+    pub fn recovery(&self) -> &Recovery {
+        &self.recovery
+    }
+
+    pub(crate) fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// Close the database, checkpointing the WAL into the main file.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    async fn connect(path: &Path) -> Result<SqlitePool, Error> {
         let options = SqliteConnectOptions::new()
-            .filename(&path)
+            .filename(path)
             .create_if_missing(true)
             .journal_mode(SqliteJournalMode::Wal)
             .pragma("synchronous", "NORMAL")
@@ -73,13 +148,202 @@ impl SqliteRepository {
             .max_connections(1)
             .connect_with(options)
             .await?;
+        Ok(pool)
+    }
 
-        sqlx::migrate!("./migrations")
-            .run(&pool)
+    /// Open, migrate and verify the database at `path`.
+    async fn open_checked(path: &Path) -> Result<Self, Error> {
+        let pool = Self::connect(path).await?;
+        let repo = Self {
+            path: path.to_path_buf(),
+            pool,
+            backups: 0,
+            recovery: Recovery::Clean,
+            preloaded: Arc::default(),
+        };
+        match repo.verify().await {
+            Ok(snapshot) => {
+                *repo.preloaded() = Some(snapshot);
+                Ok(repo)
+            }
+            Err(err) => {
+                repo.pool.close().await;
+                Err(err)
+            }
+        }
+    }
+
+    /// Open the database at `path` read-only, for inspecting it without
+    /// migrating, recovering or otherwise modifying it. Fails when the file
+    /// is missing, fails `PRAGMA quick_check`, lacks migrations of this
+    /// build, or was written by a newer schema version.
+    pub async fn open_read_only(path: &Path) -> Result<Self, Error> {
+        let options = SqliteConnectOptions::new().filename(path).read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        let repo = Self {
+            path: path.to_path_buf(),
+            pool,
+            backups: 0,
+            recovery: Recovery::Clean,
+            preloaded: Arc::default(),
+        };
+        match repo.verify_read_only().await {
+            Ok(()) => Ok(repo),
+            Err(err) => {
+                repo.pool.close().await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn verify_read_only(&self) -> Result<(), Error> {
+        self.quick_check().await?;
+
+        let migrated = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let applied = if migrated > 0 {
+            sqlx::query_scalar::<_, i64>("SELECT version FROM _sqlx_migrations WHERE success = 1")
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            Vec::new()
+        };
+        let migrator = sqlx::migrate!("./migrations");
+        if let Some(missing) = migrator
+            .iter()
+            .find(|migration| !applied.contains(&migration.version))
+        {
+            return Err(Error::UnmigratedState {
+                path: self.path.clone(),
+                missing: missing.version,
+            });
+        }
+
+        self.check_schema_version().await
+    }
+
+    async fn quick_check(&self) -> Result<(), Error> {
+        let check = sqlx::query_scalar::<_, String>("PRAGMA quick_check")
+            .fetch_one(&self.pool)
+            .await?;
+        if check != "ok" {
+            return Err(Error::CorruptState(check));
+        }
+        Ok(())
+    }
+
+    /// Check the database and decode its snapshot.
+    async fn verify(&self) -> Result<StoresSnapshot, Error> {
+        self.quick_check().await?;
+
+        // Migrations applied by a newer build are tolerated here so the
+        // schema version check below can tell the database is newer.
+        let mut migrator = sqlx::migrate!("./migrations");
+        migrator
+            .set_ignore_missing(true)
+            .run(&self.pool)
             .await
             .map_err(sqlx::Error::from)?;
+        self.check_schema_version().await?;
 
-        Ok(Self { path, pool })
+        // Integrity checks do not cover row contents such as rkyv blobs.
+        self.load_snapshot().await
+    }
+
+    async fn check_schema_version(&self) -> Result<(), Error> {
+        let stored = sqlx::query_scalar!("SELECT schema_version FROM state WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        if let Some(found) = stored
+            && found > i64::from(SNAPSHOT_SCHEMA_VERSION)
+        {
+            return Err(Error::NewerSchema {
+                found: found as u32,
+                supported: SNAPSHOT_SCHEMA_VERSION,
+            });
+        }
+        Ok(())
+    }
+
+    fn preloaded(&self) -> MutexGuard<'_, Option<StoresSnapshot>> {
+        match self.preloaded.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Replace the unusable database at `path` by the newest valid backup,
+    /// or by an empty database when none is valid.
+    async fn recover(path: PathBuf, err: Error) -> Result<Self, Error> {
+        warn!(path = %path.display(), %err, "state database unusable; recovering");
+        let suffix = match err {
+            Error::NewerSchema { .. } => ".newer",
+            _ => ".corrupt",
+        };
+        let quarantined = quarantine(&path, suffix)?;
+
+        for backup in existing_backups(&path) {
+            std::fs::copy(&backup, &path)?;
+            match Self::open_checked(&path).await {
+                Ok(mut repo) => {
+                    warn!(
+                        backup = %backup.display(),
+                        quarantined = %quarantined.display(),
+                        "state restored from backup"
+                    );
+                    repo.recovery = Recovery::RestoredBackup {
+                        backup,
+                        quarantined,
+                    };
+                    return Ok(repo);
+                }
+                Err(err) if is_unusable(&err) => {
+                    warn!(backup = %backup.display(), %err, "state backup unusable");
+                    remove_database(&path)?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut repo = Self::open_checked(&path).await?;
+        warn!(
+            quarantined = %quarantined.display(),
+            "no usable state backup; starting with an empty model"
+        );
+        repo.recovery = Recovery::Reset { quarantined };
+        Ok(repo)
+    }
+
+    /// Snapshot the database into the newest backup slot, shifting older
+    /// backups down. `VACUUM INTO` gives a consistent copy while the
+    /// connection stays open.
+    async fn backup(&self) -> Result<(), Error> {
+        let fresh = sibling(&self.path, ".bak.tmp");
+        remove_database(&fresh)?;
+        let target = fresh.to_string_lossy().to_string();
+        sqlx::query("VACUUM INTO ?")
+            .bind(target)
+            .execute(&self.pool)
+            .await?;
+        rotate_backups(&self.path, &fresh, self.backups)?;
+        Ok(())
+    }
+
+    /// Refresh backups after a successful save once the newest is a day
+    /// old. Failures are logged only: the save itself went through.
+    async fn refresh_backups(&self) {
+        if self.backups > 0
+            && backup_due(&self.path)
+            && let Err(err) = self.backup().await
+        {
+            warn!(path = %self.path.display(), %err, "state backup failed");
+        }
     }
 
     async fn save_snapshot(&self, snapshot: &StoresSnapshot) -> Result<(), Error> {
@@ -94,103 +358,171 @@ impl SqliteRepository {
         sqlx::query!("DELETE FROM markovs")
             .execute(&mut *tx)
             .await?;
+        sqlx::query!("DELETE FROM launch_histograms")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM launch_sequences")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM active_exes")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM markov_cold")
+            .execute(&mut *tx)
+            .await?;
 
-        let meta = &snapshot.meta;
-        let created_at = meta
-            .created_at
-            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs().to_string());
-
-        let app_version = meta.app_version.clone();
-        let schema_version = meta.schema_version as i64;
-        let model_time = snapshot.state.model_time as i64;
-        let last_accounting_time = snapshot.state.last_accounting_time as i64;
-        sqlx::query!(
-            "INSERT INTO state (id, schema_version, app_version, created_at, model_time, last_accounting_time) \
-             VALUES (1, ?, ?, ?, ?, ?)",
-            schema_version,
-            app_version,
-            created_at,
-            model_time,
-            last_accounting_time
-        )
-        .execute(&mut *tx)
-        .await?;
+        write_state(&mut tx, snapshot).await?;
+        let state = &snapshot.state;
+        for exe in &state.exes {
+            write_exe(&mut tx, exe).await?;
+        }
+        for map in &state.maps {
+            write_map(&mut tx, map).await?;
+        }
+        for map in &state.exe_maps {
+            write_exe_map(&mut tx, map).await?;
+        }
+        for markov in &state.markov_edges {
+            write_markov(&mut tx, markov).await?;
+        }
+        for hist in &state.launch_histograms {
+            write_launch_histogram(&mut tx, hist).await?;
+        }
+        for seq in &state.launch_sequences {
+            write_launch_sequence(&mut tx, seq).await?;
+        }
+        for active in &state.active_exes {
+            write_active_exe(&mut tx, active).await?;
+        }
+        for markov in &state.cold_markov_edges {
+            write_cold_markov(&mut tx, markov).await?;
+        }
 
-        for exe in &snapshot.state.exes {
-            let path = exe.path.to_string_lossy().to_string();
-            let total_running_time = exe.total_running_time as i64;
-            let last_seen_time = exe.last_seen_time.map(|v| v as i64);
-            sqlx::query!(
-                "INSERT INTO exes (path, total_running_time, last_seen_time) VALUES (?, ?, ?)",
-                path,
-                total_running_time,
-                last_seen_time
-            )
-            .execute(&mut *tx)
+        tx.commit().await?;
+        debug!(path = %self.path.display(), "snapshot persisted");
+        Ok(())
+    }
+
+    /// Write only the rows in `delta`. Falls back to a full snapshot when
+    /// the database was not written by this schema version.
+    async fn save_delta_rows(
+        &self,
+        snapshot: &StoresSnapshot,
+        delta: &SnapshotDelta,
+    ) -> Result<(), Error> {
+        let stored = sqlx::query_scalar!("SELECT schema_version FROM state WHERE id = 1")
+            .fetch_optional(&self.pool)
             .await?;
+        if stored != Some(i64::from(SNAPSHOT_SCHEMA_VERSION)) {
+            return self.save_snapshot(snapshot).await;
         }
 
-        for map in &snapshot.state.maps {
-            let path = map.path.to_string_lossy().to_string();
-            let offset = map.offset as i64;
-            let length = map.length as i64;
-            let update_time = map.update_time as i64;
+        let mut tx = self.pool.begin().await?;
+        write_state(&mut tx, snapshot).await?;
+
+        for path in &delta.exes.removed {
+            let path = path.to_string_lossy().to_string();
+            sqlx::query!("DELETE FROM exes WHERE path = ?", path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for key in &delta.maps.removed {
+            let path = key.path.to_string_lossy().to_string();
+            let offset = key.offset as i64;
+            let length = key.length as i64;
             sqlx::query!(
-                "INSERT INTO maps (path, offset, length, update_time) VALUES (?, ?, ?, ?)",
+                "DELETE FROM maps WHERE path = ? AND offset = ? AND length = ?",
                 path,
                 offset,
-                length,
-                update_time
+                length
             )
             .execute(&mut *tx)
             .await?;
         }
-
-        for map in &snapshot.state.exe_maps {
-            let exe_path = map.exe_path.to_string_lossy().to_string();
-            let map_path = map.map_key.path.to_string_lossy().to_string();
-            let map_offset = map.map_key.offset as i64;
-            let map_length = map.map_key.length as i64;
-            let prob = map.prob as f64;
+        for (exe_path, map_key) in &delta.exe_maps.removed {
+            let exe_path = exe_path.to_string_lossy().to_string();
+            let map_path = map_key.path.to_string_lossy().to_string();
+            let map_offset = map_key.offset as i64;
+            let map_length = map_key.length as i64;
             sqlx::query!(
-                "INSERT INTO exe_maps (exe_path, map_path, map_offset, map_length, prob) \
-                 VALUES (?, ?, ?, ?, ?)",
+                "DELETE FROM exe_maps WHERE exe_path = ? AND map_path = ? AND map_offset = ? \
+                 AND map_length = ?",
                 exe_path,
                 map_path,
                 map_offset,
-                map_length,
-                prob
+                map_length
             )
             .execute(&mut *tx)
             .await?;
         }
-
-        for markov in &snapshot.state.markov_edges {
-            let ttl: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&markov.time_to_leave)
-                .map_err(|err| Error::RkyvSerialize(err.to_string()))?
-                .into();
-            let tp: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&markov.transition_prob)
-                .map_err(|err| Error::RkyvSerialize(err.to_string()))?
-                .into();
-            let exe_a = markov.exe_a.to_string_lossy().to_string();
-            let exe_b = markov.exe_b.to_string_lossy().to_string();
-            let both_running_time = markov.both_running_time as i64;
+        for (exe_a, exe_b) in &delta.markov_edges.removed {
+            let exe_a = exe_a.to_string_lossy().to_string();
+            let exe_b = exe_b.to_string_lossy().to_string();
+            sqlx::query!(
+                "DELETE FROM markovs WHERE exe_a = ? AND exe_b = ?",
+                exe_a,
+                exe_b
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        for exe_path in &delta.launch_histograms.removed {
+            let exe_path = exe_path.to_string_lossy().to_string();
+            sqlx::query!("DELETE FROM launch_histograms WHERE exe_path = ?", exe_path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for context in &delta.launch_sequences.removed {
+            let context = encode_context(context)?;
+            sqlx::query!("DELETE FROM launch_sequences WHERE context = ?", context)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for exe_path in &delta.active_exes.removed {
+            let exe_path = exe_path.to_string_lossy().to_string();
+            sqlx::query!("DELETE FROM active_exes WHERE exe_path = ?", exe_path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for (exe_a, exe_b) in &delta.cold_markov_edges.removed {
+            let exe_a = exe_a.to_string_lossy().to_string();
+            let exe_b = exe_b.to_string_lossy().to_string();
             sqlx::query!(
-                "INSERT INTO markovs (exe_a, exe_b, time_to_leave, transition_prob, both_running_time) \
-                 VALUES (?, ?, ?, ?, ?)",
+                "DELETE FROM markov_cold WHERE exe_a = ? AND exe_b = ?",
                 exe_a,
-                exe_b,
-                ttl,
-                tp,
-                both_running_time
+                exe_b
             )
             .execute(&mut *tx)
             .await?;
         }
 
+        for exe in &delta.exes.upserted {
+            write_exe(&mut tx, exe).await?;
+        }
+        for map in &delta.maps.upserted {
+            write_map(&mut tx, map).await?;
+        }
+        for map in &delta.exe_maps.upserted {
+            write_exe_map(&mut tx, map).await?;
+        }
+        for markov in &delta.markov_edges.upserted {
+            write_markov(&mut tx, markov).await?;
+        }
+        for hist in &delta.launch_histograms.upserted {
+            write_launch_histogram(&mut tx, hist).await?;
+        }
+        for seq in &delta.launch_sequences.upserted {
+            write_launch_sequence(&mut tx, seq).await?;
+        }
+        for active in &delta.active_exes.upserted {
+            write_active_exe(&mut tx, active).await?;
+        }
+        for markov in &delta.cold_markov_edges.upserted {
+            write_cold_markov(&mut tx, markov).await?;
+        }
+
         tx.commit().await?;
-        debug!(path = %self.path.display(), "snapshot persisted");
+        debug!(path = %self.path.display(), rows = delta.len(), "snapshot delta persisted");
         Ok(())
     }
 
@@ -200,14 +532,7 @@ impl SqliteRepository {
             app_version: None,
             created_at: None,
         };
-        let mut state = StateSnapshot {
-            model_time: 0,
-            last_accounting_time: 0,
-            exes: Vec::new(),
-            maps: Vec::new(),
-            exe_maps: Vec::new(),
-            markov_edges: Vec::new(),
-        };
+        let mut state = StateSnapshot::default();
 
         let row = sqlx::query!(
             "SELECT schema_version as \"schema_version!\", app_version, created_at, \
@@ -277,7 +602,8 @@ impl SqliteRepository {
 
         let rows = sqlx::query!(
             "SELECT exe_a as \"exe_a!\", exe_b as \"exe_b!\", time_to_leave as \"time_to_leave!\", \
-             transition_prob as \"transition_prob!\", both_running_time as \"both_running_time!\" \
+             transition_prob as \"transition_prob!\", both_running_time as \"both_running_time!\", \
+             state as \"state!\", last_change_time as \"last_change_time!\", state_last_left \
              FROM markovs"
         )
         .fetch_all(&self.pool)
@@ -290,12 +616,99 @@ impl SqliteRepository {
             let transition_prob: [[f32; 4]; 4] =
                 rkyv::from_bytes::<[[f32; 4]; 4], rkyv::rancor::Error>(&tp)
                     .map_err(|err| Error::RkyvDeserialize(err.to_string()))?;
+            // Rows from before schema version 4 have no state; the snapshot
+            // upgrade fills it in.
+            let state_last_left = match row.state_last_left {
+                Some(bytes) => rkyv::from_bytes::<[u64; 4], rkyv::rancor::Error>(&bytes)
+                    .map_err(|err| Error::RkyvDeserialize(err.to_string()))?,
+                None => [0; 4],
+            };
             state.markov_edges.push(MarkovRecord {
                 exe_a: PathBuf::from(row.exe_a),
                 exe_b: PathBuf::from(row.exe_b),
                 time_to_leave,
                 transition_prob,
                 both_running_time: row.both_running_time as u64,
+                state: row.state as u8,
+                last_change_time: row.last_change_time as u64,
+                state_last_left,
+            });
+        }
+
+        let rows = sqlx::query!(
+            "SELECT exe_path as \"exe_path!\", weights as \"weights!\", updated_at as \"updated_at!\" \
+             FROM launch_histograms"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            let weights: [f32; HOURS_PER_WEEK] =
+                rkyv::from_bytes::<[f32; HOURS_PER_WEEK], rkyv::rancor::Error>(&row.weights)
+                    .map_err(|err| Error::RkyvDeserialize(err.to_string()))?;
+            state.launch_histograms.push(LaunchHistogramRecord {
+                exe_path: PathBuf::from(row.exe_path),
+                weights,
+                updated_at: row.updated_at as u64,
+            });
+        }
+
+        let rows = sqlx::query!(
+            "SELECT context as \"context!\", followers as \"followers!\", updated_at as \"updated_at!\" \
+             FROM launch_sequences"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            let context = rkyv::from_bytes::<Vec<String>, rkyv::rancor::Error>(&row.context)
+                .map_err(|err| Error::RkyvDeserialize(err.to_string()))?;
+            let followers =
+                rkyv::from_bytes::<Vec<(String, f32)>, rkyv::rancor::Error>(&row.followers)
+                    .map_err(|err| Error::RkyvDeserialize(err.to_string()))?;
+            state.launch_sequences.push(LaunchSequenceRecord {
+                context: context.into_iter().map(PathBuf::from).collect(),
+                followers: followers
+                    .into_iter()
+                    .map(|(path, weight)| (PathBuf::from(path), weight))
+                    .collect(),
+                updated_at: row.updated_at as u64,
+            });
+        }
+
+        let rows = sqlx::query!(
+            "SELECT exe_path as \"exe_path!\", last_active as \"last_active!\" FROM active_exes"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            state.active_exes.push(ActiveRecord {
+                exe_path: PathBuf::from(row.exe_path),
+                last_active: row.last_active as u64,
+            });
+        }
+
+        let rows = sqlx::query!(
+            "SELECT exe_a as \"exe_a!\", exe_b as \"exe_b!\", time_to_leave as \"time_to_leave!\", \
+             transition_prob as \"transition_prob!\", both_running_time as \"both_running_time!\" \
+             FROM markov_cold"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            let time_to_leave =
+                rkyv::from_bytes::<[f32; 4], rkyv::rancor::Error>(&row.time_to_leave)
+                    .map_err(|err| Error::RkyvDeserialize(err.to_string()))?;
+            let transition_prob =
+                rkyv::from_bytes::<[[f32; 4]; 4], rkyv::rancor::Error>(&row.transition_prob)
+                    .map_err(|err| Error::RkyvDeserialize(err.to_string()))?;
+            state.cold_markov_edges.push(MarkovRecord {
+                exe_a: PathBuf::from(row.exe_a),
+                exe_b: PathBuf::from(row.exe_b),
+                time_to_leave,
+                transition_prob,
+                both_running_time: row.both_running_time as u64,
+                state: 0,
+                last_change_time: 0,
+                state_last_left: [0; 4],
             });
         }
 
@@ -306,10 +719,243 @@ impl SqliteRepository {
 #[async_trait]
 impl StateRepository for SqliteRepository {
     async fn load(&self) -> Result<StoresSnapshot, Error> {
-        self.load_snapshot().await
+        let preloaded = self.preloaded().take();
+        match preloaded {
+            Some(snapshot) => Ok(snapshot),
+            None => self.load_snapshot().await,
+        }
     }
 
     async fn save(&self, snapshot: &StoresSnapshot) -> Result<(), Error> {
-        self.save_snapshot(snapshot).await
+        self.preloaded().take();
+        self.save_snapshot(snapshot).await?;
+        self.refresh_backups().await;
+        Ok(())
+    }
+
+    async fn save_delta(
+        &self,
+        snapshot: &StoresSnapshot,
+        delta: &SnapshotDelta,
+    ) -> Result<(), Error> {
+        self.preloaded().take();
+        self.save_delta_rows(snapshot, delta).await?;
+        self.refresh_backups().await;
+        Ok(())
+    }
+
+    async fn record_usage(
+        &self,
+        records: &[UsageRecord],
+        retention: Duration,
+    ) -> Result<(), Error> {
+        self.append_usage(records, retention).await
     }
 }
+
+async fn write_state(conn: &mut SqliteConnection, snapshot: &StoresSnapshot) -> Result<(), Error> {
+    let meta = &snapshot.meta;
+    let created_at = meta
+        .created_at
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs().to_string());
+
+    let app_version = meta.app_version.clone();
+    let schema_version = meta.schema_version as i64;
+    let model_time = snapshot.state.model_time as i64;
+    let last_accounting_time = snapshot.state.last_accounting_time as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO state (id, schema_version, app_version, created_at, model_time, last_accounting_time) \
+         VALUES (1, ?, ?, ?, ?, ?)",
+        schema_version,
+        app_version,
+        created_at,
+        model_time,
+        last_accounting_time
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn write_exe(conn: &mut SqliteConnection, exe: &ExeRecord) -> Result<(), Error> {
+    let path = exe.path.to_string_lossy().to_string();
+    let total_running_time = exe.total_running_time as i64;
+    let last_seen_time = exe.last_seen_time.map(|v| v as i64);
+    sqlx::query!(
+        "INSERT OR REPLACE INTO exes (path, total_running_time, last_seen_time) VALUES (?, ?, ?)",
+        path,
+        total_running_time,
+        last_seen_time
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn write_map(conn: &mut SqliteConnection, map: &MapRecord) -> Result<(), Error> {
+    let path = map.path.to_string_lossy().to_string();
+    let offset = map.offset as i64;
+    let length = map.length as i64;
+    let update_time = map.update_time as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO maps (path, offset, length, update_time) VALUES (?, ?, ?, ?)",
+        path,
+        offset,
+        length,
+        update_time
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn write_exe_map(conn: &mut SqliteConnection, map: &ExeMapRecord) -> Result<(), Error> {
+    let exe_path = map.exe_path.to_string_lossy().to_string();
+    let map_path = map.map_key.path.to_string_lossy().to_string();
+    let map_offset = map.map_key.offset as i64;
+    let map_length = map.map_key.length as i64;
+    let prob = map.prob as f64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO exe_maps (exe_path, map_path, map_offset, map_length, prob) \
+         VALUES (?, ?, ?, ?, ?)",
+        exe_path,
+        map_path,
+        map_offset,
+        map_length,
+        prob
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn write_markov(conn: &mut SqliteConnection, markov: &MarkovRecord) -> Result<(), Error> {
+    let ttl: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&markov.time_to_leave)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into();
+    let tp: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&markov.transition_prob)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into();
+    let exe_a = markov.exe_a.to_string_lossy().to_string();
+    let exe_b = markov.exe_b.to_string_lossy().to_string();
+    let left: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&markov.state_last_left)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into();
+    let both_running_time = markov.both_running_time as i64;
+    let state = i64::from(markov.state);
+    let last_change_time = markov.last_change_time as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO markovs (exe_a, exe_b, time_to_leave, transition_prob, \
+         both_running_time, state, last_change_time, state_last_left) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        exe_a,
+        exe_b,
+        ttl,
+        tp,
+        both_running_time,
+        state,
+        last_change_time,
+        left
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn write_launch_histogram(
+    conn: &mut SqliteConnection,
+    hist: &LaunchHistogramRecord,
+) -> Result<(), Error> {
+    let weights: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&hist.weights)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into();
+    let exe_path = hist.exe_path.to_string_lossy().to_string();
+    let updated_at = hist.updated_at as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO launch_histograms (exe_path, weights, updated_at) VALUES (?, ?, ?)",
+        exe_path,
+        weights,
+        updated_at
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn write_launch_sequence(
+    conn: &mut SqliteConnection,
+    seq: &LaunchSequenceRecord,
+) -> Result<(), Error> {
+    let followers: Vec<(String, f32)> = seq
+        .followers
+        .iter()
+        .map(|(path, weight)| (path.to_string_lossy().to_string(), *weight))
+        .collect();
+    let context = encode_context(&seq.context)?;
+    let followers: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&followers)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into();
+    let updated_at = seq.updated_at as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO launch_sequences (context, followers, updated_at) VALUES (?, ?, ?)",
+        context,
+        followers,
+        updated_at
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn write_active_exe(conn: &mut SqliteConnection, active: &ActiveRecord) -> Result<(), Error> {
+    let exe_path = active.exe_path.to_string_lossy().to_string();
+    let last_active = active.last_active as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO active_exes (exe_path, last_active) VALUES (?, ?)",
+        exe_path,
+        last_active
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Write a dormant edge's statistics; its state fields are not stored.
+async fn write_cold_markov(
+    conn: &mut SqliteConnection,
+    markov: &MarkovRecord,
+) -> Result<(), Error> {
+    let ttl: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&markov.time_to_leave)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into();
+    let tp: Vec<u8> = rkyv::to_bytes::<rkyv::rancor::Error>(&markov.transition_prob)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into();
+    let exe_a = markov.exe_a.to_string_lossy().to_string();
+    let exe_b = markov.exe_b.to_string_lossy().to_string();
+    let both_running_time = markov.both_running_time as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO markov_cold (exe_a, exe_b, time_to_leave, transition_prob, \
+         both_running_time) VALUES (?, ?, ?, ?, ?)",
+        exe_a,
+        exe_b,
+        ttl,
+        tp,
+        both_running_time
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Encode a launch context the way it is keyed in `launch_sequences`.
+fn encode_context(context: &[PathBuf]) -> Result<Vec<u8>, Error> {
+    let context_paths: Vec<String> = context
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    Ok(rkyv::to_bytes::<rkyv::rancor::Error>(&context_paths)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?
+        .into())
+}
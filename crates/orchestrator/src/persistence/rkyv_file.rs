@@ -0,0 +1,563 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::MapKey;
+use crate::error::Error;
+use crate::persistence::recovery::{
+    Recovery, backup_due, existing_backups, is_unusable, quarantine, remove_database,
+    rotate_backups, sibling,
+};
+use crate::persistence::{
+    ActiveRecord, ExeMapRecord, ExeRecord, LaunchHistogramRecord, LaunchSequenceRecord, MapRecord,
+    MarkovRecord, NoopRepository, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta, StateRepository,
+    StateSnapshot, StoresSnapshot,
+};
+use crate::stores::HOURS_PER_WEEK;
+use async_trait::async_trait;
+use rkyv::rancor;
+use rkyv::util::AlignedVec;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+const MAGIC: [u8; 8] = *b"PRELOADR";
+
+/// Version of the file layout: header fields and the archived types below.
+const FILE_VERSION: u32 = 1;
+
+/// Magic, version, CRC-32 of the payload, payload length, and padding so the
+/// payload starts 16-byte aligned for in-place validation.
+const HEADER_LEN: usize = 32;
+
+/// Stores the whole snapshot as a single rkyv archive behind a checksummed
+/// header. Each save writes a temporary file and renames it over the old
+/// one, so a crash leaves either the previous or the new state. Loads
+/// validate the archive in place with `bytecheck` before decoding it.
+#[derive(Debug, Clone)]
+pub struct RkyvFileRepository {
+    path: PathBuf,
+    backups: usize,
+    recovery: Recovery,
+    /// Set by [`open_read_only`](Self::open_read_only); saves then fail.
+    read_only: bool,
+    /// Snapshot decoded while opening, handed to the first load so the
+    /// archive is not read and decoded twice.
+    preloaded: Arc<Mutex<Option<StoresSnapshot>>>,
+}
+
+impl RkyvFileRepository {
+    /// Create a repository backed by the archive at `path`.
+    ///
+    /// A file that fails its checksum or validation, or that was written by
+    /// a newer schema version, is moved aside and replaced by the newest
+    /// backup that passes; with none, the model starts empty. See
+    /// [`recovery`](Self::recovery). Errors reading the file, such as
+    /// missing permissions, are returned as is.
+    pub async fn new(path: PathBuf) -> Result<Self, Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut repo = Self {
+            path,
+            backups: 0,
+            recovery: Recovery::Clean,
+            read_only: false,
+            preloaded: Arc::default(),
+        };
+        match repo.read().await {
+            Ok(snapshot) => *repo.preloaded() = Some(snapshot),
+            Err(err) if is_unusable(&err) => repo.recovery = repo.recover(err).await?,
+            Err(err) => return Err(err),
+        }
+        Ok(repo)
+    }
+
+    /// Open the archive at `path` read-only, for inspecting it without
+    /// recovering or otherwise modifying it. Fails when the file is
+    /// missing, fails its checksum or validation, or was written by a newer
+    /// schema version; saves through the repository fail as well.
+    pub async fn open_read_only(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("state archive not found: {}", path.display()),
+            )
+            .into());
+        }
+        let repo = Self {
+            path: path.to_path_buf(),
+            backups: 0,
+            recovery: Recovery::Clean,
+            read_only: true,
+            preloaded: Arc::default(),
+        };
+        *repo.preloaded() = Some(repo.read().await?);
+        Ok(repo)
+    }
+
+    /// Keep `backups` rotated copies of the archive, refreshed by a save
+    /// once the newest copy is a day old. 0 disables backups.
+    pub fn with_backups(mut self, backups: usize) -> Self {
+        self.backups = backups;
+        self
+    }
+
+    /// What had to be done to open the archive.
+    pub fn recovery(&self) -> &Recovery {
+        &self.recovery
+    }
+
+    /// Read and decode the archive; a missing file is an empty model.
+    async fn read(&self) -> Result<StoresSnapshot, Error> {
+        let path = self.path.clone();
+        let bytes = tokio::task::spawn_blocking(move || read_file(&path))
+            .await
+            .map_err(std::io::Error::other)??;
+        match bytes {
+            Some(bytes) => decode(&bytes),
+            None => NoopRepository.load().await,
+        }
+    }
+
+    /// Move the unusable archive aside and restore the newest valid backup.
+    async fn recover(&self, err: Error) -> Result<Recovery, Error> {
+        warn!(path = %self.path.display(), %err, "state archive unusable; recovering");
+        let suffix = match err {
+            Error::NewerSchema { .. } => ".newer",
+            _ => ".corrupt",
+        };
+        let quarantined = quarantine(&self.path, suffix)?;
+
+        for backup in existing_backups(&self.path) {
+            std::fs::copy(&backup, &self.path)?;
+            match self.read().await {
+                Ok(snapshot) => {
+                    *self.preloaded() = Some(snapshot);
+                    warn!(
+                        backup = %backup.display(),
+                        quarantined = %quarantined.display(),
+                        "state restored from backup"
+                    );
+                    return Ok(Recovery::RestoredBackup {
+                        backup,
+                        quarantined,
+                    });
+                }
+                Err(err) if is_unusable(&err) => {
+                    warn!(backup = %backup.display(), %err, "state backup unusable");
+                    remove_database(&self.path)?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        warn!(
+            quarantined = %quarantined.display(),
+            "no usable state backup; starting with an empty model"
+        );
+        Ok(Recovery::Reset { quarantined })
+    }
+
+    fn preloaded(&self) -> MutexGuard<'_, Option<StoresSnapshot>> {
+        match self.preloaded.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Copy the freshly written archive into the newest backup slot once
+    /// that is a day old. Failures are logged only: the save itself went
+    /// through.
+    fn refresh_backups(&self) {
+        if self.backups == 0 || !backup_due(&self.path) {
+            return;
+        }
+        let fresh = sibling(&self.path, ".bak.tmp");
+        let result = std::fs::copy(&self.path, &fresh)
+            .and_then(|_| rotate_backups(&self.path, &fresh, self.backups));
+        if let Err(err) = result {
+            warn!(path = %self.path.display(), %err, "state backup failed");
+        }
+    }
+}
+
+#[async_trait]
+impl StateRepository for RkyvFileRepository {
+    async fn load(&self) -> Result<StoresSnapshot, Error> {
+        let preloaded = self.preloaded().take();
+        match preloaded {
+            Some(snapshot) => Ok(snapshot),
+            None => self.read().await,
+        }
+    }
+
+    async fn save(&self, snapshot: &StoresSnapshot) -> Result<(), Error> {
+        if self.read_only {
+            return Err(std::io::Error::other("state archive is open read-only").into());
+        }
+        self.preloaded().take();
+        let bytes = encode(snapshot)?;
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || write_atomic(&path, &bytes))
+            .await
+            .map_err(std::io::Error::other)??;
+        self.refresh_backups();
+        debug!(path = %self.path.display(), "snapshot persisted");
+        Ok(())
+    }
+}
+
+fn read_file(path: &Path) -> std::io::Result<Option<AlignedVec>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut bytes = AlignedVec::new();
+    bytes.extend_from_reader(&mut file)?;
+    Ok(Some(bytes))
+}
+
+/// Write `bytes` next to `path`, flush them to disk, and rename over `path`.
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = sibling(path, ".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+    if let Some(parent) = path.parent()
+        && let Ok(dir) = File::open(parent)
+    {
+        dir.sync_all()?;
+    }
+    Ok(())
+}
+
+fn encode(snapshot: &StoresSnapshot) -> Result<Vec<u8>, Error> {
+    let archive = ArchiveFile::from(snapshot);
+    let payload = rkyv::to_bytes::<rancor::Error>(&archive)
+        .map_err(|err| Error::RkyvSerialize(err.to_string()))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&FILE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.resize(HEADER_LEN, 0);
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+fn decode(bytes: &AlignedVec) -> Result<StoresSnapshot, Error> {
+    let corrupt = |reason: &str| Error::CorruptState(reason.to_owned());
+    if bytes.len() < HEADER_LEN || bytes[..8] != MAGIC {
+        return Err(corrupt("not a state archive"));
+    }
+    let version = u32::from_le_bytes(bytes[8..12].try_into().expect("4 bytes"));
+    let checksum = u32::from_le_bytes(bytes[12..16].try_into().expect("4 bytes"));
+    let len = u64::from_le_bytes(bytes[16..24].try_into().expect("8 bytes"));
+    if version != FILE_VERSION {
+        return Err(Error::CorruptState(format!(
+            "unsupported archive version {version}"
+        )));
+    }
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() as u64 != len {
+        return Err(corrupt("truncated archive"));
+    }
+    if crc32fast::hash(payload) != checksum {
+        return Err(corrupt("archive checksum mismatch"));
+    }
+
+    let file = rkyv::from_bytes::<ArchiveFile, rancor::Error>(payload)
+        .map_err(|err| Error::CorruptState(err.to_string()))?;
+    if file.state.schema_version > SNAPSHOT_SCHEMA_VERSION {
+        return Err(Error::NewerSchema {
+            found: file.state.schema_version,
+            supported: SNAPSHOT_SCHEMA_VERSION,
+        });
+    }
+    Ok(file.into())
+}
+
+/// Root of the archive: the model state plus the active set and cold edges.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+struct ArchiveFile {
+    state: ArchiveState,
+    active_exes: Vec<(String, u64)>,
+    cold_markov_edges: Vec<ArchiveMarkov>,
+}
+
+/// Archived form of [`StoresSnapshot`]. Paths are stored as strings, like
+/// the SQLite tables.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+struct ArchiveState {
+    schema_version: u32,
+    app_version: Option<String>,
+    created_at: Option<u64>,
+    model_time: u64,
+    last_accounting_time: u64,
+    exes: Vec<(String, u64, Option<u64>)>,
+    maps: Vec<(String, u64, u64, u64)>,
+    exe_maps: Vec<(String, String, u64, u64, f32)>,
+    markov_edges: Vec<ArchiveMarkov>,
+    launch_histograms: Vec<ArchiveHistogram>,
+    launch_sequences: Vec<ArchiveSequence>,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+struct ArchiveMarkov {
+    exe_a: String,
+    exe_b: String,
+    time_to_leave: [f32; 4],
+    transition_prob: [[f32; 4]; 4],
+    both_running_time: u64,
+    state: u8,
+    last_change_time: u64,
+    state_last_left: [u64; 4],
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+struct ArchiveHistogram {
+    exe_path: String,
+    weights: [f32; HOURS_PER_WEEK],
+    updated_at: u64,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+struct ArchiveSequence {
+    context: Vec<String>,
+    followers: Vec<(String, f32)>,
+    updated_at: u64,
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+impl From<&MarkovRecord> for ArchiveMarkov {
+    fn from(edge: &MarkovRecord) -> Self {
+        Self {
+            exe_a: path_string(&edge.exe_a),
+            exe_b: path_string(&edge.exe_b),
+            time_to_leave: edge.time_to_leave,
+            transition_prob: edge.transition_prob,
+            both_running_time: edge.both_running_time,
+            state: edge.state,
+            last_change_time: edge.last_change_time,
+            state_last_left: edge.state_last_left,
+        }
+    }
+}
+
+impl From<ArchiveMarkov> for MarkovRecord {
+    fn from(edge: ArchiveMarkov) -> Self {
+        Self {
+            exe_a: PathBuf::from(edge.exe_a),
+            exe_b: PathBuf::from(edge.exe_b),
+            time_to_leave: edge.time_to_leave,
+            transition_prob: edge.transition_prob,
+            both_running_time: edge.both_running_time,
+            state: edge.state,
+            last_change_time: edge.last_change_time,
+            state_last_left: edge.state_last_left,
+        }
+    }
+}
+
+impl From<&StoresSnapshot> for ArchiveFile {
+    fn from(snapshot: &StoresSnapshot) -> Self {
+        let state = &snapshot.state;
+        Self {
+            state: ArchiveState::from(snapshot),
+            active_exes: state
+                .active_exes
+                .iter()
+                .map(|active| (path_string(&active.exe_path), active.last_active))
+                .collect(),
+            cold_markov_edges: state
+                .cold_markov_edges
+                .iter()
+                .map(ArchiveMarkov::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<ArchiveFile> for StoresSnapshot {
+    fn from(file: ArchiveFile) -> Self {
+        let mut snapshot = StoresSnapshot::from(file.state);
+        snapshot.state.active_exes = file
+            .active_exes
+            .into_iter()
+            .map(|(exe_path, last_active)| ActiveRecord {
+                exe_path: PathBuf::from(exe_path),
+                last_active,
+            })
+            .collect();
+        snapshot.state.cold_markov_edges = file
+            .cold_markov_edges
+            .into_iter()
+            .map(MarkovRecord::from)
+            .collect();
+        snapshot
+    }
+}
+
+impl From<&StoresSnapshot> for ArchiveState {
+    fn from(snapshot: &StoresSnapshot) -> Self {
+        let state = &snapshot.state;
+        Self {
+            schema_version: snapshot.meta.schema_version,
+            app_version: snapshot.meta.app_version.clone(),
+            created_at: snapshot
+                .meta
+                .created_at
+                .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|since| since.as_secs()),
+            model_time: state.model_time,
+            last_accounting_time: state.last_accounting_time,
+            exes: state
+                .exes
+                .iter()
+                .map(|exe| {
+                    let path = path_string(&exe.path);
+                    (path, exe.total_running_time, exe.last_seen_time)
+                })
+                .collect(),
+            maps: state
+                .maps
+                .iter()
+                .map(|map| {
+                    (
+                        path_string(&map.path),
+                        map.offset,
+                        map.length,
+                        map.update_time,
+                    )
+                })
+                .collect(),
+            exe_maps: state
+                .exe_maps
+                .iter()
+                .map(|row| {
+                    let exe = path_string(&row.exe_path);
+                    let key = &row.map_key;
+                    (
+                        exe,
+                        path_string(&key.path),
+                        key.offset,
+                        key.length,
+                        row.prob,
+                    )
+                })
+                .collect(),
+            markov_edges: state.markov_edges.iter().map(ArchiveMarkov::from).collect(),
+            launch_histograms: state
+                .launch_histograms
+                .iter()
+                .map(|hist| ArchiveHistogram {
+                    exe_path: path_string(&hist.exe_path),
+                    weights: hist.weights,
+                    updated_at: hist.updated_at,
+                })
+                .collect(),
+            launch_sequences: state
+                .launch_sequences
+                .iter()
+                .map(|seq| ArchiveSequence {
+                    context: seq.context.iter().map(|path| path_string(path)).collect(),
+                    followers: seq
+                        .followers
+                        .iter()
+                        .map(|(path, weight)| (path_string(path), *weight))
+                        .collect(),
+                    updated_at: seq.updated_at,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ArchiveState> for StoresSnapshot {
+    fn from(archive: ArchiveState) -> Self {
+        Self {
+            meta: SnapshotMeta {
+                schema_version: archive.schema_version,
+                app_version: archive.app_version,
+                created_at: archive
+                    .created_at
+                    .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            },
+            state: StateSnapshot {
+                model_time: archive.model_time,
+                last_accounting_time: archive.last_accounting_time,
+                exes: archive
+                    .exes
+                    .into_iter()
+                    .map(|(path, total_running_time, last_seen_time)| ExeRecord {
+                        path: PathBuf::from(path),
+                        total_running_time,
+                        last_seen_time,
+                    })
+                    .collect(),
+                maps: archive
+                    .maps
+                    .into_iter()
+                    .map(|(path, offset, length, update_time)| MapRecord {
+                        path: PathBuf::from(path),
+                        offset,
+                        length,
+                        update_time,
+                    })
+                    .collect(),
+                exe_maps: archive
+                    .exe_maps
+                    .into_iter()
+                    .map(|(exe_path, path, offset, length, prob)| ExeMapRecord {
+                        exe_path: PathBuf::from(exe_path),
+                        map_key: MapKey {
+                            path: Arc::from(Path::new(&path)),
+                            offset,
+                            length,
+                        },
+                        prob,
+                    })
+                    .collect(),
+                markov_edges: archive
+                    .markov_edges
+                    .into_iter()
+                    .map(MarkovRecord::from)
+                    .collect(),
+                launch_histograms: archive
+                    .launch_histograms
+                    .into_iter()
+                    .map(|hist| LaunchHistogramRecord {
+                        exe_path: PathBuf::from(hist.exe_path),
+                        weights: hist.weights,
+                        updated_at: hist.updated_at,
+                    })
+                    .collect(),
+                launch_sequences: archive
+                    .launch_sequences
+                    .into_iter()
+                    .map(|seq| LaunchSequenceRecord {
+                        context: seq.context.into_iter().map(PathBuf::from).collect(),
+                        followers: seq
+                            .followers
+                            .into_iter()
+                            .map(|(path, weight)| (PathBuf::from(path), weight))
+                            .collect(),
+                        updated_at: seq.updated_at,
+                    })
+                    .collect(),
+                active_exes: Vec::new(),
+                cold_markov_edges: Vec::new(),
+            },
+        }
+    }
+}
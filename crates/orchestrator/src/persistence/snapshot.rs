@@ -1,25 +1,39 @@
 #![forbid(unsafe_code)]
 
 use crate::domain::MapKey;
+use crate::stores::HOURS_PER_WEEK;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::SystemTime;
 
-pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 5;
 
-#[derive(Debug, Clone)]
+/// The default is an empty model at the current schema version.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StoresSnapshot {
     pub meta: SnapshotMeta,
     pub state: StateSnapshot,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SnapshotMeta {
     pub schema_version: u32,
     pub app_version: Option<String>,
+    #[serde(with = "serde_impls::unix_seconds")]
     pub created_at: Option<SystemTime>,
 }
 
-#[derive(Debug, Clone)]
+impl Default for SnapshotMeta {
+    fn default() -> Self {
+        Self {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            app_version: None,
+            created_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StateSnapshot {
     pub model_time: u64,
     pub last_accounting_time: u64,
@@ -27,16 +41,23 @@ pub struct StateSnapshot {
     pub maps: Vec<MapRecord>,
     pub exe_maps: Vec<ExeMapRecord>,
     pub markov_edges: Vec<MarkovRecord>,
+    pub launch_histograms: Vec<LaunchHistogramRecord>,
+    pub launch_sequences: Vec<LaunchSequenceRecord>,
+    #[serde(default)]
+    pub active_exes: Vec<ActiveRecord>,
+    /// Statistics of dormant edges; their state fields are unused.
+    #[serde(default)]
+    pub cold_markov_edges: Vec<MarkovRecord>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExeRecord {
     pub path: PathBuf,
     pub total_running_time: u64,
     pub last_seen_time: Option<u64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MapRecord {
     pub path: PathBuf,
     pub offset: u64,
@@ -44,18 +65,154 @@ pub struct MapRecord {
     pub update_time: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExeMapRecord {
     pub exe_path: PathBuf,
+    #[serde(with = "serde_impls::map_key")]
     pub map_key: MapKey,
     pub prob: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarkovRecord {
     pub exe_a: PathBuf,
     pub exe_b: PathBuf,
     pub time_to_leave: [f32; 4],
     pub transition_prob: [[f32; 4]; 4],
     pub both_running_time: u64,
+    /// `MarkovState` index of the pair when the snapshot was taken.
+    #[serde(default)]
+    pub state: u8,
+    #[serde(default)]
+    pub last_change_time: u64,
+    #[serde(default)]
+    pub state_last_left: [u64; 4],
+}
+
+impl MarkovRecord {
+    /// The same edge with `exe_a` and `exe_b` exchanged: the "only A" and
+    /// "only B" states swap places in every per-state field.
+    pub fn reversed(mut self) -> Self {
+        const SWAP: [usize; 4] = [0, 2, 1, 3];
+        std::mem::swap(&mut self.exe_a, &mut self.exe_b);
+        let (time_to_leave, transition_prob) = (self.time_to_leave, self.transition_prob);
+        let state_last_left = self.state_last_left;
+        for from in 0..4 {
+            self.time_to_leave[from] = time_to_leave[SWAP[from]];
+            self.state_last_left[from] = state_last_left[SWAP[from]];
+            for to in 0..4 {
+                self.transition_prob[from][to] = transition_prob[SWAP[from]][SWAP[to]];
+            }
+        }
+        if let Some(&state) = SWAP.get(self.state as usize) {
+            self.state = state as u8;
+        }
+        self
+    }
+}
+
+/// An exe in the active set, which decides the lazily created Markov edges.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveRecord {
+    pub exe_path: PathBuf,
+    /// Model time the exe was last seen running.
+    pub last_active: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchHistogramRecord {
+    pub exe_path: PathBuf,
+    #[serde(with = "serde_impls::array")]
+    pub weights: [f32; HOURS_PER_WEEK],
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchSequenceRecord {
+    /// Launch context, oldest exe first.
+    pub context: Vec<PathBuf>,
+    pub followers: Vec<(PathBuf, f32)>,
+    pub updated_at: u64,
+}
+
+/// Serde adapters for fields whose types have no suitable representation
+/// of their own.
+mod serde_impls {
+    pub(super) mod unix_seconds {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::time::{Duration, SystemTime};
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<SystemTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            time.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<SystemTime>, D::Error> {
+            let secs = Option::<u64>::deserialize(deserializer)?;
+            Ok(secs.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)))
+        }
+    }
+
+    pub(super) mod map_key {
+        use crate::domain::MapKey;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::path::{Path, PathBuf};
+
+        #[derive(Serialize)]
+        struct Ref<'a> {
+            path: &'a Path,
+            offset: u64,
+            length: u64,
+        }
+
+        #[derive(Deserialize)]
+        struct Owned {
+            path: PathBuf,
+            offset: u64,
+            length: u64,
+        }
+
+        pub fn serialize<S: Serializer>(key: &MapKey, serializer: S) -> Result<S::Ok, S::Error> {
+            Ref {
+                path: &key.path,
+                offset: key.offset,
+                length: key.length,
+            }
+            .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MapKey, D::Error> {
+            let key = Owned::deserialize(deserializer)?;
+            Ok(MapKey::new(key.path, key.offset, key.length))
+        }
+    }
+
+    /// Fixed-size arrays longer than serde supports natively.
+    pub(super) mod array {
+        use serde::de::Error;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer, const N: usize>(
+            values: &[f32; N],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(values)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+            deserializer: D,
+        ) -> Result<[f32; N], D::Error> {
+            let values = Vec::<f32>::deserialize(deserializer)?;
+            let len = values.len();
+            values
+                .try_into()
+                .map_err(|_| D::Error::invalid_length(len, &"a fixed-size array"))
+        }
+    }
 }
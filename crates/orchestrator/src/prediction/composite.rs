@@ -0,0 +1,133 @@
+#![forbid(unsafe_code)]
+
+use crate::prediction::{
+    ComponentScores, FrequencyPredictor, MarkovPredictor, Prediction, Predictor, SequencePredictor,
+    TimeOfDayPredictor,
+};
+use crate::stores::Stores;
+use config::{CombineMode, Config};
+use half::f16;
+
+struct Component {
+    name: &'static str,
+    weight: f32,
+    predictor: Box<dyn Predictor>,
+}
+
+/// Runs several predictors and combines their exe scores.
+///
+/// Components only score exes; map scores are derived once, from the
+/// combined exe scores. An exe's ETA is the soonest any component
+/// reports. Each component's exe scores are kept in [`Prediction::components`] so a prediction can
+/// be explained afterwards.
+pub struct CompositePredictor {
+    combine: CombineMode,
+    components: Vec<Component>,
+}
+
+impl CompositePredictor {
+    /// Build the ensemble from `model.composite`. Components with a
+    /// non-positive weight are left out.
+    pub fn new(config: &Config) -> Self {
+        let weights = &config.model.composite.weights;
+        let mut composite = Self::with_combine(config.model.composite.combine);
+        composite.push(
+            "markov",
+            weights.markov,
+            Box::new(MarkovPredictor::new(config)),
+        );
+        composite.push(
+            "frequency",
+            weights.frequency,
+            Box::new(FrequencyPredictor::new(config)),
+        );
+        composite.push(
+            "time_of_day",
+            weights.time_of_day,
+            Box::new(TimeOfDayPredictor::new(config)),
+        );
+        composite.push(
+            "sequence",
+            weights.sequence,
+            Box::new(SequencePredictor::new(config)),
+        );
+        composite
+    }
+
+    /// An empty ensemble; add components with [`Self::push`].
+    pub fn with_combine(combine: CombineMode) -> Self {
+        Self {
+            combine,
+            components: Vec::new(),
+        }
+    }
+
+    /// Add a component. Non-positive weights are ignored.
+    pub fn push(&mut self, name: &'static str, weight: f32, predictor: Box<dyn Predictor>) {
+        if weight > 0.0 {
+            self.components.push(Component {
+                name,
+                weight,
+                predictor,
+            });
+        }
+    }
+
+    fn combine(&self, scores: impl Iterator<Item = (f32, f32)>) -> f32 {
+        match self.combine {
+            CombineMode::Weighted => {
+                let (sum, total_weight) = scores
+                    .fold((0.0, 0.0), |(sum, total), (weight, score)| {
+                        (sum + weight * score, total + weight)
+                    });
+                if total_weight > 0.0 {
+                    sum / total_weight
+                } else {
+                    0.0
+                }
+            }
+            CombineMode::NoisyOr => {
+                let not_needed = scores.fold(1.0, |acc, (weight, score)| {
+                    acc * (1.0 - (weight.min(1.0) * score).clamp(0.0, 1.0))
+                });
+                1.0 - not_needed
+            }
+        }
+    }
+}
+
+impl Predictor for CompositePredictor {
+    fn score_exes(&self, stores: &Stores) -> Prediction {
+        let mut prediction = Prediction::default();
+        let mut components = Vec::with_capacity(self.components.len());
+        for component in &self.components {
+            let scored = component.predictor.score_exes(stores);
+            for (exe_id, eta) in scored.exe_eta {
+                let entry = prediction.exe_eta.entry(exe_id).or_insert(eta);
+                *entry = entry.min(eta);
+            }
+            components.push(ComponentScores {
+                name: component.name,
+                weight: component.weight,
+                exe_scores: scored.exe_scores,
+            });
+        }
+
+        for (exe_id, _) in stores.exes.iter() {
+            let needed = self.combine(components.iter().map(|component| {
+                let score = component
+                    .exe_scores
+                    .get(&exe_id)
+                    .map(|s| s.to_f32())
+                    .unwrap_or(0.0);
+                (component.weight, score)
+            }));
+            prediction
+                .exe_scores
+                .insert(exe_id, f16::from_f32(needed.clamp(0.0, 1.0)));
+        }
+
+        prediction.components = components;
+        prediction
+    }
+}
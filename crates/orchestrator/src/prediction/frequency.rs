@@ -0,0 +1,48 @@
+#![forbid(unsafe_code)]
+
+use crate::math::fast_exp_neg;
+use crate::prediction::{Prediction, Predictor};
+use crate::stores::Stores;
+use config::Config;
+use half::f16;
+
+/// Scores exes by their share of model time spent running, damped by how
+/// long ago they were last seen running.
+#[derive(Debug, Clone)]
+pub struct FrequencyPredictor {
+    decay: f32,
+}
+
+impl FrequencyPredictor {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            decay: config.model.frequency.decay_factor(),
+        }
+    }
+}
+
+impl Predictor for FrequencyPredictor {
+    fn score_exes(&self, stores: &Stores) -> Prediction {
+        let now = stores.model_time;
+        let mut prediction = Prediction::default();
+
+        for (exe_id, exe) in stores.exes.iter() {
+            let needed = if exe.running || now == 0 {
+                0.0
+            } else {
+                let share = (exe.total_running_time as f32 / now as f32).clamp(0.0, 1.0);
+                let recency = match exe.last_seen_time {
+                    Some(seen) if self.decay > 0.0 => {
+                        fast_exp_neg(-self.decay * now.saturating_sub(seen) as f32)
+                    }
+                    Some(_) => 1.0,
+                    None => 0.0,
+                };
+                share * recency
+            };
+            prediction.exe_scores.insert(exe_id, f16::from_f32(needed));
+        }
+
+        prediction
+    }
+}
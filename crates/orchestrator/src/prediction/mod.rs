@@ -1,7 +1,15 @@
 #![forbid(unsafe_code)]
 
+mod composite;
+mod frequency;
 mod predictor;
+mod sequence;
+mod time_of_day;
 mod types;
 
+pub use composite::CompositePredictor;
+pub use frequency::FrequencyPredictor;
 pub use predictor::{MarkovPredictor, Predictor};
-pub use types::{Prediction, PredictionSummary};
+pub use sequence::SequencePredictor;
+pub use time_of_day::TimeOfDayPredictor;
+pub use types::{ComponentScores, Prediction, PredictionSummary};
@@ -9,21 +9,29 @@ use half::f16;
 use rustc_hash::FxHashMap;
 
 pub trait Predictor: Send + Sync {
-    /// Produce exe and map scores for the next cycle.
-    fn predict(&self, stores: &Stores) -> Prediction;
+    /// Produce exe scores (and ETAs, where known) for the prediction
+    /// horizon, leaving map scores empty.
+    fn score_exes(&self, stores: &Stores) -> Prediction;
+
+    /// Produce exe and map scores for the prediction horizon.
+    fn predict(&self, stores: &Stores) -> Prediction {
+        let mut prediction = self.score_exes(stores);
+        derive_map_scores(stores, &mut prediction);
+        prediction
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct MarkovPredictor {
     use_correlation: bool,
-    cycle_secs: f32,
+    horizon_secs: f32,
 }
 
 impl MarkovPredictor {
     pub fn new(config: &Config) -> Self {
         Self {
             use_correlation: config.model.use_correlation,
-            cycle_secs: config.model.cycle.as_secs_f32(),
+            horizon_secs: config.model.horizon_secs(),
         }
     }
 
@@ -49,31 +57,45 @@ impl MarkovPredictor {
         let numerator = (t as f32 * ab_time as f32) - (a_time as f32 * b_time as f32);
         let denom =
             (a_time as f32 * b_time as f32 * (t - a_time) as f32 * (t - b_time) as f32).sqrt();
-        if denom == 0.0 { None } else { Some(numerator / denom) }
+        if denom == 0.0 {
+            None
+        } else {
+            Some(numerator / denom)
+        }
     }
 
+    /// Probability that the edge leaves `state` for one where the target
+    /// exe runs within `horizon` seconds, and the expected time (seconds)
+    /// of that change given it happens within the horizon.
     fn p_needed(
         edge: &crate::stores::EdgeRef<'_>,
         state: MarkovState,
         target_state: MarkovState,
-        cycle: f32,
-    ) -> f32 {
+        horizon: f32,
+    ) -> (f32, f32) {
         let state_ix = state.index();
         let tt = edge.time_to_leave[state_ix].to_f32();
         if tt <= 0.0 {
-            return 0.0;
+            return (0.0, horizon);
         }
-        let p_state_change = 1.0 - fast_exp_neg(-cycle / tt);
+        let stay = fast_exp_neg(-horizon / tt);
+        let p_state_change = 1.0 - stay;
         let target_ix = target_state.index();
         let both_ix = MarkovState::Both.index();
         let p_runs_next = edge.transition_prob[state_ix][target_ix].to_f32()
             + edge.transition_prob[state_ix][both_ix].to_f32();
-        (p_state_change * p_runs_next).clamp(0.0, 1.0)
+        // Mean of an exponential leave time truncated to [0, horizon].
+        let eta = if p_state_change > 1e-6 {
+            (tt - horizon * stay / p_state_change).clamp(0.0, horizon)
+        } else {
+            horizon / 2.0
+        };
+        ((p_state_change * p_runs_next).clamp(0.0, 1.0), eta)
     }
 }
 
 impl Predictor for MarkovPredictor {
-    fn predict(&self, stores: &Stores) -> Prediction {
+    fn score_exes(&self, stores: &Stores) -> Prediction {
         // Pre-cache running state to avoid repeated store lookups per edge.
         let running: FxHashMap<ExeId, bool> = stores
             .exes
@@ -83,6 +105,9 @@ impl Predictor for MarkovPredictor {
 
         let mut not_needed: FxHashMap<ExeId, f32> =
             FxHashMap::with_capacity_and_hasher(running.len(), Default::default());
+        // Probability-weighted sum of edge ETAs and the weight total, per exe.
+        let mut eta_acc: FxHashMap<ExeId, (f32, f32)> =
+            FxHashMap::with_capacity_and_hasher(running.len(), Default::default());
 
         for (key, edge) in stores.markov.iter() {
             let a = key.a();
@@ -100,17 +125,22 @@ impl Predictor for MarkovPredictor {
                 1.0
             };
 
-            if !a_running {
-                let base = Self::p_needed(&edge, state, MarkovState::AOnly, self.cycle_secs);
-                let p = (base * corr).clamp(0.0, 1.0);
-                let entry = not_needed.entry(a).or_insert(1.0);
-                *entry *= 1.0 - p;
-            }
-            if !b_running {
-                let base = Self::p_needed(&edge, state, MarkovState::BOnly, self.cycle_secs);
+            for (exe, exe_running, target) in [
+                (a, a_running, MarkovState::AOnly),
+                (b, b_running, MarkovState::BOnly),
+            ] {
+                if exe_running {
+                    continue;
+                }
+                let (base, eta) = Self::p_needed(&edge, state, target, self.horizon_secs);
                 let p = (base * corr).clamp(0.0, 1.0);
-                let entry = not_needed.entry(b).or_insert(1.0);
+                let entry = not_needed.entry(exe).or_insert(1.0);
                 *entry *= 1.0 - p;
+                if p > 0.0 {
+                    let acc = eta_acc.entry(exe).or_insert((0.0, 0.0));
+                    acc.0 += p * eta;
+                    acc.1 += p;
+                }
             }
         }
 
@@ -134,26 +164,45 @@ impl Predictor for MarkovPredictor {
                 // Budget and sort order ensure high-confidence predictions come first.
                 let needed = markov_needed.max(base_prob).max(1e-6);
                 prediction.exe_scores.insert(exe_id, f16::from_f32(needed));
+                if let Some(&(weighted, total)) = eta_acc.get(&exe_id) {
+                    prediction.exe_eta.insert(exe_id, weighted / total);
+                }
             }
         }
 
-        // Map scores derived from exe scores (Pr map needed).
-        // Uses 4-lane parallel accumulators so the compiler can
-        // auto-vectorize the reduction for maps with many linked exes.
-        for (map_id, _map) in stores.maps.iter() {
-            let mut acc = [1.0f32; 4];
-            let mut lane = 0usize;
-            for exe_id in stores.exe_maps.exes_for_map(map_id) {
-                let exe_score = prediction.exe_scores.get(&exe_id).copied().unwrap_or(f16::ZERO).to_f32();
-                acc[lane] *= 1.0 - exe_score;
-                lane = (lane + 1) & 3;
+        prediction
+    }
+}
+
+/// Fill `prediction.map_scores` from its exe scores (Pr map needed), and
+/// `prediction.map_eta` with the soonest ETA of the exes using each map.
+/// Uses 4-lane parallel accumulators so the compiler can
+/// auto-vectorize the reduction for maps with many linked exes.
+fn derive_map_scores(stores: &Stores, prediction: &mut Prediction) {
+    let with_eta = !prediction.exe_eta.is_empty();
+    for (map_id, _map) in stores.maps.iter() {
+        let mut acc = [1.0f32; 4];
+        let mut lane = 0usize;
+        let mut eta = f32::INFINITY;
+        for exe_id in stores.exe_maps.exes_for_map(map_id) {
+            let exe_score = prediction
+                .exe_scores
+                .get(&exe_id)
+                .copied()
+                .unwrap_or(f16::ZERO)
+                .to_f32();
+            acc[lane] *= 1.0 - exe_score;
+            lane = (lane + 1) & 3;
+            if with_eta && let Some(&exe_eta) = prediction.exe_eta.get(&exe_id) {
+                eta = eta.min(exe_eta);
             }
-            let not_needed = acc[0] * acc[1] * acc[2] * acc[3];
-            let needed = (1.0 - not_needed).clamp(0.0, 1.0);
-            prediction.map_scores.insert(map_id, f16::from_f32(needed));
         }
-
-        prediction
+        let not_needed = acc[0] * acc[1] * acc[2] * acc[3];
+        let needed = (1.0 - not_needed).clamp(0.0, 1.0);
+        prediction.map_scores.insert(map_id, f16::from_f32(needed));
+        if eta.is_finite() {
+            prediction.map_eta.insert(map_id, eta);
+        }
     }
 }
 
@@ -236,13 +285,13 @@ mod tests {
             for score in prediction.exe_scores.values() {
                 let s = score.to_f32();
                 prop_assert!(!s.is_nan());
-                prop_assert!(s >= 0.0 && s <= 1.0);
+                prop_assert!((0.0..=1.0).contains(&s));
             }
 
             for score in prediction.map_scores.values() {
                 let s = score.to_f32();
                 prop_assert!(!s.is_nan());
-                prop_assert!(s >= 0.0 && s <= 1.0);
+                prop_assert!((0.0..=1.0).contains(&s));
             }
         }
     }
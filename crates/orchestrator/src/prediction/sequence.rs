@@ -0,0 +1,71 @@
+#![forbid(unsafe_code)]
+
+use crate::prediction::{Prediction, Predictor};
+use crate::stores::Stores;
+use config::Config;
+use half::f16;
+use rustc_hash::FxHashMap;
+
+/// Scores exes by how often they were launched right after the launches
+/// that just happened.
+///
+/// Every suffix of the current launch chain, up to `max_order` long, is
+/// looked up in the sequence model; each exe keeps its best damped
+/// follow-up share across those contexts, so long, well-supported
+/// contexts win over short ones. Once the last launch is more than `gap`
+/// in the past every exe scores zero.
+#[derive(Debug, Clone)]
+pub struct SequencePredictor {
+    max_order: usize,
+    gap: u64,
+    decay: f32,
+    prior: f32,
+}
+
+impl SequencePredictor {
+    pub fn new(config: &Config) -> Self {
+        let settings = &config.model.sequence;
+        Self {
+            max_order: settings.max_order,
+            gap: settings.gap.as_secs(),
+            decay: settings.decay_factor(),
+            prior: settings.prior.max(0.0),
+        }
+    }
+}
+
+impl Predictor for SequencePredictor {
+    fn score_exes(&self, stores: &Stores) -> Prediction {
+        let now = stores.model_time;
+        let chain = stores.sequences.chain(now, self.gap);
+
+        let mut follow: FxHashMap<_, f32> = FxHashMap::default();
+        for order in 1..=self.max_order.min(chain.len()) {
+            let Some(ctx) = stores.sequences.get(&chain[chain.len() - order..]) else {
+                continue;
+            };
+            let mix = ctx.decay_mix(now, self.decay);
+            let denom = ctx.total() * mix + self.prior;
+            if denom <= 0.0 {
+                continue;
+            }
+            for (&exe_id, &weight) in &ctx.next {
+                let share = (weight * mix / denom).clamp(0.0, 1.0);
+                let best = follow.entry(exe_id).or_insert(0.0);
+                *best = best.max(share);
+            }
+        }
+
+        let mut prediction = Prediction::default();
+        for (exe_id, exe) in stores.exes.iter() {
+            let needed = if exe.running {
+                0.0
+            } else {
+                follow.get(&exe_id).copied().unwrap_or(0.0)
+            };
+            prediction.exe_scores.insert(exe_id, f16::from_f32(needed));
+        }
+
+        prediction
+    }
+}
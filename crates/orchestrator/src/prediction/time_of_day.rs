@@ -0,0 +1,57 @@
+#![forbid(unsafe_code)]
+
+use crate::prediction::{Prediction, Predictor};
+use crate::stores::Stores;
+use config::Config;
+use half::f16;
+
+/// Scores exes by how much of their launch history falls in the hours
+/// just ahead of the current local time.
+///
+/// Launch histograms are maintained by the model updater; this predictor
+/// only reads them. Without a wall-clock reading every exe scores zero.
+#[derive(Debug, Clone)]
+pub struct TimeOfDayPredictor {
+    lookahead_secs: u64,
+    decay: f32,
+    prior: f32,
+}
+
+impl TimeOfDayPredictor {
+    pub fn new(config: &Config) -> Self {
+        let settings = &config.model.time_of_day;
+        Self {
+            lookahead_secs: settings.lookahead.as_secs(),
+            decay: settings.decay_factor(),
+            prior: settings.prior,
+        }
+    }
+}
+
+impl Predictor for TimeOfDayPredictor {
+    fn score_exes(&self, stores: &Stores) -> Prediction {
+        let mut prediction = Prediction::default();
+
+        for (exe_id, exe) in stores.exes.iter() {
+            let needed = match (exe.running, stores.wall_time) {
+                (false, Some(now)) => stores
+                    .launches
+                    .get(exe_id)
+                    .map(|hist| {
+                        hist.window_share(
+                            now,
+                            stores.utc_offset,
+                            self.lookahead_secs,
+                            self.decay,
+                            self.prior,
+                        )
+                    })
+                    .unwrap_or(0.0),
+                _ => 0.0,
+            };
+            prediction.exe_scores.insert(exe_id, f16::from_f32(needed));
+        }
+
+        prediction
+    }
+}
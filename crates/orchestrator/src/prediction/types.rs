@@ -8,6 +8,21 @@ use rustc_hash::FxHashMap;
 pub struct Prediction {
     pub exe_scores: FxHashMap<ExeId, f16>,
     pub map_scores: FxHashMap<MapId, f16>,
+    /// Expected seconds until an exe is needed, for exes whose predictor
+    /// can tell. Absent when unknown.
+    pub exe_eta: FxHashMap<ExeId, f32>,
+    /// Soonest expected seconds until any exe using the map is needed.
+    pub map_eta: FxHashMap<MapId, f32>,
+    /// Per-component exe scores when produced by an ensemble; empty otherwise.
+    pub components: Vec<ComponentScores>,
+}
+
+/// Exe scores one component predictor contributed to an ensemble.
+#[derive(Debug, Clone)]
+pub struct ComponentScores {
+    pub name: &'static str,
+    pub weight: f32,
+    pub exe_scores: FxHashMap<ExeId, f16>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -23,4 +38,19 @@ impl Prediction {
             num_maps_scored: self.map_scores.len(),
         }
     }
+
+    /// Per-component `(name, weight, score)` breakdown for one exe.
+    pub fn explain(&self, exe_id: ExeId) -> Vec<(&'static str, f32, f32)> {
+        self.components
+            .iter()
+            .map(|component| {
+                let score = component
+                    .exe_scores
+                    .get(&exe_id)
+                    .map(|s| s.to_f32())
+                    .unwrap_or(0.0);
+                (component.name, component.weight, score)
+            })
+            .collect()
+    }
 }
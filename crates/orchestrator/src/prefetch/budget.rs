@@ -0,0 +1,208 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::{MemStat, Psi};
+use config::{Config, PressurePolicy};
+use tracing::debug;
+
+/// Prefetch memory budget from the `model.memory` policy percentages,
+/// bounded by the cgroup limit and scaled down under memory or I/O pressure.
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+    memtotal: i32,
+    memavailable: i32,
+    cgroup: bool,
+}
+
+/// Budget scale in `0.0..=1.0` driven by memory and I/O pressure.
+///
+/// Owned by the engine rather than the planner so it carries over when
+/// planners are rebuilt on reload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PressureScale(f32);
+
+impl Default for PressureScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl PressureScale {
+    /// Update the scale from one cycle's PSI readings: zero it above a
+    /// `full` threshold, shrink it above a `some` threshold, and ramp it
+    /// back up otherwise. Missing readings count as no pressure, and a
+    /// disabled policy resets the scale.
+    pub fn observe(&mut self, policy: &PressurePolicy, mem: &MemStat) {
+        if !policy.enabled {
+            *self = Self::default();
+            return;
+        }
+        let above = |psi: Option<Psi>, limit: fn(&Psi) -> f32, threshold: f32| {
+            psi.is_some_and(|psi| limit(&psi) > threshold)
+        };
+        let full = above(mem.memory_pressure, |p| p.full, policy.memory_full)
+            || above(mem.io_pressure, |p| p.full, policy.io_full);
+        let some = above(mem.memory_pressure, |p| p.some, policy.memory_some)
+            || above(mem.io_pressure, |p| p.some, policy.io_some);
+
+        let scale = self.0;
+        let next = if full {
+            0.0
+        } else if some {
+            scale * policy.shrink.clamp(0.0, 1.0)
+        } else {
+            (scale + policy.ramp_up.max(0.0)).min(1.0)
+        };
+        if next != scale {
+            debug!(scale = next, full, some, "pressure budget scale changed");
+            self.0 = next;
+        }
+    }
+
+    /// Current scale in `0.0..=1.0`.
+    pub fn get(&self) -> f32 {
+        self.0
+    }
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(config: &Config) -> Self {
+        let policy = config.model.memory.clamp();
+        Self {
+            memtotal: policy.memtotal,
+            memavailable: policy.memavailable,
+            cgroup: policy.cgroup,
+        }
+    }
+
+    /// Budget in KB for the given memory snapshot and pressure scale.
+    pub(crate) fn available_kb(&self, mem: &MemStat, pressure: PressureScale) -> u64 {
+        let (total, available) = self.effective(mem);
+        let mut budget = self.memtotal as i64 * total as i64 / 100;
+        budget += self.memavailable as i64 * available as i64 / 100;
+        (budget.max(0) as f64 * pressure.get() as f64) as u64
+    }
+
+    /// Total and available memory in KB. Inside a limited cgroup, prefetched
+    /// pages are charged to us, so the limit caps the total and the headroom
+    /// left under it caps what is available.
+    fn effective(&self, mem: &MemStat) -> (u64, u64) {
+        let limit = self
+            .cgroup
+            .then_some(mem.cgroup)
+            .flatten()
+            .and_then(|cgroup| Some((cgroup.limit()?, cgroup.headroom?)));
+        match limit {
+            Some((limit, headroom)) => (mem.total.min(limit), mem.available.min(headroom)),
+            None => (mem.total, mem.available),
+        }
+    }
+}
+
+/// Size in KB, rounded up.
+pub(crate) fn kb(bytes: u64) -> u64 {
+    bytes.div_ceil(1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::CgroupMemory;
+
+    fn mem(memory: Option<Psi>, io: Option<Psi>) -> MemStat {
+        MemStat {
+            total: 0,
+            available: 1000,
+            free: 0,
+            cached: 0,
+            pagein: 0,
+            pageout: 0,
+            memory_pressure: memory,
+            io_pressure: io,
+            cgroup: None,
+        }
+    }
+
+    #[test]
+    fn budget_shrinks_under_pressure_and_ramps_back() {
+        let mut config = Config::default();
+        config.model.memory.memtotal = 0;
+        config.model.memory.memavailable = 100;
+        let budget = MemoryBudget::new(&config);
+        let policy = config.model.pressure;
+        let mut pressure = PressureScale::default();
+        let calm = mem(Some(Psi::default()), Some(Psi::default()));
+
+        pressure.observe(&policy, &calm);
+        assert_eq!(budget.available_kb(&calm, pressure), 1000);
+
+        let some = mem(
+            Some(Psi {
+                some: 20.0,
+                full: 1.0,
+            }),
+            None,
+        );
+        pressure.observe(&policy, &some);
+        assert_eq!(budget.available_kb(&some, pressure), 500);
+
+        let full = mem(
+            None,
+            Some(Psi {
+                some: 60.0,
+                full: 30.0,
+            }),
+        );
+        pressure.observe(&policy, &full);
+        assert_eq!(budget.available_kb(&full, pressure), 0);
+
+        pressure.observe(&policy, &calm);
+        assert_eq!(budget.available_kb(&calm, pressure), 250);
+        for _ in 0..3 {
+            pressure.observe(&policy, &calm);
+        }
+        assert_eq!(budget.available_kb(&calm, pressure), 1000);
+    }
+
+    #[test]
+    fn budget_is_bounded_by_cgroup_headroom() {
+        let mut config = Config::default();
+        config.model.memory.memtotal = 10;
+        config.model.memory.memavailable = 100;
+        let mut snapshot = mem(None, None);
+        snapshot.total = 4000;
+        snapshot.cgroup = Some(CgroupMemory {
+            max: Some(800),
+            high: Some(600),
+            headroom: Some(400),
+        });
+
+        // 10% of min(4000, 600) + 100% of min(1000, 400).
+        let budget = MemoryBudget::new(&config);
+        assert_eq!(
+            budget.available_kb(&snapshot, PressureScale::default()),
+            60 + 400
+        );
+
+        snapshot.cgroup = Some(CgroupMemory {
+            max: None,
+            high: None,
+            headroom: None,
+        });
+        assert_eq!(
+            budget.available_kb(&snapshot, PressureScale::default()),
+            400 + 1000
+        );
+
+        config.model.memory.cgroup = false;
+        snapshot.cgroup = Some(CgroupMemory {
+            max: Some(500),
+            high: None,
+            headroom: Some(0),
+        });
+        let budget = MemoryBudget::new(&config);
+        assert_eq!(
+            budget.available_kb(&snapshot, PressureScale::default()),
+            400 + 1000
+        );
+    }
+}
@@ -0,0 +1,298 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::{MapKey, MapSegment};
+use rustc_hash::FxHashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Groups segments by the file behind them. Two segments are the same
+/// file when they share a `(device, inode)`, or a path: segments loaded
+/// from the state carry no inode until the scanner sees them again, while
+/// fresh segments of the same file do.
+#[derive(Debug, Default)]
+struct Files {
+    by_inode: FxHashMap<(u64, u64), usize>,
+    by_path: FxHashMap<Arc<Path>, usize>,
+    /// Union-find parent of each file id.
+    parent: Vec<usize>,
+}
+
+impl Files {
+    fn root(&self, mut id: usize) -> usize {
+        while self.parent[id] != id {
+            id = self.parent[id];
+        }
+        id
+    }
+
+    fn inode(map: &MapSegment) -> Option<(u64, u64)> {
+        (map.device != 0 || map.inode != 0).then_some((map.device, map.inode))
+    }
+
+    /// The file of `map`, if any segment of it was added.
+    fn get(&self, map: &MapSegment) -> Option<usize> {
+        Self::inode(map)
+            .and_then(|inode| self.by_inode.get(&inode))
+            .or_else(|| self.by_path.get(&map.path))
+            .map(|&id| self.root(id))
+    }
+
+    /// Add `map`, returning its file and, when it links two files seen
+    /// apart so far, the file merged into it.
+    fn insert(&mut self, map: &MapSegment) -> (usize, Option<usize>) {
+        let by_inode = Self::inode(map)
+            .and_then(|inode| self.by_inode.get(&inode))
+            .map(|&id| self.root(id));
+        let by_path = self.by_path.get(&map.path).map(|&id| self.root(id));
+        let (file, merged) = match (by_inode, by_path) {
+            (Some(file), Some(other)) if other != file => {
+                self.parent[other] = file;
+                (file, Some(other))
+            }
+            (Some(file), _) | (None, Some(file)) => (file, None),
+            (None, None) => {
+                self.parent.push(self.parent.len());
+                (self.parent.len() - 1, None)
+            }
+        };
+        if let Some(inode) = Self::inode(map) {
+            self.by_inode.entry(inode).or_insert(file);
+        }
+        self.by_path.entry(map.path.clone()).or_insert(file);
+        (file, merged)
+    }
+}
+
+/// Byte ranges a plan already covers, per file, so bytes shared by
+/// overlapping segments are charged only once.
+#[derive(Debug, Default)]
+pub(crate) struct Coverage {
+    files: Files,
+    /// Sorted, disjoint `[start, end)` ranges per file.
+    ranges: FxHashMap<usize, Vec<(u64, u64)>>,
+}
+
+impl Coverage {
+    /// Bytes of `map` not covered yet.
+    pub(crate) fn uncovered(&self, map: &MapSegment) -> u64 {
+        let (start, end) = span(map);
+        let covered: u64 = self
+            .files
+            .get(map)
+            .and_then(|file| self.ranges.get(&file))
+            .map(|ranges| {
+                ranges
+                    .iter()
+                    .map(|&(s, e)| e.min(end).saturating_sub(s.max(start)))
+                    .sum()
+            })
+            .unwrap_or(0);
+        (end - start).saturating_sub(covered)
+    }
+
+    /// Mark `map` as covered.
+    pub(crate) fn insert(&mut self, map: &MapSegment) {
+        let (file, merged) = self.files.insert(map);
+        if let Some(other) = merged.and_then(|other| self.ranges.remove(&other)) {
+            for range in other {
+                self.add(file, range);
+            }
+        }
+        self.add(file, span(map));
+    }
+
+    fn add(&mut self, file: usize, (start, end): (u64, u64)) {
+        if start == end {
+            return;
+        }
+        let ranges = self.ranges.entry(file).or_default();
+        let at = ranges.partition_point(|&(_, e)| e < start);
+        let mut merged = (start, end);
+        let mut last = at;
+        while last < ranges.len() && ranges[last].0 <= merged.1 {
+            merged.0 = merged.0.min(ranges[last].0);
+            merged.1 = merged.1.max(ranges[last].1);
+            last += 1;
+        }
+        ranges.splice(at..last, [merged]);
+    }
+}
+
+/// Bytes to charge for `map` when only `uncovered` of its bytes are new to
+/// the plan: its uncached bytes scaled by the uncovered share.
+pub(crate) fn charge(map: &MapSegment, uncached: u64, uncovered: u64) -> u64 {
+    if map.length == 0 || uncovered >= map.length {
+        return uncached;
+    }
+    (uncached as u128 * uncovered as u128 / map.length as u128) as u64
+}
+
+/// One prefetch call: a contiguous range of a file and the planned maps
+/// it covers.
+#[derive(Debug, Clone)]
+pub(crate) struct Extent {
+    pub(crate) path: Arc<Path>,
+    pub(crate) offset: u64,
+    pub(crate) length: u64,
+    pub(crate) maps: Vec<MapKey>,
+}
+
+/// Merge overlapping and adjacent segments of the same file into minimal
+/// ranges. Extents keep the plan order of their earliest member.
+pub(crate) fn coalesce<'a>(maps: impl IntoIterator<Item = &'a MapSegment>) -> Vec<Extent> {
+    let mut ids = Files::default();
+    let maps: Vec<(usize, &MapSegment)> = maps
+        .into_iter()
+        .map(|map| (ids.insert(map).0, map))
+        .collect();
+    let mut files: FxHashMap<usize, Vec<(usize, &MapSegment)>> = FxHashMap::default();
+    for (index, (file, map)) in maps.into_iter().enumerate() {
+        files.entry(ids.root(file)).or_default().push((index, map));
+    }
+
+    let mut extents: Vec<(usize, Extent)> = Vec::new();
+    for mut segments in files.into_values() {
+        segments.sort_unstable_by_key(|(index, map)| (map.offset, *index));
+        let mut current: Option<(usize, Extent)> = None;
+        for (index, map) in segments {
+            let (start, end) = span(map);
+            match &mut current {
+                Some((first, extent)) if start <= extent.offset + extent.length => {
+                    *first = (*first).min(index);
+                    extent.length = extent.length.max(end - extent.offset);
+                    extent.maps.push(map.key());
+                }
+                _ => {
+                    extents.extend(current.take());
+                    current = Some((
+                        index,
+                        Extent {
+                            path: map.path.clone(),
+                            offset: start,
+                            length: end - start,
+                            maps: vec![map.key()],
+                        },
+                    ));
+                }
+            }
+        }
+        extents.extend(current);
+    }
+
+    extents.sort_unstable_by_key(|(first, _)| *first);
+    extents.into_iter().map(|(_, extent)| extent).collect()
+}
+
+fn span(map: &MapSegment) -> (u64, u64) {
+    (map.offset, map.offset.saturating_add(map.length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn segment(path: &str, offset: u64, length: u64, inode: u64) -> MapSegment {
+        let mut map = MapSegment::new(path, offset, length, 0);
+        map.device = if inode == 0 { 0 } else { 1 };
+        map.inode = inode;
+        map
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_segments_per_inode() {
+        let text = segment("/lib/libfoo.so", 0, 8192, 7);
+        let data = segment("/lib/libfoo.so", 8192, 4096, 7);
+        let whole = segment("/lib/libfoo.so", 0, 10_000, 7);
+        let other = segment("/lib/libbar.so", 4096, 4096, 9);
+        let far = segment("/lib/libfoo.so", 65536, 4096, 7);
+
+        let extents = coalesce([&other, &text, &far, &whole, &data]);
+        let spans: Vec<_> = extents
+            .iter()
+            .map(|e| (e.path.to_str().unwrap(), e.offset, e.length, e.maps.len()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("/lib/libbar.so", 4096, 4096, 1),
+                ("/lib/libfoo.so", 0, 12288, 3),
+                ("/lib/libfoo.so", 65536, 4096, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_of_one_file_from_every_source_are_merged() {
+        // procfs mapping, fanotify's whole file, and a segment loaded from
+        // the state without an inode, all of one library.
+        let text = segment("/lib/libfoo.so", 0, 8192, 7);
+        let whole = segment("/lib/libfoo.so", 0, 12288, 7);
+        let loaded = segment("/lib/libfoo.so", 8192, 8192, 0);
+        let hardlink = segment("/lib/libfoo.so.1", 16384, 4096, 7);
+
+        let extents = coalesce([&loaded, &hardlink, &text, &whole]);
+        let spans: Vec<_> = extents
+            .iter()
+            .map(|e| (e.offset, e.length, e.maps.len()))
+            .collect();
+        assert_eq!(spans, vec![(0, 20480, 4)]);
+
+        // The loaded segment is covered before the link to the inode is
+        // known; its bytes still count once.
+        let mut coverage = Coverage::default();
+        coverage.insert(&loaded);
+        coverage.insert(&hardlink);
+        assert_eq!(coverage.uncovered(&text), 8192);
+        coverage.insert(&text);
+        assert_eq!(coverage.uncovered(&whole), 0);
+        assert_eq!(coverage.uncovered(&hardlink), 0);
+    }
+
+    #[test]
+    fn coverage_counts_shared_bytes_once() {
+        let mut coverage = Coverage::default();
+        let text = segment("/lib/libfoo.so", 0, 8192, 7);
+        let whole = segment("/lib/libfoo.so", 0, 12288, 7);
+        let renamed = segment("/lib/libfoo.so.1", 4096, 4096, 7);
+
+        assert_eq!(coverage.uncovered(&text), 8192);
+        coverage.insert(&text);
+        assert_eq!(coverage.uncovered(&whole), 4096);
+        assert_eq!(coverage.uncovered(&renamed), 0);
+        assert_eq!(charge(&whole, 12288, 4096), 4096);
+        assert_eq!(charge(&whole, 6144, 4096), 2048);
+    }
+
+    proptest! {
+        #[test]
+        fn coverage_matches_union_of_segments(
+            segments in prop::collection::vec((0u64..64, 1u64..16), 1..20),
+        ) {
+            let maps: Vec<_> = segments
+                .iter()
+                .map(|(offset, length)| segment("/f", offset * 512, length * 512, 3))
+                .collect();
+
+            let mut coverage = Coverage::default();
+            let mut charged = 0;
+            for map in &maps {
+                charged += coverage.uncovered(map);
+                coverage.insert(map);
+            }
+
+            let mut bytes = vec![false; 80 * 512];
+            for map in &maps {
+                for byte in &mut bytes[map.offset as usize..(map.offset + map.length) as usize] {
+                    *byte = true;
+                }
+            }
+            let union = bytes.iter().filter(|b| **b).count() as u64;
+            prop_assert_eq!(charged, union);
+
+            let extents = coalesce(&maps);
+            prop_assert_eq!(extents.iter().map(|e| e.length).sum::<u64>(), union);
+            prop_assert_eq!(extents.iter().map(|e| e.maps.len()).sum::<usize>(), maps.len());
+        }
+    }
+}
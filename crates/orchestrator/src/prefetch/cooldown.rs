@@ -0,0 +1,171 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::{MapKey, MapSegment};
+use crate::prefetch::residency::{ResidencyProbe, ResidencyProbes};
+use rustc_hash::FxHashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Per-map hysteresis so maps warmed recently are not replanned (and
+/// re-probed by the prefetcher) every cycle.
+///
+/// The engine keeps it and lends it to every plan, so rebuilding the
+/// planner on reload or a power profile switch does not forget which maps
+/// were just prefetched. The period comes from the planner's config.
+#[derive(Debug, Default)]
+pub struct Cooldown {
+    maps: Arc<Mutex<FxHashMap<MapKey, MapState>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MapState {
+    /// When the map was last prefetched.
+    prefetched_at: Instant,
+    /// Uncached bytes last observed for the map, first right after it was
+    /// prefetched.
+    uncached: u64,
+}
+
+impl Cooldown {
+    /// Blocking task that starts a cooldown of `period` for `prefetched`,
+    /// keyed to the uncached bytes `residency` observes for each map
+    /// afterwards. `None` when the period is zero or nothing was prefetched.
+    pub(crate) fn record(
+        &self,
+        period: Duration,
+        prefetched: &[MapKey],
+        residency: &ResidencyProbe,
+    ) -> Option<ResidencyProbes> {
+        if period.is_zero() || prefetched.is_empty() {
+            return None;
+        }
+        let maps = self.maps.clone();
+        let prefetched = prefetched.to_vec();
+        let residency = residency.clone();
+        Some(Box::new(move || {
+            let observed: Vec<_> = prefetched
+                .into_iter()
+                .map(|key| {
+                    let uncached = residency.observe(&key);
+                    (key, uncached)
+                })
+                .collect();
+            let now = Instant::now();
+            let mut maps = lock(&maps);
+            for (key, uncached) in observed {
+                maps.insert(
+                    key,
+                    MapState {
+                        prefetched_at: now,
+                        uncached,
+                    },
+                );
+            }
+        }))
+    }
+
+    /// Start a planning pass, forgetting maps prefetched longer than
+    /// `period` ago. A zero period admits every map.
+    pub(crate) fn begin(&self, period: Duration) -> CooldownPass<'_> {
+        let now = Instant::now();
+        let mut maps = lock(&self.maps);
+        maps.retain(|_, state| now.duration_since(state.prefetched_at) < period);
+        CooldownPass {
+            maps,
+            enabled: !period.is_zero(),
+            skipped: 0,
+            evicted: 0,
+        }
+    }
+}
+
+/// Cooldown state held for the duration of one plan.
+pub(crate) struct CooldownPass<'a> {
+    maps: MutexGuard<'a, FxHashMap<MapKey, MapState>>,
+    enabled: bool,
+    /// Maps skipped because they are still cooling down.
+    pub(crate) skipped: usize,
+    /// Maps in cooldown admitted again because pages were evicted.
+    pub(crate) evicted: usize,
+}
+
+impl CooldownPass<'_> {
+    /// Whether `map`, last probed as missing `uncached` bytes from the page
+    /// cache, may be planned. Maps in cooldown whose residency is unknown
+    /// are skipped.
+    pub(crate) fn admit(&mut self, map: &MapSegment, uncached: Option<u64>) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        let key = map.key();
+        let Some(state) = self.maps.get_mut(&key) else {
+            return true;
+        };
+        if let Some(uncached) = uncached {
+            if uncached > state.uncached {
+                self.maps.remove(&key);
+                self.evicted += 1;
+                return true;
+            }
+            state.uncached = uncached;
+        }
+        self.skipped += 1;
+        false
+    }
+}
+
+fn lock(maps: &Mutex<FxHashMap<MapKey, MapState>>) -> MutexGuard<'_, FxHashMap<MapKey, MapState>> {
+    match maps.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+
+    const PERIOD: Duration = Duration::from_secs(300);
+
+    #[test]
+    fn skips_recent_maps_until_evicted() {
+        let cooldown = Cooldown::default();
+        let residency = ResidencyProbe::new(&Config::default());
+        let map = MapSegment::new("/map/a", 0, 8192, 0);
+
+        let mut pass = cooldown.begin(PERIOD);
+        assert!(pass.admit(&map, Some(8192)));
+        drop(pass);
+
+        // Unreadable, so it is still fully uncached after the prefetch.
+        let record = cooldown.record(PERIOD, &[map.key()], &residency).unwrap();
+        record();
+
+        let mut pass = cooldown.begin(PERIOD);
+        assert!(!pass.admit(&map, Some(4096)));
+        assert!(!pass.admit(&map, None));
+        assert_eq!((pass.skipped, pass.evicted), (2, 0));
+        drop(pass);
+
+        let mut pass = cooldown.begin(PERIOD);
+        assert!(pass.admit(&map, Some(8192)));
+        assert_eq!((pass.skipped, pass.evicted), (0, 1));
+    }
+
+    #[test]
+    fn zero_period_disables_cooldown() {
+        let cooldown = Cooldown::default();
+        let residency = ResidencyProbe::new(&Config::default());
+        let map = MapSegment::new("/map/a", 0, 8192, 0);
+
+        assert!(
+            cooldown
+                .record(Duration::ZERO, &[map.key()], &residency)
+                .is_none()
+        );
+        let mut pass = cooldown.begin(Duration::ZERO);
+        assert!(pass.admit(&map, None));
+        assert_eq!(pass.skipped, 0);
+    }
+}
@@ -0,0 +1,338 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::{MapId, MemStat};
+use crate::prediction::Prediction;
+use crate::prefetch::budget::{MemoryBudget, PressureScale, kb};
+use crate::prefetch::coalesce::{Coverage, charge};
+use crate::prefetch::cooldown::Cooldown;
+use crate::prefetch::order::{PlanOrder, SelectedMap};
+use crate::prefetch::residency::{ResidencyProbe, ResidencyProbes};
+use crate::prefetch::{PrefetchPlan, PrefetchPlanner, PrefetchReport};
+use crate::stores::Stores;
+use config::Config;
+use half::f16;
+use std::cmp::Ordering;
+use std::time::Duration;
+use tracing::trace;
+
+/// Capacity resolution of the knapsack table. Item sizes are rounded up to
+/// `budget / KNAPSACK_BUCKETS`, so selections never exceed the budget.
+const KNAPSACK_BUCKETS: u64 = 4096;
+
+/// Upper bound on `knapsack_candidates`. The table holds one flag per
+/// candidate and capacity bucket, so this caps it at about 1 MiB.
+const MAX_KNAPSACK_CANDIDATES: usize = 256;
+
+/// Ranks maps by expected benefit per KB (score / uncached size) instead of
+/// raw score, so many small likely maps are not crowded out by one large one.
+///
+/// With `knapsack_candidates > 0` the densest candidates are additionally
+/// solved as a bounded 0/1 knapsack (maximising total score within the
+/// budget); the result replaces the greedy pick when it scores higher.
+/// Larger values are clamped to `MAX_KNAPSACK_CANDIDATES`.
+#[derive(Debug)]
+pub struct DensityPrefetchPlanner {
+    order: PlanOrder,
+    budget: MemoryBudget,
+    residency: ResidencyProbe,
+    cooldown_period: Duration,
+    knapsack_candidates: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    id: MapId,
+    score: f16,
+    eta: f32,
+    kb: u64,
+    bytes: u64,
+}
+
+impl Candidate {
+    fn density(&self) -> f32 {
+        self.score.to_f32() / self.kb.max(1) as f32
+    }
+}
+
+impl DensityPrefetchPlanner {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            order: PlanOrder::new(config),
+            budget: MemoryBudget::new(config),
+            residency: ResidencyProbe::new(config),
+            cooldown_period: config.system.prefetch_cooldown,
+            knapsack_candidates: config
+                .system
+                .knapsack_candidates
+                .min(MAX_KNAPSACK_CANDIDATES),
+        }
+    }
+
+    /// Greedy fill in the given order; returns chosen indices and KB used.
+    fn greedy(candidates: &[Candidate], budget_kb: u64) -> (Vec<usize>, u64) {
+        let mut used = 0u64;
+        let mut chosen = Vec::new();
+        for (idx, candidate) in candidates.iter().enumerate() {
+            if used + candidate.kb <= budget_kb {
+                used += candidate.kb;
+                chosen.push(idx);
+            }
+        }
+        (chosen, used)
+    }
+
+    /// Exact 0/1 knapsack over bucketed sizes; returns chosen indices.
+    fn knapsack(candidates: &[Candidate], budget_kb: u64) -> Vec<usize> {
+        let unit = budget_kb.div_ceil(KNAPSACK_BUCKETS).max(1);
+        let capacity = (budget_kb / unit) as usize;
+        let weights: Vec<usize> = candidates
+            .iter()
+            .map(|c| c.kb.div_ceil(unit) as usize)
+            .collect();
+
+        let mut best = vec![0.0f32; capacity + 1];
+        let mut take = vec![false; candidates.len() * (capacity + 1)];
+        for (idx, candidate) in candidates.iter().enumerate() {
+            let weight = weights[idx];
+            if weight > capacity {
+                continue;
+            }
+            let value = candidate.score.to_f32();
+            for cap in (weight..=capacity).rev() {
+                let with = best[cap - weight] + value;
+                if with > best[cap] {
+                    best[cap] = with;
+                    take[idx * (capacity + 1) + cap] = true;
+                }
+            }
+        }
+
+        let mut chosen = Vec::new();
+        let mut cap = capacity;
+        for idx in (0..candidates.len()).rev() {
+            if take[idx * (capacity + 1) + cap] {
+                chosen.push(idx);
+                cap -= weights[idx];
+            }
+        }
+        chosen.reverse();
+        chosen
+    }
+
+    fn total_score(candidates: &[Candidate], chosen: &[usize]) -> f32 {
+        chosen
+            .iter()
+            .map(|&idx| candidates[idx].score.to_f32())
+            .sum()
+    }
+}
+
+impl PrefetchPlanner for DensityPrefetchPlanner {
+    fn plan(
+        &self,
+        prediction: &Prediction,
+        stores: &Stores,
+        memstat: &MemStat,
+        pressure: PressureScale,
+        cooldown: &Cooldown,
+    ) -> PrefetchPlan {
+        let mut cooldown = cooldown.begin(self.cooldown_period);
+        let mut candidates: Vec<Candidate> = prediction
+            .map_scores
+            .iter()
+            .filter(|(_, score)| **score > f16::ZERO)
+            .filter_map(|(id, score)| {
+                let map = stores.maps.get(*id)?;
+                let probed = self.residency.probed(map);
+                if !cooldown.admit(map, probed) {
+                    return None;
+                }
+                let uncached = probed.unwrap_or(map.length);
+                Some(Candidate {
+                    id: *id,
+                    score: *score,
+                    eta: prediction.map_eta.get(id).copied().unwrap_or(f32::INFINITY),
+                    kb: kb(uncached),
+                    bytes: uncached,
+                })
+            })
+            .collect();
+        candidates.sort_unstable_by(|a, b| {
+            b.density()
+                .total_cmp(&a.density())
+                .then_with(|| self.order.rank(a.score, a.eta, b.score, b.eta))
+                .then_with(|| a.kb.cmp(&b.kb))
+        });
+
+        let budget_kb = self.budget.available_kb(memstat, pressure);
+        let head_len = self.knapsack_candidates.min(candidates.len());
+        let head = &candidates[..head_len];
+
+        let chosen = if head.is_empty() || budget_kb == 0 {
+            Vec::new()
+        } else {
+            let (greedy, _) = Self::greedy(head, budget_kb);
+            let exact = Self::knapsack(head, budget_kb);
+            if Self::total_score(head, &exact) > Self::total_score(head, &greedy) {
+                exact
+            } else {
+                greedy
+            }
+        };
+
+        // Charge the chosen head once per byte, then fill the rest greedily
+        // with whatever segments still fit once shared bytes are discounted.
+        let mut coverage = Coverage::default();
+        let mut used_kb = 0u64;
+        let mut total_bytes = 0u64;
+        let mut picked = Vec::with_capacity(chosen.len());
+        let rest = (head_len..candidates.len()).map(|idx| (idx, false));
+        for (idx, required) in chosen.into_iter().map(|idx| (idx, true)).chain(rest) {
+            let candidate = &candidates[idx];
+            let Some(map) = stores.maps.get(candidate.id) else {
+                continue;
+            };
+            let bytes = charge(map, candidate.bytes, coverage.uncovered(map));
+            if !required && used_kb + kb(bytes) > budget_kb {
+                continue;
+            }
+            used_kb += kb(bytes);
+            total_bytes = total_bytes.saturating_add(bytes);
+            coverage.insert(map);
+            picked.push((candidate, map, bytes));
+        }
+
+        let mut selected: Vec<SelectedMap> = Vec::with_capacity(picked.len());
+        for (candidate, map, bytes) in picked {
+            self.residency.invalidate(map);
+            selected.push(SelectedMap {
+                id: candidate.id,
+                score: candidate.score,
+                eta: candidate.eta,
+                bytes,
+                index: selected.len(),
+            });
+        }
+        // Fetch the most likely maps first within the chosen set.
+        selected.sort_by(
+            |a, b| match self.order.rank(a.score, a.eta, b.score, b.eta) {
+                Ordering::Equal => a.index.cmp(&b.index),
+                other => other,
+            },
+        );
+        for (index, item) in selected.iter_mut().enumerate() {
+            item.index = index;
+        }
+        let selected = self.order.order_for_io(selected, stores);
+
+        trace!(
+            selected = selected.len(),
+            total_bytes,
+            cooldown_skipped = cooldown.skipped,
+            evicted = cooldown.evicted,
+            "density prefetch plan created"
+        );
+
+        PrefetchPlan {
+            maps: selected.iter().map(|item| item.id).collect(),
+            charged: selected.iter().map(|item| item.bytes).collect(),
+            total_bytes,
+            budget_bytes: budget_kb * 1024,
+            cooldown_skipped: cooldown.skipped,
+            evicted: cooldown.evicted,
+        }
+    }
+
+    fn residency_probes(
+        &self,
+        prediction: &Prediction,
+        stores: &Stores,
+        memstat: &MemStat,
+        pressure: PressureScale,
+    ) -> Option<ResidencyProbes> {
+        // Probe the densest maps by full size first, until what they are
+        // missing from the page cache fills the budget. Unprobed maps rank
+        // by their full size, which only lowers their density.
+        let mut maps: Vec<_> = prediction
+            .map_scores
+            .iter()
+            .filter(|(_, score)| **score > f16::ZERO)
+            .filter_map(|(id, score)| {
+                let map = stores.maps.get(*id)?;
+                let density = score.to_f32() / kb(map.length).max(1) as f32;
+                Some((density, map.clone()))
+            })
+            .collect();
+        maps.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+        let maps = maps.into_iter().map(|(_, map)| map).collect();
+        self.residency
+            .probes(maps, self.budget.available_kb(memstat, pressure))
+    }
+
+    fn record_prefetch(
+        &self,
+        report: &PrefetchReport,
+        cooldown: &Cooldown,
+    ) -> Option<ResidencyProbes> {
+        cooldown.record(self.cooldown_period, &report.prefetched, &self.residency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::MapSegment;
+    use config::{MemoryPolicy, SortStrategy};
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    proptest! {
+        #[test]
+        fn density_planner_respects_budget_and_uniqueness(
+            maps in prop::collection::vec((1u64..65536, 0f32..1f32), 0..40),
+            available in 0u64..256,
+            knapsack_candidates in 0usize..48,
+        ) {
+            let mut config = Config::default();
+            config.model.memory = MemoryPolicy { memtotal: 0, memavailable: 100, ..Default::default() };
+            config.system.sortstrategy = SortStrategy::None;
+            config.system.knapsack_candidates = knapsack_candidates;
+
+            let planner = DensityPrefetchPlanner::new(&config);
+            let mut stores = Stores::default();
+            let mut prediction = Prediction::default();
+            for (idx, (size, score)) in maps.iter().enumerate() {
+                let map_id = stores.ensure_map(MapSegment::new(format!("/map/{idx}"), 0, *size, 0));
+                prediction.map_scores.insert(map_id, f16::from_f32(*score));
+            }
+
+            let mem = MemStat {
+                total: 0,
+                available,
+                free: 0,
+                cached: 0,
+                pagein: 0,
+                pageout: 0,
+                memory_pressure: None,
+                io_pressure: None,
+                cgroup: None,
+            };
+            let plan = planner.plan(
+                &prediction,
+                &stores,
+                &mem,
+                PressureScale::default(),
+                &Cooldown::default(),
+            );
+
+            let used_kb: u64 = plan
+                .maps
+                .iter()
+                .map(|id| kb(stores.maps.get(*id).unwrap().length))
+                .sum();
+            prop_assert!(used_kb <= available);
+            let unique: HashSet<_> = plan.maps.iter().copied().collect();
+            prop_assert_eq!(unique.len(), plan.maps.len());
+        }
+    }
+}
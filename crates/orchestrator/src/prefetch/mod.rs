@@ -1,12 +1,26 @@
 #![deny(unsafe_code)]
 
+mod budget;
+mod coalesce;
+mod cooldown;
+mod density_planner;
+mod order;
 mod plan;
 mod planner;
 mod prefetcher;
+mod preview;
+mod residency;
+mod throttle;
 
+pub use budget::PressureScale;
+pub use cooldown::Cooldown;
+pub use density_planner::DensityPrefetchPlanner;
 pub use plan::{PrefetchPlan, PrefetchReport};
 pub use planner::{GreedyPrefetchPlanner, PrefetchPlanner};
 pub use prefetcher::{
     MadvisePrefetcher, NoopPrefetcher, PosixFadvisePrefetcher, Prefetcher, ReadPrefetcher,
     ReadaheadPrefetcher,
 };
+pub use preview::{PlanEntry, PlanPreview};
+pub use residency::ResidencyProbes;
+pub use throttle::ThrottleSettings;
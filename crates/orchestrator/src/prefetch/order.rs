@@ -0,0 +1,178 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::MapId;
+use crate::stores::Stores;
+use config::{Config, SortStrategy};
+use half::f16;
+use std::cmp::Ordering;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Default block size for computing block index when metadata is unavailable.
+const DEFAULT_BLOCK_SIZE: u64 = 4096;
+
+#[derive(Debug, Clone)]
+pub(crate) struct SelectedMap {
+    pub(crate) id: MapId,
+    pub(crate) score: f16,
+    pub(crate) eta: f32,
+    /// Bytes charged against the budget.
+    pub(crate) bytes: u64,
+    pub(crate) index: usize,
+}
+
+/// Priority and I/O ordering shared by the planners.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PlanOrder {
+    sort: SortStrategy,
+    eta: Option<EtaBuckets>,
+}
+
+/// ETAs grouped into buckets one model cycle wide, so maps expected in the
+/// same cycle are still ranked by score.
+#[derive(Debug, Clone, Copy)]
+struct EtaBuckets {
+    width: f32,
+    /// Bucket for maps without an ETA: the end of the prediction horizon.
+    unknown: u32,
+}
+
+impl EtaBuckets {
+    fn bucket(&self, eta: f32) -> u32 {
+        if eta.is_finite() {
+            (eta.max(0.0) / self.width) as u32
+        } else {
+            self.unknown
+        }
+    }
+}
+
+impl PlanOrder {
+    pub(crate) fn new(config: &Config) -> Self {
+        let eta = config.system.prioritize_by_eta.then(|| {
+            let width = config.model.cycle.as_secs_f32().max(1.0);
+            EtaBuckets {
+                width,
+                unknown: (config.model.horizon_secs() / width) as u32,
+            }
+        });
+        Self {
+            sort: config.system.sortstrategy,
+            eta,
+        }
+    }
+
+    /// Priority order: highest score first. When ranking by ETA, maps due in
+    /// an earlier cycle come first and score orders maps within a cycle;
+    /// maps without an ETA rank as if due at the end of the horizon.
+    pub(crate) fn rank(&self, a_score: f16, a_eta: f32, b_score: f16, b_eta: f32) -> Ordering {
+        let by_score = b_score.total_cmp(&a_score);
+        match self.eta {
+            Some(buckets) => buckets
+                .bucket(a_eta)
+                .cmp(&buckets.bucket(b_eta))
+                .then(by_score),
+            None => by_score,
+        }
+    }
+
+    /// Sort selected maps based on strategy for I/O efficiency.
+    pub(crate) fn order_for_io(
+        &self,
+        selected: Vec<SelectedMap>,
+        stores: &Stores,
+    ) -> Vec<SelectedMap> {
+        match self.sort {
+            SortStrategy::None => selected,
+            SortStrategy::Path => {
+                let mut keyed: Vec<SelectedWithKey<Arc<Path>>> = selected
+                    .into_iter()
+                    .map(|item| {
+                        let key = stores.maps.get(item.id).map(|m| m.path.clone());
+                        SelectedWithKey { item, key }
+                    })
+                    .collect();
+                self.sort_by_score_and_key(&mut keyed);
+                keyed.into_iter().map(|entry| entry.item).collect()
+            }
+            SortStrategy::Block => {
+                let mut keyed: Vec<SelectedWithKey<BlockKey>> = selected
+                    .into_iter()
+                    .map(|item| {
+                        let key = stores.maps.get(item.id).and_then(|map| {
+                            if map.device == 0 && map.inode == 0 {
+                                return None;
+                            }
+                            let block = map.offset / DEFAULT_BLOCK_SIZE;
+                            Some(BlockKey {
+                                device: map.device,
+                                block,
+                                offset: map.offset,
+                            })
+                        });
+                        SelectedWithKey { item, key }
+                    })
+                    .collect();
+                self.sort_by_score_and_key(&mut keyed);
+                keyed.into_iter().map(|entry| entry.item).collect()
+            }
+            SortStrategy::Inode => {
+                let mut keyed: Vec<SelectedWithKey<InodeKey>> = selected
+                    .into_iter()
+                    .map(|item| {
+                        let key = stores.maps.get(item.id).and_then(|map| {
+                            if map.device == 0 && map.inode == 0 {
+                                return None;
+                            }
+                            Some(InodeKey {
+                                device: map.device,
+                                inode: map.inode,
+                                offset: map.offset,
+                            })
+                        });
+                        SelectedWithKey { item, key }
+                    })
+                    .collect();
+                self.sort_by_score_and_key(&mut keyed);
+                keyed.into_iter().map(|entry| entry.item).collect()
+            }
+        }
+    }
+
+    fn sort_by_score_and_key<K: Ord>(&self, items: &mut [SelectedWithKey<K>]) {
+        items.sort_unstable_by(|a, b| {
+            let score_cmp = self.rank(a.item.score, a.item.eta, b.item.score, b.item.eta);
+            if score_cmp != Ordering::Equal {
+                return score_cmp;
+            }
+            match (&a.key, &b.key) {
+                (Some(a_key), Some(b_key)) => a_key
+                    .cmp(b_key)
+                    .then_with(|| a.item.index.cmp(&b.item.index)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => a.item.index.cmp(&b.item.index),
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SelectedWithKey<K> {
+    item: SelectedMap,
+    key: Option<K>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct BlockKey {
+    device: u64,
+    block: u64,
+    offset: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct InodeKey {
+    device: u64,
+    inode: u64,
+    offset: u64,
+}
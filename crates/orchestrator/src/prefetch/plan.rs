@@ -2,17 +2,47 @@
 
 use crate::domain::MapId;
 use crate::domain::MapKey;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct PrefetchPlan {
     pub maps: Vec<MapId>,
+    /// Bytes charged for each of `maps`, in the same order.
+    pub charged: Vec<u64>,
+    /// Bytes charged against the budget: the uncached part of each map
+    /// when residency-aware planning is on, full map lengths otherwise.
+    /// Bytes shared by overlapping segments of one file count once.
     pub total_bytes: u64,
     pub budget_bytes: u64,
+    /// Candidates left out because they were prefetched recently and
+    /// have not been evicted since.
+    pub cooldown_skipped: usize,
+    /// Candidates in cooldown replanned because their pages were evicted.
+    pub evicted: usize,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct PrefetchReport {
+    /// Maps prefetched successfully.
     pub num_maps: usize,
+    /// Keys of the maps prefetched successfully.
+    pub prefetched: Vec<MapKey>,
+    /// Prefetch calls the plan was merged into: overlapping and adjacent
+    /// segments of the same file are read as one range.
+    pub extents: usize,
+    /// Planned maps left undispatched because execution was cancelled.
+    pub skipped: usize,
+    /// Whether execution stopped before working through the whole plan.
+    pub cancelled: bool,
+    /// Bytes the plan expected to read (`PrefetchPlan::total_bytes`).
     pub total_bytes: u64,
+    /// Bytes actually issued for reading after skipping cached pages.
+    pub read_bytes: u64,
+    /// Time dispatch was held back by I/O throttling.
+    pub throttled: Duration,
+    /// Planned-out maps still in cooldown (`PrefetchPlan::cooldown_skipped`).
+    pub cooldown_skipped: usize,
+    /// Maps replanned after eviction (`PrefetchPlan::evicted`).
+    pub evicted: usize,
     pub failures: Vec<MapKey>,
 }
@@ -2,192 +2,187 @@
 
 use crate::domain::{MapId, MemStat};
 use crate::prediction::Prediction;
-use crate::prefetch::PrefetchPlan;
+use crate::prefetch::budget::{MemoryBudget, PressureScale, kb};
+use crate::prefetch::coalesce::{Coverage, charge};
+use crate::prefetch::cooldown::Cooldown;
+use crate::prefetch::order::{PlanOrder, SelectedMap};
+use crate::prefetch::residency::{ResidencyProbe, ResidencyProbes};
+use crate::prefetch::{PrefetchPlan, PrefetchReport};
 use crate::stores::Stores;
-use config::{Config, SortStrategy};
+use config::Config;
 use half::f16;
-use std::cmp::Ordering;
+use std::time::Duration;
 use tracing::trace;
 
-/// Default block size for computing block index when metadata is unavailable.
-const DEFAULT_BLOCK_SIZE: u64 = 4096;
-
 pub trait PrefetchPlanner: Send + Sync {
-    /// Create a prefetch plan from prediction scores and memory stats.
-    fn plan(&self, prediction: &Prediction, stores: &Stores, memstat: &MemStat) -> PrefetchPlan;
+    /// Create a prefetch plan from prediction scores and memory stats,
+    /// with the budget scaled by `pressure`, skipping maps still in
+    /// `cooldown`.
+    fn plan(
+        &self,
+        prediction: &Prediction,
+        stores: &Stores,
+        memstat: &MemStat,
+        pressure: PressureScale,
+        cooldown: &Cooldown,
+    ) -> PrefetchPlan;
+
+    /// Page-cache probes for the maps `plan` may select, run on the
+    /// blocking pool before planning. `plan` never probes; maps left
+    /// unprobed are charged in full.
+    fn residency_probes(
+        &self,
+        _prediction: &Prediction,
+        _stores: &Stores,
+        _memstat: &MemStat,
+        _pressure: PressureScale,
+    ) -> Option<ResidencyProbes> {
+        None
+    }
+
+    /// Start the cooldown for the maps `report` prefetched. Returns the
+    /// residency probes it needs, to run on the blocking pool.
+    fn record_prefetch(
+        &self,
+        _report: &PrefetchReport,
+        _cooldown: &Cooldown,
+    ) -> Option<ResidencyProbes> {
+        None
+    }
 }
 
 #[derive(Debug)]
 pub struct GreedyPrefetchPlanner {
-    sort: SortStrategy,
-    memtotal: i32,
-    memavailable: i32,
+    order: PlanOrder,
+    budget: MemoryBudget,
+    residency: ResidencyProbe,
+    cooldown_period: Duration,
 }
 
 impl GreedyPrefetchPlanner {
     pub fn new(config: &Config) -> Self {
-        let policy = config.model.memory.clamp();
         Self {
-            sort: config.system.sortstrategy,
-            memtotal: policy.memtotal,
-            memavailable: policy.memavailable,
+            order: PlanOrder::new(config),
+            budget: MemoryBudget::new(config),
+            residency: ResidencyProbe::new(config),
+            cooldown_period: config.system.prefetch_cooldown,
         }
     }
 
-    fn available_kb(&self, mem: &MemStat) -> u64 {
-        let mut budget = self.memtotal as i64 * mem.total as i64 / 100;
-        budget += self.memavailable as i64 * mem.available as i64 / 100;
-        budget.max(0) as u64
+    fn available_kb(&self, mem: &MemStat, pressure: PressureScale) -> u64 {
+        self.budget.available_kb(mem, pressure)
     }
 
-    fn kb(bytes: u64) -> u64 {
-        bytes.div_ceil(1024)
-    }
-}
-
-impl PrefetchPlanner for GreedyPrefetchPlanner {
-    fn plan(&self, prediction: &Prediction, stores: &Stores, memstat: &MemStat) -> PrefetchPlan {
-        let mut items: Vec<(MapId, f16)> = prediction
+    /// Scored maps in priority order.
+    fn ranked(&self, prediction: &Prediction) -> Vec<(MapId, f16, f32)> {
+        let mut items: Vec<(MapId, f16, f32)> = prediction
             .map_scores
             .iter()
             .filter(|(_, score)| **score > f16::ZERO)
-            .map(|(id, score)| (*id, *score))
+            .map(|(id, score)| {
+                let eta = prediction.map_eta.get(id).copied().unwrap_or(f32::INFINITY);
+                (*id, *score, eta)
+            })
             .collect();
-        items.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        items.sort_unstable_by(|a, b| self.order.rank(a.1, a.2, b.1, b.2));
+        items
+    }
+}
 
-        let mut budget_kb = self.available_kb(memstat);
+impl PrefetchPlanner for GreedyPrefetchPlanner {
+    fn plan(
+        &self,
+        prediction: &Prediction,
+        stores: &Stores,
+        memstat: &MemStat,
+        pressure: PressureScale,
+        cooldown: &Cooldown,
+    ) -> PrefetchPlan {
+        let items = self.ranked(prediction);
+
+        let mut budget_kb = self.available_kb(memstat, pressure);
         let mut selected = Vec::new();
         let mut total_bytes: u64 = 0;
+        let mut cooldown = cooldown.begin(self.cooldown_period);
+        let mut coverage = Coverage::default();
 
-        for (map_id, score) in items {
+        for (map_id, score, eta) in items {
             let Some(map) = stores.maps.get(map_id) else {
                 continue;
             };
-            let map_kb = Self::kb(map.length);
+            // Once the budget is spent only segments the plan already
+            // covers can still fit, so others are not probed.
+            let uncovered = coverage.uncovered(map);
+            if budget_kb == 0 && uncovered > 0 {
+                continue;
+            }
+            let probed = self.residency.probed(map);
+            if !cooldown.admit(map, probed) {
+                continue;
+            }
+            let uncached = probed.unwrap_or(map.length);
+            // Bytes shared with already selected segments are charged once.
+            let bytes = charge(map, uncached, uncovered);
+            let map_kb = kb(bytes);
             if map_kb > budget_kb {
                 continue;
             }
             budget_kb = budget_kb.saturating_sub(map_kb);
-            total_bytes = total_bytes.saturating_add(map.length);
+            coverage.insert(map);
+            self.residency.invalidate(map);
+            total_bytes = total_bytes.saturating_add(bytes);
             selected.push(SelectedMap {
                 id: map_id,
                 score,
+                eta,
+                bytes,
                 index: selected.len(),
             });
         }
 
-        // Sort selected maps based on strategy for I/O efficiency.
-        match self.sort {
-            SortStrategy::None => {}
-            SortStrategy::Path => {
-                let mut keyed: Vec<SelectedWithKey<std::sync::Arc<std::path::Path>>> = selected
-                    .into_iter()
-                    .map(|item| {
-                        let key = stores.maps.get(item.id).map(|m| m.path.clone());
-                        SelectedWithKey { item, key }
-                    })
-                    .collect();
-                sort_by_score_and_key(&mut keyed);
-                selected = keyed.into_iter().map(|entry| entry.item).collect();
-            }
-            SortStrategy::Block => {
-                let mut keyed: Vec<SelectedWithKey<BlockKey>> = selected
-                    .into_iter()
-                    .map(|item| {
-                        let key = stores.maps.get(item.id).and_then(|map| {
-                            if map.device == 0 && map.inode == 0 {
-                                return None;
-                            }
-                            let block = map.offset / DEFAULT_BLOCK_SIZE;
-                            Some(BlockKey {
-                                device: map.device,
-                                block,
-                                offset: map.offset,
-                            })
-                        });
-                        SelectedWithKey { item, key }
-                    })
-                    .collect();
-                sort_by_score_and_key(&mut keyed);
-                selected = keyed.into_iter().map(|entry| entry.item).collect();
-            }
-            SortStrategy::Inode => {
-                let mut keyed: Vec<SelectedWithKey<InodeKey>> = selected
-                    .into_iter()
-                    .map(|item| {
-                        let key = stores.maps.get(item.id).and_then(|map| {
-                            if map.device == 0 && map.inode == 0 {
-                                return None;
-                            }
-                            Some(InodeKey {
-                                device: map.device,
-                                inode: map.inode,
-                                offset: map.offset,
-                            })
-                        });
-                        SelectedWithKey { item, key }
-                    })
-                    .collect();
-                sort_by_score_and_key(&mut keyed);
-                selected = keyed.into_iter().map(|entry| entry.item).collect();
-            }
-        }
+        let selected = self.order.order_for_io(selected, stores);
 
         trace!(
             selected = selected.len(),
-            total_bytes, "prefetch plan created"
+            total_bytes,
+            cooldown_skipped = cooldown.skipped,
+            evicted = cooldown.evicted,
+            "prefetch plan created"
         );
 
         PrefetchPlan {
-            maps: selected.into_iter().map(|item| item.id).collect(),
+            maps: selected.iter().map(|item| item.id).collect(),
+            charged: selected.iter().map(|item| item.bytes).collect(),
             total_bytes,
-            budget_bytes: self.available_kb(memstat) * 1024,
+            budget_bytes: self.available_kb(memstat, pressure) * 1024,
+            cooldown_skipped: cooldown.skipped,
+            evicted: cooldown.evicted,
         }
     }
-}
-
-#[derive(Debug, Clone)]
-struct SelectedMap {
-    id: MapId,
-    score: f16,
-    index: usize,
-}
-
-#[derive(Debug, Clone)]
-struct SelectedWithKey<K> {
-    item: SelectedMap,
-    key: Option<K>,
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct BlockKey {
-    device: u64,
-    block: u64,
-    offset: u64,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct InodeKey {
-    device: u64,
-    inode: u64,
-    offset: u64,
-}
+    fn residency_probes(
+        &self,
+        prediction: &Prediction,
+        stores: &Stores,
+        memstat: &MemStat,
+        pressure: PressureScale,
+    ) -> Option<ResidencyProbes> {
+        let maps = self
+            .ranked(prediction)
+            .into_iter()
+            .filter_map(|(id, _, _)| stores.maps.get(id).cloned())
+            .collect();
+        self.residency
+            .probes(maps, self.available_kb(memstat, pressure))
+    }
 
-fn sort_by_score_and_key<K: Ord>(items: &mut [SelectedWithKey<K>]) {
-    items.sort_unstable_by(|a, b| {
-        let score_cmp = b.item.score.total_cmp(&a.item.score);
-        if score_cmp != Ordering::Equal {
-            return score_cmp;
-        }
-        match (&a.key, &b.key) {
-            (Some(a_key), Some(b_key)) => a_key
-                .cmp(b_key)
-                .then_with(|| a.item.index.cmp(&b.item.index)),
-            (Some(_), None) => Ordering::Less,
-            (None, Some(_)) => Ordering::Greater,
-            (None, None) => a.item.index.cmp(&b.item.index),
-        }
-    });
+    fn record_prefetch(
+        &self,
+        report: &PrefetchReport,
+        cooldown: &Cooldown,
+    ) -> Option<ResidencyProbes> {
+        cooldown.record(self.cooldown_period, &report.prefetched, &self.residency)
+    }
 }
 
 #[cfg(test)]
@@ -210,7 +205,7 @@ mod tests {
             available in 0u64..1024,
         ) {
             let mut config = Config::default();
-            config.model.memory = MemoryPolicy { memtotal, memavailable };
+            config.model.memory = MemoryPolicy { memtotal, memavailable, ..Default::default() };
             config.system.sortstrategy = SortStrategy::None;
 
             let planner = GreedyPrefetchPlanner::new(&config);
@@ -234,10 +229,19 @@ mod tests {
                 cached: 0,
                 pagein: 0,
                 pageout: 0,
+                memory_pressure: None,
+                io_pressure: None,
+                cgroup: None,
             };
 
-            let plan = planner.plan(&prediction, &stores, &mem);
-            let budget_bytes = planner.available_kb(&mem) * 1024;
+            let plan = planner.plan(
+                &prediction,
+                &stores,
+                &mem,
+                PressureScale::default(),
+                &Cooldown::default(),
+            );
+            let budget_bytes = planner.available_kb(&mem, PressureScale::default()) * 1024;
 
             prop_assert!(plan.total_bytes <= budget_bytes);
 
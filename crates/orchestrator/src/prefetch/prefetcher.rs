@@ -1,5 +1,8 @@
 #![deny(unsafe_code)]
 
+use crate::prefetch::coalesce::{Extent, coalesce};
+use crate::prefetch::residency::uncached_ranges;
+use crate::prefetch::throttle::{IoThrottle, ThrottleSettings};
 use crate::prefetch::{PrefetchPlan, PrefetchReport};
 use crate::stores::Stores;
 use async_trait::async_trait;
@@ -9,12 +12,22 @@ use std::fs::OpenOptions;
 use std::io::{Read, Seek, SeekFrom};
 use std::num::NonZeroUsize;
 use std::os::unix::fs::OpenOptionsExt;
+use tokio_util::sync::CancellationToken;
 use tracing::debug;
 
+/// Maps dispatched per chunk unless overridden with `with_chunk_size`.
+const DEFAULT_CHUNK_SIZE: usize = 64;
+
 #[async_trait]
 pub trait Prefetcher: Send + Sync {
-    /// Execute the prefetch plan (side effects only).
-    async fn execute(&self, plan: &PrefetchPlan, stores: &Stores) -> PrefetchReport;
+    /// Execute the prefetch plan (side effects only). Stops dispatching
+    /// once `cancel` fires and reports how far it got.
+    async fn execute(
+        &self,
+        plan: &PrefetchPlan,
+        stores: &Stores,
+        cancel: &CancellationToken,
+    ) -> PrefetchReport;
 }
 
 #[derive(Debug, Default)]
@@ -22,7 +35,12 @@ pub struct NoopPrefetcher;
 
 #[async_trait]
 impl Prefetcher for NoopPrefetcher {
-    async fn execute(&self, _plan: &PrefetchPlan, _stores: &Stores) -> PrefetchReport {
+    async fn execute(
+        &self,
+        _plan: &PrefetchPlan,
+        _stores: &Stores,
+        _cancel: &CancellationToken,
+    ) -> PrefetchReport {
         PrefetchReport::default()
     }
 }
@@ -34,11 +52,29 @@ impl Prefetcher for NoopPrefetcher {
 #[derive(Debug, Clone)]
 pub struct ReadPrefetcher {
     concurrency: usize,
+    chunk_size: usize,
+    throttle: ThrottleSettings,
 }
 
 impl ReadPrefetcher {
     pub fn new(concurrency: usize) -> Self {
-        Self { concurrency }
+        Self {
+            concurrency,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            throttle: ThrottleSettings::default(),
+        }
+    }
+
+    /// Dispatch plans in chunks of `chunk_size` maps.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Throttle dispatch while executing plans.
+    pub fn with_throttle(mut self, throttle: ThrottleSettings) -> Self {
+        self.throttle = throttle;
+        self
     }
 
     fn readahead(path: &std::path::Path, offset: i64, length: i64) -> Result<(), std::io::Error> {
@@ -74,11 +110,18 @@ impl ReadPrefetcher {
 
 #[async_trait]
 impl Prefetcher for ReadPrefetcher {
-    async fn execute(&self, plan: &PrefetchPlan, stores: &Stores) -> PrefetchReport {
-        execute_concurrent(plan, stores, self.concurrency, |path, offset, length| {
-            Self::readahead(path, offset, length)
-        })
-        .await
+    async fn execute(
+        &self,
+        plan: &PrefetchPlan,
+        stores: &Stores,
+        cancel: &CancellationToken,
+    ) -> PrefetchReport {
+        let options = ExecuteOptions {
+            concurrency: self.concurrency,
+            chunk_size: self.chunk_size,
+            throttle: self.throttle,
+        };
+        execute_concurrent(plan, stores, options, cancel, Self::readahead).await
     }
 }
 
@@ -92,11 +135,29 @@ pub type PosixFadvisePrefetcher = ReadPrefetcher;
 #[derive(Debug, Clone)]
 pub struct ReadaheadPrefetcher {
     concurrency: usize,
+    chunk_size: usize,
+    throttle: ThrottleSettings,
 }
 
 impl ReadaheadPrefetcher {
     pub fn new(concurrency: usize) -> Self {
-        Self { concurrency }
+        Self {
+            concurrency,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            throttle: ThrottleSettings::default(),
+        }
+    }
+
+    /// Dispatch plans in chunks of `chunk_size` maps.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Throttle dispatch while executing plans.
+    pub fn with_throttle(mut self, throttle: ThrottleSettings) -> Self {
+        self.throttle = throttle;
+        self
     }
 
     /// Probe whether readahead(2) is available on this kernel.
@@ -154,11 +215,18 @@ impl ReadaheadPrefetcher {
 
 #[async_trait]
 impl Prefetcher for ReadaheadPrefetcher {
-    async fn execute(&self, plan: &PrefetchPlan, stores: &Stores) -> PrefetchReport {
-        execute_concurrent(plan, stores, self.concurrency, |path, offset, length| {
-            Self::do_readahead(path, offset, length)
-        })
-        .await
+    async fn execute(
+        &self,
+        plan: &PrefetchPlan,
+        stores: &Stores,
+        cancel: &CancellationToken,
+    ) -> PrefetchReport {
+        let options = ExecuteOptions {
+            concurrency: self.concurrency,
+            chunk_size: self.chunk_size,
+            throttle: self.throttle,
+        };
+        execute_concurrent(plan, stores, options, cancel, Self::do_readahead).await
     }
 }
 
@@ -169,11 +237,29 @@ impl Prefetcher for ReadaheadPrefetcher {
 #[derive(Debug, Clone)]
 pub struct MadvisePrefetcher {
     concurrency: usize,
+    chunk_size: usize,
+    throttle: ThrottleSettings,
 }
 
 impl MadvisePrefetcher {
     pub fn new(concurrency: usize) -> Self {
-        Self { concurrency }
+        Self {
+            concurrency,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            throttle: ThrottleSettings::default(),
+        }
+    }
+
+    /// Dispatch plans in chunks of `chunk_size` maps.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Throttle dispatch while executing plans.
+    pub fn with_throttle(mut self, throttle: ThrottleSettings) -> Self {
+        self.throttle = throttle;
+        self
     }
 
     /// Probe whether madvise(MADV_WILLNEED) is available.
@@ -182,11 +268,7 @@ impl MadvisePrefetcher {
         true
     }
 
-    fn do_madvise(
-        path: &std::path::Path,
-        offset: i64,
-        length: i64,
-    ) -> Result<(), std::io::Error> {
+    fn do_madvise(path: &std::path::Path, offset: i64, length: i64) -> Result<(), std::io::Error> {
         use nix::sys::mman;
 
         if length <= 0 {
@@ -231,208 +313,130 @@ impl MadvisePrefetcher {
 
 #[async_trait]
 impl Prefetcher for MadvisePrefetcher {
-    async fn execute(&self, plan: &PrefetchPlan, stores: &Stores) -> PrefetchReport {
-        execute_concurrent(plan, stores, self.concurrency, |path, offset, length| {
-            Self::do_madvise(path, offset, length)
-        })
-        .await
+    async fn execute(
+        &self,
+        plan: &PrefetchPlan,
+        stores: &Stores,
+        cancel: &CancellationToken,
+    ) -> PrefetchReport {
+        let options = ExecuteOptions {
+            concurrency: self.concurrency,
+            chunk_size: self.chunk_size,
+            throttle: self.throttle,
+        };
+        execute_concurrent(plan, stores, options, cancel, Self::do_madvise).await
     }
 }
 
 // ---------------------------------------------------------------------------
-// mincore — determine uncached page ranges
+// Shared concurrent execution helper
 // ---------------------------------------------------------------------------
 
-/// Query the page cache via mincore(2) and return contiguous uncached byte ranges.
-/// Falls back to the full range if mincore is unavailable.
-fn uncached_ranges(
-    path: &std::path::Path,
-    offset: i64,
-    length: i64,
-) -> Vec<(i64, i64)> {
-    if length <= 0 {
-        return vec![];
-    }
-
-    #[allow(unsafe_code)]
-    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
-    if page_size == 0 {
-        return vec![(offset, length)];
-    }
-
-    let file = match OpenOptions::new()
-        .read(true)
-        .custom_flags(libc::O_NOCTTY | libc::O_NOATIME)
-        .open(path)
-    {
-        Ok(f) => f,
-        Err(_) => return vec![(offset, length)],
-    };
-
-    // Align offset down and length up to page boundaries for mmap.
-    let aligned_offset = (offset as usize) & !(page_size - 1);
-    let end = (offset as usize) + (length as usize);
-    let aligned_end = (end + page_size - 1) & !(page_size - 1);
-    let aligned_length = aligned_end - aligned_offset;
-
-    let Some(map_len) = NonZeroUsize::new(aligned_length) else {
-        return vec![];
-    };
-
-    use nix::sys::mman;
-
-    #[allow(unsafe_code)]
-    let addr = match unsafe {
-        mman::mmap(
-            None,
-            map_len,
-            mman::ProtFlags::PROT_READ,
-            mman::MapFlags::MAP_PRIVATE,
-            &file,
-            aligned_offset as i64,
-        )
-    } {
-        Ok(a) => a,
-        Err(_) => return vec![(offset, length)],
-    };
-
-    let num_pages = aligned_length / page_size;
-    let mut vec = vec![0u8; num_pages];
-
-    #[allow(unsafe_code)]
-    let mincore_ok = unsafe {
-        libc::mincore(
-            addr.as_ptr() as *mut libc::c_void,
-            aligned_length,
-            vec.as_mut_ptr(),
-        ) == 0
-    };
-
-    #[allow(unsafe_code)]
-    unsafe {
-        let _ = mman::munmap(addr, aligned_length);
-    }
-
-    if !mincore_ok {
-        return vec![(offset, length)];
-    }
-
-    // Convert the page-level cache bitmap into byte ranges of uncached regions.
-    // Only consider pages that overlap the original [offset, offset+length) range.
-    let orig_start = offset as usize;
-    let orig_end = end;
-    let mut ranges = Vec::new();
-    let mut run_start: Option<usize> = None;
-
-    for (i, &cached) in vec.iter().enumerate() {
-        let page_start = aligned_offset + i * page_size;
-        let page_end = page_start + page_size;
-
-        // Skip pages entirely outside the original range.
-        if page_end <= orig_start || page_start >= orig_end {
-            if let Some(start) = run_start.take() {
-                let clamped_start = start.max(orig_start) as i64;
-                let clamped_end = page_start.min(orig_end) as i64;
-                if clamped_end > clamped_start {
-                    ranges.push((clamped_start, clamped_end - clamped_start));
-                }
-            }
-            continue;
-        }
-
-        let in_cache = (cached & 1) != 0;
-        if !in_cache {
-            if run_start.is_none() {
-                run_start = Some(page_start);
-            }
-        } else if let Some(start) = run_start.take() {
-            let clamped_start = start.max(orig_start) as i64;
-            let clamped_end = page_start.min(orig_end) as i64;
-            if clamped_end > clamped_start {
-                ranges.push((clamped_start, clamped_end - clamped_start));
-            }
-        }
-    }
-
-    // Flush trailing run.
-    if let Some(start) = run_start {
-        let clamped_start = start.max(orig_start) as i64;
-        let clamped_end = orig_end as i64;
-        if clamped_end > clamped_start {
-            ranges.push((clamped_start, clamped_end - clamped_start));
-        }
-    }
-
-    ranges
+/// Per-prefetcher settings for [`execute_concurrent`].
+#[derive(Debug, Clone, Copy)]
+struct ExecuteOptions {
+    concurrency: usize,
+    chunk_size: usize,
+    throttle: ThrottleSettings,
 }
 
-// ---------------------------------------------------------------------------
-// Shared concurrent execution helper
-// ---------------------------------------------------------------------------
-
 async fn execute_concurrent<F>(
     plan: &PrefetchPlan,
     stores: &Stores,
-    concurrency: usize,
+    options: ExecuteOptions,
+    cancel: &CancellationToken,
     readahead_fn: F,
 ) -> PrefetchReport
 where
     F: Fn(&std::path::Path, i64, i64) -> Result<(), std::io::Error> + Send + Sync + 'static + Clone,
 {
     let mut report = PrefetchReport::default();
-    let concurrency = concurrency.max(1);
-
-    let tasks: Vec<(crate::domain::MapKey, std::sync::Arc<std::path::Path>, i64, i64)> = plan
-        .maps
-        .iter()
-        .filter_map(|map_id| {
-            let map = stores.maps.get(*map_id)?;
-            Some((
-                map.key(),
-                map.path.clone(),
-                map.offset as i64,
-                map.length as i64,
-            ))
-        })
-        .collect();
-
-    let mut stream = stream::iter(tasks).map(move |(map_key, path, offset, length)| {
+    let concurrency = options.concurrency.max(1);
+    let chunk_size = options.chunk_size.max(1);
+    let throttle = std::sync::Arc::new(IoThrottle::new(options.throttle));
+
+    // Overlapping and adjacent segments of one file become a single call.
+    let tasks = coalesce(
+        plan.maps
+            .iter()
+            .filter_map(|map_id| stores.maps.get(*map_id)),
+    );
+    report.extents = tasks.len();
+
+    let run = |extent: Extent| {
         let f = readahead_fn.clone();
+        let throttle = throttle.clone();
+        let cancel = cancel.clone();
         async move {
-            let join = tokio::task::spawn_blocking(move || {
-                // Use mincore to skip already-cached pages.
-                let ranges = uncached_ranges(&path, offset, length);
-                if ranges.is_empty() {
-                    return Ok(()); // fully cached
+            let Extent {
+                path,
+                offset,
+                length,
+                maps,
+            } = extent;
+            let (offset, length) = (offset as i64, length as i64);
+            if cancel.is_cancelled() {
+                return (maps, None);
+            }
+
+            // Use mincore to skip already-cached pages.
+            let probe_path = path.clone();
+            let ranges =
+                tokio::task::spawn_blocking(move || uncached_ranges(&probe_path, offset, length))
+                    .await
+                    .unwrap_or_else(|_| vec![(offset, length)]);
+            let bytes: u64 = ranges.iter().map(|(_, len)| *len as u64).sum();
+            if bytes > 0 {
+                tokio::select! {
+                    _ = cancel.cancelled() => return (maps, None),
+                    _ = throttle.acquire(bytes) => {}
                 }
+            }
+
+            let join = tokio::task::spawn_blocking(move || {
                 for (range_offset, range_length) in ranges {
                     f(&path, range_offset, range_length)?;
                 }
-                Ok(())
+                Ok(bytes)
             })
             .await;
             match join {
-                Ok(result) => (map_key, result),
+                Ok(result) => (maps, Some(result)),
                 Err(err) => {
                     let err = std::io::Error::other(err);
-                    (map_key, Err(err))
+                    (maps, Some(Err(err)))
                 }
             }
         }
-    });
-
-    while let Some((map_key, result)) =
-        stream.by_ref().buffer_unordered(concurrency).next().await
-    {
-        match result {
-            Ok(()) => report.num_maps += 1,
-            Err(err) => {
-                debug!(?map_key, %err, "prefetch failed");
-                report.failures.push(map_key);
+    };
+
+    // Work through the plan a chunk at a time so cancellation takes effect
+    // without abandoning reads already handed to the blocking pool.
+    let mut pending = tasks.into_iter();
+    while !cancel.is_cancelled() && pending.len() > 0 {
+        let chunk: Vec<_> = pending.by_ref().take(chunk_size).collect();
+        let mut stream = stream::iter(chunk).map(&run).buffer_unordered(concurrency);
+        while let Some((maps, outcome)) = stream.next().await {
+            match outcome {
+                Some(Ok(read)) => {
+                    report.num_maps += maps.len();
+                    report.read_bytes += read;
+                    report.prefetched.extend(maps);
+                }
+                Some(Err(err)) => {
+                    debug!(?maps, %err, "prefetch failed");
+                    report.failures.extend(maps);
+                }
+                None => report.skipped += maps.len(),
             }
         }
     }
+    report.skipped += pending.map(|extent| extent.maps.len()).sum::<usize>();
+    report.cancelled = report.skipped > 0;
 
     report.total_bytes = plan.total_bytes;
+    report.cooldown_skipped = plan.cooldown_skipped;
+    report.evicted = plan.evicted;
+    report.throttled = throttle.waited().await;
     report
 }
@@ -0,0 +1,63 @@
+#![forbid(unsafe_code)]
+
+use crate::prediction::Prediction;
+use crate::prefetch::PrefetchPlan;
+use crate::prefetch::residency::uncached_bytes;
+use crate::stores::Stores;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// A prefetch plan resolved for display: what would be read, in order,
+/// without reading anything.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlanPreview {
+    pub budget_bytes: u64,
+    /// Bytes the planner charged against the budget.
+    pub total_bytes: u64,
+    pub entries: Vec<PlanEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanEntry {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub length: u64,
+    pub score: f32,
+    /// Bytes of the range already in the page cache.
+    pub resident_bytes: u64,
+    /// Bytes the planner charged up to and including this entry.
+    pub cumulative_bytes: u64,
+}
+
+impl PlanPreview {
+    /// Resolve `plan` against the maps and scores it was built from,
+    /// probing page-cache residency of every entry.
+    pub fn new(plan: &PrefetchPlan, prediction: &Prediction, stores: &Stores) -> Self {
+        let mut cumulative_bytes = 0u64;
+        let entries = plan
+            .maps
+            .iter()
+            .enumerate()
+            .filter_map(|(index, id)| {
+                let map = stores.maps.get(*id)?;
+                let uncached = uncached_bytes(&map.path, map.offset as i64, map.length as i64);
+                let charged = plan.charged.get(index).copied().unwrap_or_default();
+                cumulative_bytes = cumulative_bytes.saturating_add(charged);
+                Some(PlanEntry {
+                    path: map.path.to_path_buf(),
+                    offset: map.offset,
+                    length: map.length,
+                    score: prediction.map_scores.get(id).map_or(0.0, |s| s.to_f32()),
+                    resident_bytes: map.length.saturating_sub(uncached),
+                    cumulative_bytes,
+                })
+            })
+            .collect();
+
+        Self {
+            budget_bytes: plan.budget_bytes,
+            total_bytes: plan.total_bytes,
+            entries,
+        }
+    }
+}
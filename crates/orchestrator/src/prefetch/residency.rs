@@ -0,0 +1,305 @@
+#![deny(unsafe_code)]
+
+use crate::domain::{MapKey, MapSegment};
+use crate::prefetch::budget::kb;
+use config::Config;
+use moka::sync::Cache;
+use std::fs::OpenOptions;
+use std::num::NonZeroUsize;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `cachestat(2)` syscall number (Linux 6.5+, same on all architectures).
+const SYS_CACHESTAT: libc::c_long = 451;
+
+/// Upper bound on cached residency entries.
+const CACHE_CAPACITY: u64 = 65_536;
+
+/// Set once `cachestat(2)` has returned ENOSYS; mincore is used from then on.
+static CACHESTAT_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Residency probes to run on the blocking pool before planning.
+pub type ResidencyProbes = Box<dyn FnOnce() + Send>;
+
+/// Page-cache residency prober used while planning.
+///
+/// Results are cached per map for a short TTL so maps that stay candidates
+/// across consecutive ticks are not probed every cycle. When disabled every
+/// map is reported fully uncached.
+#[derive(Clone)]
+pub(crate) struct ResidencyProbe {
+    cache: Option<Cache<MapKey, u64>>,
+}
+
+impl std::fmt::Debug for ResidencyProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResidencyProbe")
+            .field("enabled", &self.cache.is_some())
+            .finish()
+    }
+}
+
+impl ResidencyProbe {
+    pub(crate) fn new(config: &Config) -> Self {
+        let system = &config.system;
+        let ttl = system.residency_cache_ttl.unwrap_or(config.model.cycle);
+        let cache = system.residency_aware.then(|| {
+            Cache::builder()
+                .max_capacity(CACHE_CAPACITY)
+                .time_to_live(ttl)
+                .build()
+        });
+        Self { cache }
+    }
+
+    /// Drop the cached result for `map`, e.g. once it has been planned for
+    /// prefetching and its residency is about to change.
+    pub(crate) fn invalidate(&self, map: &MapSegment) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(&map.key());
+        }
+    }
+
+    /// Bytes of `map` not in the page cache, as last probed by
+    /// [`Self::probes`]. Never probes, so it is safe to call on the async
+    /// runtime; `None` when disabled or the map has not been probed.
+    pub(crate) fn probed(&self, map: &MapSegment) -> Option<u64> {
+        self.cache.as_ref()?.get(&map.key())
+    }
+
+    /// Bytes of `key` not in the page cache, probed afresh and not cached.
+    /// Reports the whole map when disabled.
+    pub(crate) fn observe(&self, key: &MapKey) -> u64 {
+        if self.cache.is_none() {
+            return key.length;
+        }
+        probe(key)
+    }
+
+    /// Blocking probes for `maps`, in planning order, that fill the cache
+    /// so a following plan does not probe on the async runtime. Probing
+    /// stops once the uncached bytes found would use up `budget_kb`.
+    pub(crate) fn probes(&self, maps: Vec<MapSegment>, budget_kb: u64) -> Option<ResidencyProbes> {
+        let cache = self.cache.clone()?;
+        if maps.is_empty() {
+            return None;
+        }
+        Some(Box::new(move || {
+            let mut remaining_kb = budget_kb;
+            for map in maps {
+                if remaining_kb == 0 {
+                    break;
+                }
+                let key = map.key();
+                let map_kb = kb(cache.get_with(key.clone(), || probe(&key)));
+                if map_kb <= remaining_kb {
+                    remaining_kb -= map_kb;
+                }
+            }
+        }))
+    }
+}
+
+fn probe(key: &MapKey) -> u64 {
+    uncached_bytes(&key.path, key.offset as i64, key.length as i64).min(key.length)
+}
+
+/// Bytes of `[offset, offset + length)` in `path` not in the page cache.
+/// Uses cachestat(2) when available, mincore(2) otherwise. Unreadable
+/// files count as fully uncached.
+pub(crate) fn uncached_bytes(path: &std::path::Path, offset: i64, length: i64) -> u64 {
+    if length <= 0 {
+        return 0;
+    }
+    if !CACHESTAT_UNSUPPORTED.load(Ordering::Relaxed)
+        && let Some(bytes) = cachestat_uncached(path, offset, length)
+    {
+        return bytes;
+    }
+    uncached_ranges(path, offset, length)
+        .iter()
+        .map(|(_, len)| *len as u64)
+        .sum()
+}
+
+#[repr(C)]
+struct CachestatRange {
+    off: u64,
+    len: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Cachestat {
+    nr_cache: u64,
+    nr_dirty: u64,
+    nr_writeback: u64,
+    nr_evicted: u64,
+    nr_recently_evicted: u64,
+}
+
+/// Uncached bytes via cachestat(2). `None` if the syscall is unavailable
+/// or fails; the file-open failure case reports the full length.
+fn cachestat_uncached(path: &std::path::Path, offset: i64, length: i64) -> Option<u64> {
+    #[allow(unsafe_code)]
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    if page_size == 0 {
+        return None;
+    }
+
+    let file = match OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NOATIME)
+        .open(path)
+    {
+        Ok(f) => f,
+        Err(_) => return Some(length as u64),
+    };
+
+    let start = offset as u64 & !(page_size - 1);
+    let end = (offset as u64 + length as u64).div_ceil(page_size) * page_size;
+    let range = CachestatRange {
+        off: start,
+        len: end - start,
+    };
+    let mut stat = Cachestat::default();
+
+    #[allow(unsafe_code)]
+    let ret = unsafe {
+        libc::syscall(
+            SYS_CACHESTAT,
+            file.as_raw_fd(),
+            &range as *const CachestatRange,
+            &mut stat as *mut Cachestat,
+            0u32,
+        )
+    };
+    if ret != 0 {
+        if std::io::Error::last_os_error().raw_os_error() == Some(libc::ENOSYS) {
+            CACHESTAT_UNSUPPORTED.store(true, Ordering::Relaxed);
+        }
+        return None;
+    }
+
+    let pages = (end - start) / page_size;
+    let uncached_pages = pages.saturating_sub(stat.nr_cache);
+    Some((uncached_pages * page_size).min(length as u64))
+}
+
+// ---------------------------------------------------------------------------
+// mincore — determine uncached page ranges
+// ---------------------------------------------------------------------------
+
+/// Query the page cache via mincore(2) and return contiguous uncached byte ranges.
+/// Falls back to the full range if mincore is unavailable.
+pub(crate) fn uncached_ranges(path: &std::path::Path, offset: i64, length: i64) -> Vec<(i64, i64)> {
+    if length <= 0 {
+        return vec![];
+    }
+
+    #[allow(unsafe_code)]
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    if page_size == 0 {
+        return vec![(offset, length)];
+    }
+
+    let file = match OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NOATIME)
+        .open(path)
+    {
+        Ok(f) => f,
+        Err(_) => return vec![(offset, length)],
+    };
+
+    // Align offset down and length up to page boundaries for mmap.
+    let aligned_offset = (offset as usize) & !(page_size - 1);
+    let end = (offset as usize) + (length as usize);
+    let aligned_end = (end + page_size - 1) & !(page_size - 1);
+    let aligned_length = aligned_end - aligned_offset;
+
+    let Some(map_len) = NonZeroUsize::new(aligned_length) else {
+        return vec![];
+    };
+
+    use nix::sys::mman;
+
+    #[allow(unsafe_code)]
+    let addr = match unsafe {
+        mman::mmap(
+            None,
+            map_len,
+            mman::ProtFlags::PROT_READ,
+            mman::MapFlags::MAP_PRIVATE,
+            &file,
+            aligned_offset as i64,
+        )
+    } {
+        Ok(a) => a,
+        Err(_) => return vec![(offset, length)],
+    };
+
+    let num_pages = aligned_length / page_size;
+    let mut vec = vec![0u8; num_pages];
+
+    #[allow(unsafe_code)]
+    let mincore_ok = unsafe { libc::mincore(addr.as_ptr(), aligned_length, vec.as_mut_ptr()) == 0 };
+
+    #[allow(unsafe_code)]
+    unsafe {
+        let _ = mman::munmap(addr, aligned_length);
+    }
+
+    if !mincore_ok {
+        return vec![(offset, length)];
+    }
+
+    // Convert the page-level cache bitmap into byte ranges of uncached regions.
+    // Only consider pages that overlap the original [offset, offset+length) range.
+    let orig_start = offset as usize;
+    let orig_end = end;
+    let mut ranges = Vec::new();
+    let mut run_start: Option<usize> = None;
+
+    for (i, &cached) in vec.iter().enumerate() {
+        let page_start = aligned_offset + i * page_size;
+        let page_end = page_start + page_size;
+
+        // Skip pages entirely outside the original range.
+        if page_end <= orig_start || page_start >= orig_end {
+            if let Some(start) = run_start.take() {
+                let clamped_start = start.max(orig_start) as i64;
+                let clamped_end = page_start.min(orig_end) as i64;
+                if clamped_end > clamped_start {
+                    ranges.push((clamped_start, clamped_end - clamped_start));
+                }
+            }
+            continue;
+        }
+
+        let in_cache = (cached & 1) != 0;
+        if !in_cache {
+            if run_start.is_none() {
+                run_start = Some(page_start);
+            }
+        } else if let Some(start) = run_start.take() {
+            let clamped_start = start.max(orig_start) as i64;
+            let clamped_end = page_start.min(orig_end) as i64;
+            if clamped_end > clamped_start {
+                ranges.push((clamped_start, clamped_end - clamped_start));
+            }
+        }
+    }
+
+    // Flush trailing run.
+    if let Some(start) = run_start {
+        let clamped_start = start.max(orig_start) as i64;
+        let clamped_end = orig_end as i64;
+        if clamped_end > clamped_start {
+            ranges.push((clamped_start, clamped_end - clamped_start));
+        }
+    }
+
+    ranges
+}
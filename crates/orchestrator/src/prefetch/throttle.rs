@@ -0,0 +1,244 @@
+#![forbid(unsafe_code)]
+
+use procfs::{Current, IoPressure};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// Sector size used by `/proc/diskstats` counters.
+const SECTOR_BYTES: u64 = 512;
+
+/// Shortest activity sampling interval, so a zero setting cannot spin.
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Dispatch throttling settings for prefetch execution.
+/// `ThrottleSettings::default()` disables all throttling, unlike the
+/// configured defaults, which check activity every 500 ms.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThrottleSettings {
+    max_bytes_per_sec: u64,
+    io_pressure: f32,
+    foreground_bytes_per_sec: u64,
+    check_interval: Duration,
+    max_pause: Duration,
+}
+
+impl ThrottleSettings {
+    pub fn new(throttle: &config::Throttle) -> Self {
+        Self {
+            max_bytes_per_sec: throttle.max_bytes_per_sec,
+            io_pressure: throttle.io_pressure.max(0.0),
+            foreground_bytes_per_sec: throttle.foreground_bytes_per_sec,
+            check_interval: throttle.check_interval.max(MIN_CHECK_INTERVAL),
+            max_pause: throttle.max_pause,
+        }
+    }
+
+    fn monitors_activity(&self) -> bool {
+        self.io_pressure > 0.0 || self.foreground_bytes_per_sec > 0
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.max_bytes_per_sec > 0 || self.monitors_activity()
+    }
+}
+
+/// Per-execution dispatch gate: pauses while foreground I/O is busy and
+/// paces dispatch to the configured byte rate.
+#[derive(Debug)]
+pub(crate) struct IoThrottle {
+    settings: ThrottleSettings,
+    state: Mutex<ThrottleState>,
+}
+
+#[derive(Debug)]
+struct ThrottleState {
+    next_dispatch: Instant,
+    last_check: Option<Instant>,
+    busy: bool,
+    disk: Option<DiskSample>,
+    /// Bytes dispatched since the last disk sample.
+    own_bytes: u64,
+    /// When pausing for foreground I/O stops, set by the first pause, so
+    /// the pause is bounded by `max_pause` per execution rather than per
+    /// extent.
+    pause_deadline: Option<Instant>,
+    waited: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DiskSample {
+    at: Instant,
+    bytes: u64,
+}
+
+impl IoThrottle {
+    pub(crate) fn new(settings: ThrottleSettings) -> Self {
+        let now = Instant::now();
+        let disk = (settings.foreground_bytes_per_sec > 0)
+            .then(disk_bytes)
+            .flatten()
+            .map(|bytes| DiskSample { at: now, bytes });
+        Self {
+            settings,
+            state: Mutex::new(ThrottleState {
+                next_dispatch: now,
+                last_check: None,
+                busy: false,
+                disk,
+                own_bytes: 0,
+                pause_deadline: None,
+                waited: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Wait until `bytes` may be dispatched. The state lock is only held to
+    /// read and update the state, never while sleeping, so concurrent
+    /// workers share one pause and one pacing schedule.
+    pub(crate) async fn acquire(&self, bytes: u64) {
+        if !self.settings.is_enabled() {
+            return;
+        }
+        let started = Instant::now();
+
+        if self.settings.monitors_activity() {
+            loop {
+                let pause = {
+                    let mut state = self.state.lock().await;
+                    if !self.busy(&mut state) {
+                        break;
+                    }
+                    let now = Instant::now();
+                    let deadline = *state
+                        .pause_deadline
+                        .get_or_insert(now + self.settings.max_pause);
+                    deadline.saturating_duration_since(now)
+                };
+                if pause.is_zero() {
+                    break;
+                }
+                tokio::time::sleep(pause.min(self.settings.check_interval)).await;
+            }
+        }
+
+        if self.settings.max_bytes_per_sec > 0 {
+            let slot = {
+                let mut state = self.state.lock().await;
+                let slot = state.next_dispatch.max(Instant::now());
+                let cost =
+                    Duration::from_secs_f64(bytes as f64 / self.settings.max_bytes_per_sec as f64);
+                state.next_dispatch = slot + cost;
+                slot
+            };
+            tokio::time::sleep_until(slot.into()).await;
+        }
+
+        let mut state = self.state.lock().await;
+        state.own_bytes = state.own_bytes.saturating_add(bytes);
+        state.waited += started.elapsed();
+    }
+
+    /// Total time dispatch was held back.
+    pub(crate) async fn waited(&self) -> Duration {
+        self.state.lock().await.waited
+    }
+
+    /// Whether foreground I/O is busy, re-sampled at most once per
+    /// `check_interval`.
+    fn busy(&self, state: &mut ThrottleState) -> bool {
+        let now = Instant::now();
+        if state
+            .last_check
+            .is_some_and(|last| now.duration_since(last) < self.settings.check_interval)
+        {
+            return state.busy;
+        }
+        state.last_check = Some(now);
+
+        let pressure = self.settings.io_pressure > 0.0
+            && IoPressure::current().is_ok_and(|psi| psi.some.avg10 > self.settings.io_pressure);
+
+        let mut foreground = false;
+        if self.settings.foreground_bytes_per_sec > 0
+            && let Some(bytes) = disk_bytes()
+        {
+            if let Some(prev) = state.disk {
+                let elapsed = now.duration_since(prev.at).as_secs_f64();
+                let others = bytes
+                    .saturating_sub(prev.bytes)
+                    .saturating_sub(state.own_bytes);
+                foreground = elapsed > 0.0
+                    && others as f64 / elapsed > self.settings.foreground_bytes_per_sec as f64;
+            }
+            state.disk = Some(DiskSample { at: now, bytes });
+            state.own_bytes = 0;
+        }
+
+        let busy = pressure || foreground;
+        if busy != state.busy {
+            debug!(
+                pressure,
+                foreground,
+                "prefetch throttle {}",
+                if busy { "paused" } else { "resumed" }
+            );
+        }
+        state.busy = busy;
+        busy
+    }
+}
+
+/// Bytes read and written so far across whole physical disks. Stacked
+/// devices (dm, md) are skipped since their I/O also shows on the disks
+/// below them.
+fn disk_bytes() -> Option<u64> {
+    let stats = procfs::diskstats().ok()?;
+    let sectors = stats
+        .iter()
+        .filter(|disk| !disk.name.starts_with("loop") && !disk.name.starts_with("ram"))
+        .filter(|disk| {
+            let sys = Path::new("/sys/block").join(&disk.name);
+            sys.exists() && !has_slaves(&sys)
+        })
+        .map(|disk| disk.sectors_read.saturating_add(disk.sectors_written))
+        .fold(0u64, u64::saturating_add);
+    Some(sectors.saturating_mul(SECTOR_BYTES))
+}
+
+/// Whether the block device at `sys` is built on top of other devices.
+fn has_slaves(sys: &Path) -> bool {
+    std::fs::read_dir(sys.join("slaves")).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn concurrent_workers_share_one_bounded_pause() {
+        let throttle = IoThrottle::new(ThrottleSettings {
+            io_pressure: 1.0,
+            check_interval: Duration::from_secs(60),
+            max_pause: Duration::from_millis(100),
+            ..ThrottleSettings::default()
+        });
+        {
+            // Foreground I/O was just seen, and is not sampled again.
+            let mut state = throttle.state.lock().await;
+            state.last_check = Some(Instant::now());
+            state.busy = true;
+        }
+
+        let started = Instant::now();
+        let workers: Vec<_> = (0..4).map(|_| throttle.acquire(4096)).collect();
+        futures::future::join_all(workers).await;
+
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_millis(300), "{elapsed:?}");
+        // Every worker's wait counts, lock waits included.
+        assert!(throttle.waited().await >= Duration::from_millis(400));
+    }
+}
@@ -31,14 +31,24 @@ impl ActiveSet {
     pub fn exes(&self) -> FxHashSet<ExeId> {
         self.last_seen.keys().copied().collect()
     }
+
+    /// Active exes with the model time they were last seen running.
+    pub fn iter(&self) -> impl Iterator<Item = (ExeId, u64)> + '_ {
+        self.last_seen.iter().map(|(exe_id, last)| (*exe_id, *last))
+    }
+
+    /// Mark `exe_id` active as of `last`, as restored from a snapshot.
+    pub fn insert(&mut self, exe_id: ExeId, last: u64) {
+        self.last_seen.insert(exe_id, last);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use proptest::prelude::*;
-    use slotmap::SlotMap;
     use rustc_hash::FxHashSet;
+    use slotmap::SlotMap;
 
     proptest! {
         #[test]
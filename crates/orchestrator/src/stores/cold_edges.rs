@@ -0,0 +1,108 @@
+#![forbid(unsafe_code)]
+
+use crate::stores::EdgeKey;
+use half::f16;
+use rustc_hash::FxHashMap;
+
+/// Learned statistics of a Markov edge whose exes left the active set.
+/// State and timing are not kept: a revived edge starts from the state
+/// observed when it is recreated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColdEdge {
+    pub time_to_leave: [f16; 4],
+    pub transition_prob: [[f16; 4]; 4],
+    pub both_running_time: u64,
+}
+
+/// Most dormant edges kept; beyond it, those with the least shared running
+/// time are dropped.
+const MAX_COLD_EDGES: usize = 65_536;
+
+/// Dormant Markov edges, kept when `model.cold_edges` is on.
+#[derive(Debug, Default)]
+pub struct ColdEdges {
+    edges: FxHashMap<EdgeKey, ColdEdge>,
+}
+
+impl ColdEdges {
+    pub fn insert(&mut self, key: EdgeKey, edge: ColdEdge) {
+        self.edges.insert(key, edge);
+    }
+
+    /// Remove and return the dormant statistics for `key`, if any.
+    pub fn take(&mut self, key: EdgeKey) -> Option<ColdEdge> {
+        self.edges.remove(&key)
+    }
+
+    /// Drop the edges with the least shared running time until at most
+    /// [`MAX_COLD_EDGES`] remain.
+    pub fn trim(&mut self) {
+        self.trim_to(MAX_COLD_EDGES);
+    }
+
+    fn trim_to(&mut self, limit: usize) {
+        if self.edges.len() <= limit {
+            return;
+        }
+        let mut times: Vec<_> = self
+            .edges
+            .iter()
+            .map(|(key, edge)| (edge.both_running_time, *key))
+            .collect();
+        let excess = times.len() - limit;
+        times.select_nth_unstable_by_key(excess - 1, |(time, _)| *time);
+        for (_, key) in &times[..excess] {
+            self.edges.remove(key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.edges.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EdgeKey, &ColdEdge)> {
+        self.edges.iter().map(|(key, edge)| (*key, edge))
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ExeId;
+    use slotmap::SlotMap;
+
+    fn edge(both_running_time: u64) -> ColdEdge {
+        ColdEdge {
+            time_to_leave: [f16::ZERO; 4],
+            transition_prob: [[f16::ZERO; 4]; 4],
+            both_running_time,
+        }
+    }
+
+    #[test]
+    fn trim_keeps_edges_with_most_shared_running_time() {
+        let mut ids = SlotMap::<ExeId, ()>::with_key();
+        let a = ids.insert(());
+        let mut cold = ColdEdges::default();
+        for time in [30, 10, 40, 20] {
+            cold.insert(EdgeKey::new(a, ids.insert(())), edge(time));
+        }
+
+        cold.trim_to(2);
+
+        let mut kept: Vec<_> = cold
+            .iter()
+            .map(|(_, edge)| edge.both_running_time)
+            .collect();
+        kept.sort_unstable();
+        assert_eq!(kept, vec![30, 40]);
+    }
+}
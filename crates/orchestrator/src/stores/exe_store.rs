@@ -1,8 +1,8 @@
 #![forbid(unsafe_code)]
 
 use crate::domain::{Exe, ExeId, ExeKey};
-use slotmap::SlotMap;
 use rustc_hash::FxHashMap;
+use slotmap::SlotMap;
 
 #[derive(Debug, Default)]
 pub struct ExeStore {
@@ -0,0 +1,190 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::ExeId;
+use crate::math::fast_exp_neg;
+use rustc_hash::FxHashMap;
+
+/// Number of hour buckets in a week.
+pub const HOURS_PER_WEEK: usize = 7 * 24;
+
+/// 1970-01-01 was a Thursday; shift so that bucket 0 starts on Monday.
+const EPOCH_WEEKDAY_OFFSET_HOURS: i64 = 3 * 24;
+
+/// Hour-of-week bucket for a unix timestamp, in a zone `utc_offset`
+/// seconds east of UTC (0 = Monday 00:00 local time).
+pub fn hour_of_week(unix_secs: u64, utc_offset: i32) -> usize {
+    let local_hour = local_secs(unix_secs, utc_offset).div_euclid(3600);
+    (local_hour + EPOCH_WEEKDAY_OFFSET_HOURS).rem_euclid(HOURS_PER_WEEK as i64) as usize
+}
+
+fn local_secs(unix_secs: u64, utc_offset: i32) -> i64 {
+    unix_secs as i64 + i64::from(utc_offset)
+}
+
+/// Exponentially decayed launch counts per hour-of-week bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchHistogram {
+    pub weights: [f32; HOURS_PER_WEEK],
+    /// Unix time (seconds) the weights were last decayed to.
+    pub updated_at: u64,
+}
+
+impl LaunchHistogram {
+    pub fn new(now: u64) -> Self {
+        Self {
+            weights: [0.0; HOURS_PER_WEEK],
+            updated_at: now,
+        }
+    }
+
+    /// Decay all buckets from `updated_at` to `now`.
+    pub fn decay_to(&mut self, now: u64, decay: f32) {
+        let mix = self.decay_mix(now, decay);
+        if mix < 1.0 {
+            for weight in &mut self.weights {
+                *weight *= mix;
+            }
+        }
+        self.updated_at = self.updated_at.max(now);
+    }
+
+    /// Record one launch at unix time `now`, bucketed by local hour.
+    pub fn record(&mut self, now: u64, utc_offset: i32, decay: f32) {
+        self.decay_to(now, decay);
+        self.weights[hour_of_week(now, utc_offset)] += 1.0;
+    }
+
+    /// Total (undecayed) weight across all buckets.
+    pub fn total(&self) -> f32 {
+        self.weights.iter().sum()
+    }
+
+    /// Share of launches that fall in the hour buckets covering
+    /// `[now, now + lookahead]`, damped by `prior` pseudo-launches.
+    ///
+    /// Decay scales every bucket equally, so it only affects the result
+    /// through the prior: stale histograms score lower than fresh ones.
+    pub fn window_share(
+        &self,
+        now: u64,
+        utc_offset: i32,
+        lookahead: u64,
+        decay: f32,
+        prior: f32,
+    ) -> f32 {
+        let mix = self.decay_mix(now, decay);
+        let total = self.total() * mix;
+        let denom = total + prior.max(0.0);
+        if denom <= 0.0 {
+            return 0.0;
+        }
+
+        let local = local_secs(now, utc_offset);
+        let first_hour = local.div_euclid(3600);
+        let last_hour = local.saturating_add_unsigned(lookahead).div_euclid(3600);
+        let buckets = (last_hour - first_hour + 1).min(HOURS_PER_WEEK as i64) as usize;
+        let start = hour_of_week(now, utc_offset);
+        let window: f32 = (0..buckets)
+            .map(|i| self.weights[(start + i) % HOURS_PER_WEEK])
+            .sum();
+
+        (window * mix / denom).clamp(0.0, 1.0)
+    }
+
+    fn decay_mix(&self, now: u64, decay: f32) -> f32 {
+        let dt = now.saturating_sub(self.updated_at);
+        if dt == 0 || decay <= 0.0 {
+            return 1.0;
+        }
+        fast_exp_neg(-decay * dt as f32)
+    }
+}
+
+/// Per-exe launch histograms, keyed by ExeId.
+#[derive(Debug, Default)]
+pub struct LaunchHistograms {
+    by_exe: FxHashMap<ExeId, LaunchHistogram>,
+}
+
+impl LaunchHistograms {
+    pub fn record(&mut self, exe_id: ExeId, now: u64, utc_offset: i32, decay: f32) {
+        self.by_exe
+            .entry(exe_id)
+            .or_insert_with(|| LaunchHistogram::new(now))
+            .record(now, utc_offset, decay);
+    }
+
+    pub fn insert(&mut self, exe_id: ExeId, histogram: LaunchHistogram) {
+        self.by_exe.insert(exe_id, histogram);
+    }
+
+    pub fn get(&self, exe_id: ExeId) -> Option<&LaunchHistogram> {
+        self.by_exe.get(&exe_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ExeId, &LaunchHistogram)> {
+        self.by_exe.iter().map(|(id, hist)| (*id, hist))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn hour_of_week_starts_on_monday() {
+        // 1970-01-05 00:00 UTC was a Monday.
+        assert_eq!(hour_of_week(4 * 24 * 3600, 0), 0);
+        // 1970-01-01 00:00 UTC was a Thursday.
+        assert_eq!(hour_of_week(0, 0), 3 * 24);
+        assert_eq!(hour_of_week(4 * 24 * 3600 + 9 * 3600 + 59, 0), 9);
+    }
+
+    #[test]
+    fn hour_of_week_follows_the_utc_offset() {
+        let monday_9am_utc = 4 * 24 * 3600 + 9 * 3600;
+        assert_eq!(hour_of_week(monday_9am_utc, 2 * 3600), 11);
+        assert_eq!(hour_of_week(monday_9am_utc, -5 * 3600), 4);
+        // Sunday 23:00 local, wrapping back to the end of the week.
+        assert_eq!(hour_of_week(4 * 24 * 3600, -3600), HOURS_PER_WEEK - 1);
+        // Before the epoch in local time.
+        assert_eq!(hour_of_week(0, -3600), 3 * 24 - 1);
+    }
+
+    #[test]
+    fn window_share_uses_local_hours() {
+        let monday_9am_utc = 4 * 24 * 3600 + 9 * 3600;
+        let mut hist = LaunchHistogram::new(monday_9am_utc);
+        hist.record(monday_9am_utc, 3600, 0.0);
+
+        // The launch sits in the 10:00 local bucket, so it is only ahead
+        // when the window is read in the same zone.
+        let an_hour_before = monday_9am_utc - 3600;
+        assert!(hist.window_share(an_hour_before, 3600, 3600, 0.0, 0.0) > 0.99);
+        assert_eq!(hist.window_share(an_hour_before, 0, 3600, 0.0, 0.0), 0.0);
+    }
+
+    proptest! {
+        #[test]
+        fn window_share_is_bounded(
+            launches in prop::collection::vec(0u64..10_000_000, 0..50),
+            now in 0u64..20_000_000,
+            utc_offset in -50_400i32..50_400,
+            lookahead in 0u64..1_000_000,
+            decay in 0f32..0.01,
+            prior in 0f32..10.0,
+        ) {
+            let mut hist = LaunchHistogram::new(0);
+            let mut sorted = launches;
+            sorted.sort_unstable();
+            for time in sorted {
+                hist.record(time, utc_offset, decay);
+            }
+
+            let share = hist.window_share(now, utc_offset, lookahead, decay, prior);
+            prop_assert!(!share.is_nan());
+            prop_assert!((0.0..=1.0).contains(&share));
+        }
+    }
+}
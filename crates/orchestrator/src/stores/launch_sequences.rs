@@ -0,0 +1,175 @@
+#![forbid(unsafe_code)]
+
+use crate::domain::ExeId;
+use crate::math::fast_exp_neg;
+use rustc_hash::FxHashMap;
+use std::collections::VecDeque;
+
+/// Contexts whose total weight decays below this are dropped.
+const MIN_CONTEXT_WEIGHT: f32 = 0.01;
+
+/// Exponentially decayed counts of the exes launched after one context.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SequenceContext {
+    pub next: FxHashMap<ExeId, f32>,
+    /// Model time the weights were last decayed to.
+    pub updated_at: u64,
+}
+
+impl SequenceContext {
+    pub fn new(now: u64) -> Self {
+        Self {
+            next: FxHashMap::default(),
+            updated_at: now,
+        }
+    }
+
+    /// Decay all follower weights from `updated_at` to `now`.
+    pub fn decay_to(&mut self, now: u64, decay: f32) {
+        let mix = self.decay_mix(now, decay);
+        if mix < 1.0 {
+            for weight in self.next.values_mut() {
+                *weight *= mix;
+            }
+        }
+        self.updated_at = self.updated_at.max(now);
+    }
+
+    /// Total (undecayed) weight across all followers.
+    pub fn total(&self) -> f32 {
+        self.next.values().sum()
+    }
+
+    /// Decay multiplier from `updated_at` to `now`.
+    pub fn decay_mix(&self, now: u64, decay: f32) -> f32 {
+        let dt = now.saturating_sub(self.updated_at);
+        if dt == 0 || decay <= 0.0 {
+            return 1.0;
+        }
+        fast_exp_neg(-decay * dt as f32)
+    }
+}
+
+/// Variable-order launch sequence model.
+///
+/// Each context is an ordered run of up to `max_order` launches (oldest
+/// first) in which consecutive launches were at most `gap` seconds apart.
+/// A context records which exes were launched directly after it.
+#[derive(Debug, Default)]
+pub struct LaunchSequences {
+    /// Most recent launches with their model time, oldest first.
+    recent: VecDeque<(ExeId, u64)>,
+    contexts: FxHashMap<Vec<ExeId>, SequenceContext>,
+}
+
+impl LaunchSequences {
+    /// Launches leading up to `now`, oldest first: the longest run of
+    /// recent launches each within `gap` of the next, the last within
+    /// `gap` of `now`.
+    pub fn chain(&self, now: u64, gap: u64) -> Vec<ExeId> {
+        let mut start = self.recent.len();
+        let mut next_time = now;
+        for (idx, &(_, time)) in self.recent.iter().enumerate().rev() {
+            if next_time.saturating_sub(time) > gap {
+                break;
+            }
+            start = idx;
+            next_time = time;
+        }
+        self.recent.iter().skip(start).map(|&(id, _)| id).collect()
+    }
+
+    /// Record a launch of `exe_id` at model time `now` as the follower of
+    /// every context (up to `max_order` long) that currently precedes it.
+    pub fn record(&mut self, exe_id: ExeId, now: u64, max_order: usize, gap: u64, decay: f32) {
+        let chain = self.chain(now, gap);
+        for order in 1..=max_order.min(chain.len()) {
+            let context = chain[chain.len() - order..].to_vec();
+            let entry = self
+                .contexts
+                .entry(context)
+                .or_insert_with(|| SequenceContext::new(now));
+            entry.decay_to(now, decay);
+            *entry.next.entry(exe_id).or_insert(0.0) += 1.0;
+        }
+
+        self.recent.push_back((exe_id, now));
+        while self.recent.len() > max_order {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Drop contexts whose decayed weight has become negligible.
+    pub fn prune(&mut self, now: u64, decay: f32) {
+        self.contexts
+            .retain(|_, ctx| ctx.total() * ctx.decay_mix(now, decay) >= MIN_CONTEXT_WEIGHT);
+    }
+
+    pub fn get(&self, context: &[ExeId]) -> Option<&SequenceContext> {
+        self.contexts.get(context)
+    }
+
+    pub fn insert(&mut self, context: Vec<ExeId>, sequence: SequenceContext) {
+        self.contexts.insert(context, sequence);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[ExeId], &SequenceContext)> {
+        self.contexts.iter().map(|(ctx, seq)| (ctx.as_slice(), seq))
+    }
+
+    pub fn len(&self) -> usize {
+        self.contexts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contexts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ExeKey;
+    use crate::stores::ExeStore;
+    use std::path::PathBuf;
+
+    fn ids(n: usize) -> Vec<ExeId> {
+        let mut exes = ExeStore::default();
+        (0..n)
+            .map(|i| exes.ensure(ExeKey::new(PathBuf::from(format!("/usr/bin/app{i}")))))
+            .collect()
+    }
+
+    #[test]
+    fn chain_breaks_at_gap() {
+        let ids = ids(3);
+        let mut seq = LaunchSequences::default();
+        seq.record(ids[0], 0, 3, 60, 0.0);
+        seq.record(ids[1], 100, 3, 60, 0.0);
+        seq.record(ids[2], 130, 3, 60, 0.0);
+
+        assert_eq!(seq.chain(150, 60), vec![ids[1], ids[2]]);
+        assert!(seq.chain(500, 60).is_empty());
+        // ids[0] was too long before ids[1] to form a context for it.
+        assert!(seq.get(&[ids[0]]).is_none());
+        assert_eq!(seq.get(&[ids[1]]).unwrap().next.get(&ids[2]), Some(&1.0));
+    }
+
+    #[test]
+    fn records_every_order_up_to_max() {
+        let ids = ids(3);
+        let mut seq = LaunchSequences::default();
+        seq.record(ids[0], 0, 2, 60, 0.0);
+        seq.record(ids[1], 10, 2, 60, 0.0);
+        seq.record(ids[2], 20, 2, 60, 0.0);
+
+        assert!(seq.get(&[ids[1]]).unwrap().next.contains_key(&ids[2]));
+        assert!(
+            seq.get(&[ids[0], ids[1]])
+                .unwrap()
+                .next
+                .contains_key(&ids[2])
+        );
+        assert_eq!(seq.len(), 3);
+    }
+}
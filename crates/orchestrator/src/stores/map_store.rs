@@ -1,8 +1,8 @@
 #![forbid(unsafe_code)]
 
 use crate::domain::{MapId, MapKey, MapSegment};
-use slotmap::SlotMap;
 use rustc_hash::FxHashMap;
+use slotmap::SlotMap;
 
 #[derive(Debug, Default)]
 pub struct MapStore {
@@ -15,10 +15,19 @@ impl MapStore {
         self.ensure_with_flag(segment).0
     }
 
+    /// The id of `segment`, inserting it if new. An existing segment takes
+    /// the device and inode of `segment` when known, since segments loaded
+    /// from the state have none.
     pub fn ensure_with_flag(&mut self, segment: MapSegment) -> (MapId, bool) {
         let key = segment.key();
-        if let Some(id) = self.by_key.get(&key) {
-            return (*id, false);
+        if let Some(&id) = self.by_key.get(&key) {
+            if (segment.device != 0 || segment.inode != 0)
+                && let Some(existing) = self.maps.get_mut(id)
+            {
+                existing.device = segment.device;
+                existing.inode = segment.inode;
+            }
+            return (id, false);
         }
         let id = self.maps.insert(segment);
         self.by_key.insert(key, id);
@@ -46,3 +55,25 @@ impl MapStore {
         self.maps.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn existing_segments_learn_their_inode() {
+        let mut store = MapStore::default();
+        let (id, _) = store.ensure_with_flag(MapSegment::new("/lib/libfoo.so", 0, 4096, 0));
+
+        let mut seen = MapSegment::new("/lib/libfoo.so", 0, 4096, 5);
+        seen.device = 0x801;
+        seen.inode = 42;
+        assert_eq!(store.ensure_with_flag(seen), (id, false));
+        let map = store.get(id).unwrap();
+        assert_eq!((map.device, map.inode), (0x801, 42));
+
+        // A segment without identity leaves the known one alone.
+        store.ensure(MapSegment::new("/lib/libfoo.so", 0, 4096, 9));
+        assert_eq!(store.get(id).unwrap().inode, 42);
+    }
+}
@@ -2,7 +2,7 @@
 
 use crate::domain::{ExeId, MarkovState};
 use crate::math::fast_exp_neg;
-use crate::stores::EdgeKey;
+use crate::stores::{ColdEdge, EdgeKey};
 use half::f16;
 use half::slice::HalfFloatSliceExt;
 use rustc_hash::{FxHashMap, FxHashSet};
@@ -48,8 +48,8 @@ impl EdgeRef<'_> {
     #[inline]
     pub fn transition_prob_f32(&self) -> [[f32; 4]; 4] {
         let mut out = [[0.0f32; 4]; 4];
-        for i in 0..4 {
-            self.transition_prob[i].convert_to_f32_slice(&mut out[i]);
+        for (row, out_row) in self.transition_prob.iter().zip(out.iter_mut()) {
+            row.convert_to_f32_slice(out_row);
         }
         out
     }
@@ -78,8 +78,8 @@ impl EdgeRefMut<'_> {
     #[inline]
     pub fn transition_prob_f32(&self) -> [[f32; 4]; 4] {
         let mut out = [[0.0f32; 4]; 4];
-        for i in 0..4 {
-            self.transition_prob[i].convert_to_f32_slice(&mut out[i]);
+        for (row, out_row) in self.transition_prob.iter().zip(out.iter_mut()) {
+            row.convert_to_f32_slice(out_row);
         }
         out
     }
@@ -93,8 +93,8 @@ impl EdgeRefMut<'_> {
     /// Batch-convert f32 values to f16 and store in `transition_prob`.
     #[inline]
     pub fn set_transition_prob_f32(&mut self, values: [[f32; 4]; 4]) {
-        for i in 0..4 {
-            self.transition_prob[i].convert_from_f32_slice(&values[i]);
+        for (row, value_row) in self.transition_prob.iter_mut().zip(values.iter()) {
+            row.convert_from_f32_slice(value_row);
         }
     }
 
@@ -131,9 +131,9 @@ impl EdgeRefMut<'_> {
             let one_minus_mix = 1.0 - mix;
             let mut row_f32 = [0.0f32; 4];
             self.transition_prob[i].convert_to_f32_slice(&mut row_f32);
-            for j in 0..4 {
+            for (j, value) in row_f32.iter_mut().enumerate() {
                 let target = is_active_row * (j == new_ix) as u8 as f32;
-                row_f32[j] = mix * row_f32[j] + one_minus_mix * target;
+                *value = mix * *value + one_minus_mix * target;
             }
             self.transition_prob[i].convert_from_f32_slice(&row_f32);
         }
@@ -162,6 +162,10 @@ impl MarkovGraph {
         true
     }
 
+    pub fn contains(&self, key: EdgeKey) -> bool {
+        self.key_to_index.contains_key(&key)
+    }
+
     pub fn get_mut(&mut self, key: EdgeKey) -> Option<EdgeRefMut<'_>> {
         let idx = *self.key_to_index.get(&key)?;
         Some(EdgeRefMut {
@@ -225,16 +229,28 @@ impl MarkovGraph {
             })
     }
 
-    pub fn prune_inactive(&mut self, active: &FxHashSet<ExeId>) {
+    /// Remove edges not between two `active` exes, returning their learned
+    /// statistics.
+    pub fn drain_inactive(&mut self, active: &FxHashSet<ExeId>) -> Vec<(EdgeKey, ColdEdge)> {
+        let mut drained = Vec::new();
         let mut i = 0;
         while i < self.keys.len() {
             let key = self.keys[i];
             if active.contains(&key.0) && active.contains(&key.1) {
                 i += 1;
             } else {
+                drained.push((
+                    key,
+                    ColdEdge {
+                        time_to_leave: self.time_to_leave[i],
+                        transition_prob: self.transition_prob[i],
+                        both_running_time: self.both_running_times[i],
+                    },
+                ));
                 self.swap_remove(i);
             }
         }
+        drained
     }
 
     fn swap_remove(&mut self, idx: usize) {
@@ -1,16 +1,22 @@
 #![forbid(unsafe_code)]
 
 mod active_set;
+mod cold_edges;
 mod edge_key;
 mod exe_map_index;
 mod exe_store;
+mod launch_histograms;
+mod launch_sequences;
 mod map_store;
 mod markov_graph;
 
 pub use active_set::ActiveSet;
+pub use cold_edges::{ColdEdge, ColdEdges};
 pub use edge_key::EdgeKey;
 pub use exe_map_index::ExeMapIndex;
 pub use exe_store::ExeStore;
+pub use launch_histograms::{HOURS_PER_WEEK, LaunchHistogram, LaunchHistograms, hour_of_week};
+pub use launch_sequences::{LaunchSequences, SequenceContext};
 pub use map_store::MapStore;
 pub use markov_graph::{EdgeRef, EdgeRefMut, MarkovGraph};
 
@@ -24,8 +30,20 @@ pub struct Stores {
     pub exe_maps: ExeMapIndex,
     pub markov: MarkovGraph,
     pub active: ActiveSet,
+    /// Edges retired from `markov`, when cold edges are enabled.
+    pub cold: ColdEdges,
+    pub launches: LaunchHistograms,
+    pub sequences: LaunchSequences,
     pub model_time: u64,
     pub last_accounting_time: u64,
+    /// Unix time (seconds) of the most recent observation, if known.
+    pub wall_time: Option<u64>,
+    /// Offset of local time from UTC (seconds east) at `wall_time`.
+    pub utc_offset: i32,
+    /// Whether exe running flags come from a scan made by this process.
+    /// False after construction or load, when every running exe would
+    /// otherwise look freshly launched.
+    pub running_observed: bool,
 }
 
 impl Stores {
@@ -45,8 +63,33 @@ impl Stores {
         self.exe_maps.attach(exe_id, map_id);
     }
 
+    /// Create the edge between `a` and `b` if missing, reviving its cold
+    /// statistics if it has any. Returns whether the edge was created.
     pub fn ensure_markov_edge(&mut self, a: ExeId, b: ExeId, now: u64, state: MarkovState) -> bool {
-        self.markov.ensure_edge(a, b, now, state)
+        if !self.markov.ensure_edge(a, b, now, state) {
+            return false;
+        }
+        let key = EdgeKey::new(a, b);
+        if let Some(cold) = self.cold.take(key)
+            && let Some(edge) = self.markov.get_mut(key)
+        {
+            *edge.time_to_leave = cold.time_to_leave;
+            *edge.transition_prob = cold.transition_prob;
+            *edge.both_running_time = cold.both_running_time;
+        }
+        true
+    }
+
+    /// Drop edges that are no longer between two active exes, or move them
+    /// to the bounded cold store when `keep_cold` is set.
+    pub fn retire_inactive_edges(&mut self, keep_cold: bool) {
+        let active = self.active.exes();
+        for (key, edge) in self.markov.drain_inactive(&active) {
+            if keep_cold {
+                self.cold.insert(key, edge);
+            }
+        }
+        self.cold.trim();
     }
 
     pub fn remove_map_by_key(&mut self, key: &crate::domain::MapKey) {
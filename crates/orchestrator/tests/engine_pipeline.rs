@@ -1,20 +1,24 @@
 #![forbid(unsafe_code)]
 
 use config::{Config, MemoryPolicy, SortStrategy};
+use orchestrator::StateRepository;
 use orchestrator::clock::SystemClock;
 use orchestrator::domain::{MapSegment, MemStat};
 use orchestrator::observation::{
     DefaultAdmissionPolicy, DefaultModelUpdater, Observation, ObservationEvent, Scanner,
 };
-use orchestrator::persistence::{NoopRepository, SqliteRepository};
+use orchestrator::persistence::{NoopRepository, SqliteRepository, StoresSnapshot};
 use orchestrator::prediction::{Prediction, Predictor};
 use orchestrator::prefetch::{
     GreedyPrefetchPlanner, NoopPrefetcher, PrefetchPlan, PrefetchReport, Prefetcher,
 };
 use orchestrator::{PreloadEngine, Services};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tempfile::tempdir;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug)]
 struct StaticScanner {
@@ -37,6 +41,10 @@ struct PathScorePredictor {
 }
 
 impl Predictor for PathScorePredictor {
+    fn score_exes(&self, _stores: &orchestrator::stores::Stores) -> Prediction {
+        Prediction::default()
+    }
+
     fn predict(&self, stores: &orchestrator::stores::Stores) -> Prediction {
         let mut prediction = Prediction::default();
         for (map_id, map) in stores.maps.iter() {
@@ -46,7 +54,9 @@ impl Predictor for PathScorePredictor {
                 .find(|(path, _)| path == &map.path)
                 .map(|(_, score)| *score)
             {
-                prediction.map_scores.insert(map_id, half::f16::from_f32(score));
+                prediction
+                    .map_scores
+                    .insert(map_id, half::f16::from_f32(score));
             }
         }
         prediction
@@ -71,12 +81,38 @@ impl Prefetcher for SpyPrefetcher {
         &self,
         plan: &PrefetchPlan,
         _stores: &orchestrator::stores::Stores,
+        _cancel: &CancellationToken,
     ) -> PrefetchReport {
         self.plans.lock().unwrap().push(plan.clone());
         PrefetchReport {
             num_maps: plan.maps.len(),
             total_bytes: plan.total_bytes,
-            failures: Vec::new(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Prefetcher whose plan never finishes on its own; it only returns once
+/// cancelled.
+#[derive(Debug, Default)]
+struct StallingPrefetcher {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl Prefetcher for StallingPrefetcher {
+    async fn execute(
+        &self,
+        plan: &PrefetchPlan,
+        _stores: &orchestrator::stores::Stores,
+        cancel: &CancellationToken,
+    ) -> PrefetchReport {
+        cancel.cancelled().await;
+        self.cancelled.store(true, Ordering::SeqCst);
+        PrefetchReport {
+            skipped: plan.maps.len(),
+            cancelled: true,
+            ..Default::default()
         }
     }
 }
@@ -112,6 +148,9 @@ async fn engine_tick_flows_through_pipeline() {
                 cached: 0,
                 pagein: 0,
                 pageout: 0,
+                memory_pressure: None,
+                io_pressure: None,
+                cgroup: None,
             },
         },
         ObservationEvent::ObsEnd {
@@ -126,10 +165,14 @@ async fn engine_tick_flows_through_pipeline() {
     config.model.memory = MemoryPolicy {
         memtotal: 0,
         memavailable: 100,
+        ..Default::default()
     };
     config.system.exeprefix = vec!["!/".into(), "/test/".into()];
     config.system.mapprefix = vec!["!/".into(), "/test/".into()];
     config.system.sortstrategy = SortStrategy::None;
+    let dir = tempdir().unwrap();
+    let textfile = dir.path().join("preload.prom");
+    config.metrics.textfile = Some(textfile.clone());
 
     let spy = SpyPrefetcher::default();
     let spy_handle = spy.plans.clone();
@@ -162,6 +205,14 @@ async fn engine_tick_flows_through_pipeline() {
     let second_path = stores.maps.get(map_ids[1]).unwrap().path.clone();
     assert_eq!(first_path, map_a);
     assert_eq!(second_path, map_b);
+
+    let metrics = engine.metrics();
+    assert_eq!(metrics.ticks, 1);
+    assert_eq!((metrics.exes, metrics.maps), (1, 2));
+    assert_eq!(metrics.plan_bytes, 2048 + 1024);
+    let text = std::fs::read_to_string(&textfile).unwrap();
+    assert!(text.contains("preload_ticks_total 1\n"));
+    assert!(text.contains("preload_plan_bytes 3072\n"));
 }
 
 #[tokio::test]
@@ -253,3 +304,152 @@ async fn engine_persists_and_loads_state() {
     let expected: std::collections::HashSet<Arc<Path>> = [map_a, map_b].into_iter().collect();
     assert_eq!(map_paths, expected);
 }
+
+#[tokio::test]
+async fn shutdown_cancels_in_flight_prefetch() {
+    let mut config = Config::default();
+    config.system.dopredict = false;
+    config.persistence.save_on_shutdown = false;
+
+    let prefetcher = StallingPrefetcher::default();
+    let cancelled = prefetcher.cancelled.clone();
+
+    let services = Services {
+        scanner: Box::new(StaticScanner {
+            observation: Vec::new(),
+        }),
+        admission: Box::new(DefaultAdmissionPolicy::new(&config)),
+        updater: Box::new(DefaultModelUpdater::new(&config)),
+        predictor: Box::new(PathScorePredictor { scores: Vec::new() }),
+        planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+        prefetcher: Box::new(prefetcher),
+        repo: Box::new(NoopRepository),
+        clock: Box::new(SystemClock),
+    };
+
+    let mut engine = PreloadEngine::new(config, services).await.unwrap();
+    let cancel = CancellationToken::new();
+    let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        trigger.cancel();
+    });
+
+    tokio::time::timeout(Duration::from_secs(5), engine.run_until(cancel, control_rx))
+        .await
+        .expect("run_until should return promptly after shutdown")
+        .unwrap();
+    assert!(cancelled.load(Ordering::SeqCst));
+    assert_eq!(engine.stores().model_time, 20);
+}
+
+#[tokio::test]
+async fn tick_report_times_phases_and_autosaves() {
+    let exe_path: Arc<Path> = Arc::from(Path::new("/test/exe"));
+    let observation = vec![
+        ObservationEvent::ObsBegin {
+            time: 0,
+            scan_id: 1,
+        },
+        ObservationEvent::ExeSeen {
+            path: exe_path.clone(),
+            pid: 1234,
+        },
+        ObservationEvent::MapSeen {
+            exe_path: exe_path.clone(),
+            map: MapSegment::from_arc(Arc::from(Path::new("/test/map")), 0, 4096, 0),
+        },
+        ObservationEvent::ObsEnd {
+            time: 0,
+            scan_id: 1,
+            warnings: Vec::new(),
+        },
+    ];
+
+    let mut config = Config::default();
+    config.model.minsize = 1;
+    config.system.exeprefix = vec!["!/".into(), "/test/".into()];
+    config.system.mapprefix = vec!["!/".into(), "/test/".into()];
+    config.system.dopredict = false;
+    config.persistence.autosave_interval = Some(Duration::from_nanos(1));
+
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("state.db");
+    let repo = SqliteRepository::new(db_path.clone()).await.unwrap();
+    let services = Services {
+        scanner: Box::new(StaticScanner { observation }),
+        admission: Box::new(DefaultAdmissionPolicy::new(&config)),
+        updater: Box::new(DefaultModelUpdater::new(&config)),
+        predictor: Box::new(PathScorePredictor { scores: Vec::new() }),
+        planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+        prefetcher: Box::new(NoopPrefetcher),
+        repo: Box::new(repo),
+        clock: Box::new(SystemClock),
+    };
+
+    let mut engine = PreloadEngine::new(config, services).await.unwrap();
+    let first = engine.tick().await.unwrap();
+    assert!(first.timings.scan > Duration::ZERO);
+    assert!(first.timings.save > Duration::ZERO);
+    assert_eq!(first.timings.fanotify, Duration::ZERO);
+    assert!(first.resources.rss_bytes > 0);
+
+    let second = engine.tick().await.unwrap();
+    let metrics = engine.metrics();
+    assert_eq!(metrics.ticks, 2);
+    assert_eq!(
+        metrics.phase_time.save,
+        first.timings.save + second.timings.save
+    );
+    assert_eq!(
+        metrics.user_cpu,
+        first.resources.user_cpu + second.resources.user_cpu
+    );
+    assert_eq!(metrics.rss_bytes, second.resources.rss_bytes);
+
+    // The autosave reached the database without an explicit save.
+    let repo = SqliteRepository::new(db_path).await.unwrap();
+    let snapshot = repo.load().await.unwrap();
+    assert_eq!(snapshot.state.exes.len(), 1);
+}
+
+/// Loads an empty model and fails every save.
+#[derive(Debug)]
+struct ReadOnlyRepo;
+
+#[async_trait::async_trait]
+impl StateRepository for ReadOnlyRepo {
+    async fn load(&self) -> Result<StoresSnapshot, orchestrator::error::Error> {
+        NoopRepository.load().await
+    }
+
+    async fn save(&self, _snapshot: &StoresSnapshot) -> Result<(), orchestrator::error::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::ReadOnlyFilesystem).into())
+    }
+}
+
+#[tokio::test]
+async fn failed_autosave_keeps_the_tick_report() {
+    let mut config = Config::default();
+    config.system.dopredict = false;
+    config.persistence.autosave_interval = Some(Duration::from_nanos(1));
+    let services = Services {
+        scanner: Box::new(StaticScanner {
+            observation: Vec::new(),
+        }),
+        admission: Box::new(DefaultAdmissionPolicy::new(&config)),
+        updater: Box::new(DefaultModelUpdater::new(&config)),
+        predictor: Box::new(PathScorePredictor { scores: Vec::new() }),
+        planner: Box::new(GreedyPrefetchPlanner::new(&config)),
+        prefetcher: Box::new(NoopPrefetcher),
+        repo: Box::new(ReadOnlyRepo),
+        clock: Box::new(SystemClock),
+    };
+
+    let mut engine = PreloadEngine::new(config, services).await.unwrap();
+    engine.tick().await.unwrap();
+    engine.tick().await.unwrap();
+    assert_eq!(engine.metrics().ticks, 2);
+}
@@ -4,11 +4,12 @@ use config::Config;
 use orchestrator::{
     ModelUpdater,
     domain::MapSegment,
-    observation::{DefaultAdmissionPolicy, DefaultModelUpdater, ObservationEvent},
-    stores::Stores,
+    observation::{DefaultAdmissionPolicy, DefaultModelUpdater, Observation, ObservationEvent},
+    stores::{Stores, hour_of_week},
 };
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 #[test]
 fn admits_exe_and_maps() {
@@ -18,7 +19,12 @@ fn admits_exe_and_maps() {
     let mut stores = Stores::default();
 
     let exe_path: Arc<Path> = Arc::from(Path::new("/usr/bin/app"));
-    let map = MapSegment::from_arc(Arc::from(Path::new("/usr/lib/libfoo.so")), 0, config.model.minsize, 0);
+    let map = MapSegment::from_arc(
+        Arc::from(Path::new("/usr/lib/libfoo.so")),
+        0,
+        config.model.minsize,
+        0,
+    );
 
     let observation = vec![
         ObservationEvent::ObsBegin {
@@ -47,3 +53,132 @@ fn admits_exe_and_maps() {
     assert_eq!(stores.exes.iter().count(), 1);
     assert_eq!(stores.maps.iter().count(), 1);
 }
+
+#[test]
+fn records_launches_after_first_scan() {
+    let mut config = Config::default();
+    config.model.time_of_day.utc_offset = Some(2 * 3600);
+    let policy = DefaultAdmissionPolicy::new(&config);
+    let mut updater = DefaultModelUpdater::new(&config);
+    let mut stores = Stores::default();
+
+    let exe_path: Arc<Path> = Arc::from(Path::new("/usr/bin/app"));
+    let map = MapSegment::from_arc(
+        Arc::from(Path::new("/usr/lib/libfoo.so")),
+        0,
+        config.model.minsize,
+        0,
+    );
+    let wall = 1_700_000_000;
+
+    let scan = |time: u64, running: bool| -> Observation {
+        let mut events = vec![
+            ObservationEvent::ObsBegin {
+                time,
+                scan_id: time,
+            },
+            ObservationEvent::WallTime {
+                time: SystemTime::UNIX_EPOCH + Duration::from_secs(wall + time),
+            },
+        ];
+        if running {
+            events.push(ObservationEvent::ExeSeen {
+                path: exe_path.clone(),
+                pid: 1,
+            });
+            events.push(ObservationEvent::MapSeen {
+                exe_path: exe_path.clone(),
+                map: map.clone(),
+            });
+        }
+        events.push(ObservationEvent::ObsEnd {
+            time,
+            scan_id: time,
+            warnings: Vec::new(),
+        });
+        events
+    };
+
+    // Already running at startup: not a launch.
+    updater.apply(&mut stores, &scan(0, true), &policy).unwrap();
+    let exe_id = stores.exes.iter().next().map(|(id, _)| id).unwrap();
+    assert!(stores.launches.get(exe_id).is_none());
+    assert_eq!(stores.wall_time, Some(wall));
+    assert_eq!(stores.utc_offset, 2 * 3600);
+
+    updater
+        .apply(&mut stores, &scan(20, false), &policy)
+        .unwrap();
+    let delta = updater
+        .apply(&mut stores, &scan(40, true), &policy)
+        .unwrap();
+    assert_eq!(delta.running_now.len(), 1);
+
+    let hist = stores.launches.get(exe_id).expect("launch recorded");
+    assert!((hist.weights[hour_of_week(wall + 40, 2 * 3600)] - 1.0).abs() < 1e-6);
+    assert!((hist.total() - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn launches_in_one_scan_are_ordered_by_pid() {
+    let config = Config::default();
+    let policy = DefaultAdmissionPolicy::new(&config);
+    let mut updater = DefaultModelUpdater::new(&config);
+    let mut stores = Stores::default();
+
+    let first: Arc<Path> = Arc::from(Path::new("/usr/bin/first"));
+    let second: Arc<Path> = Arc::from(Path::new("/usr/bin/second"));
+    let scan = |time: u64, running: &[(&Arc<Path>, u32)]| -> Observation {
+        let mut events = vec![ObservationEvent::ObsBegin {
+            time,
+            scan_id: time,
+        }];
+        for &(path, pid) in running {
+            events.push(ObservationEvent::ExeSeen {
+                path: path.clone(),
+                pid,
+            });
+            events.push(ObservationEvent::MapSeen {
+                exe_path: path.clone(),
+                map: MapSegment::from_arc(
+                    Arc::from(Path::new("/usr/lib/libfoo.so")),
+                    0,
+                    config.model.minsize,
+                    time,
+                ),
+            });
+        }
+        events.push(ObservationEvent::ObsEnd {
+            time,
+            scan_id: time,
+            warnings: Vec::new(),
+        });
+        events
+    };
+
+    updater.apply(&mut stores, &scan(0, &[]), &policy).unwrap();
+    // Listed out of start order; the lower pid started first.
+    updater
+        .apply(
+            &mut stores,
+            &scan(20, &[(&second, 200), (&first, 100)]),
+            &policy,
+        )
+        .unwrap();
+
+    let id = |path: &Arc<Path>| {
+        stores
+            .exes
+            .iter()
+            .find(|(_, exe)| exe.key.path() == &**path)
+            .map(|(id, _)| id)
+            .unwrap()
+    };
+    let (first, second) = (id(&first), id(&second));
+    let context = stores
+        .sequences
+        .get(&[first])
+        .expect("first precedes second");
+    assert!(context.next.contains_key(&second));
+    assert!(stores.sequences.get(&[second]).is_none());
+}
@@ -1,8 +1,12 @@
 #![forbid(unsafe_code)]
 
-use config::Config;
+use config::{CombineMode, Config};
+use orchestrator::domain::ExeId;
 use orchestrator::domain::{ExeKey, MapSegment, MarkovState};
-use orchestrator::prediction::{MarkovPredictor, Predictor};
+use orchestrator::prediction::{
+    CompositePredictor, FrequencyPredictor, MarkovPredictor, Prediction, Predictor,
+    SequencePredictor, TimeOfDayPredictor,
+};
 use orchestrator::stores::{EdgeKey, Stores};
 use std::path::PathBuf;
 use std::time::Duration;
@@ -26,7 +30,8 @@ fn predictor_scores_non_running_exe_from_edge() {
     let edge_key = EdgeKey::new(exe_a, exe_b);
     let edge = stores.markov.get_mut(edge_key).unwrap();
     edge.time_to_leave[MarkovState::BOnly.index()] = half::f16::from_f32(1.0);
-    edge.transition_prob[MarkovState::BOnly.index()][MarkovState::AOnly.index()] = half::f16::from_f32(1.0);
+    edge.transition_prob[MarkovState::BOnly.index()][MarkovState::AOnly.index()] =
+        half::f16::from_f32(1.0);
 
     let map_id = stores.ensure_map(MapSegment::new("/usr/lib/libfoo.so", 0, 2048, now));
     stores.attach_map(exe_a, map_id);
@@ -41,6 +46,267 @@ fn predictor_scores_non_running_exe_from_edge() {
     assert!((a_score - expected).abs() < 1e-3);
     assert_eq!(b_score, 0.0);
 
-    let map_score = prediction.map_scores.get(&map_id).copied().unwrap().to_f32();
+    let map_score = prediction
+        .map_scores
+        .get(&map_id)
+        .copied()
+        .unwrap()
+        .to_f32();
     assert!((map_score - a_score).abs() < 1e-3);
 }
+
+#[test]
+fn longer_horizon_raises_score_and_reports_eta() {
+    let mut config = Config::default();
+    config.model.use_correlation = false;
+    config.model.cycle = Duration::from_secs(10);
+
+    let mut stores = Stores::default();
+    let exe_a = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/a")));
+    let exe_b = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/b")));
+    stores.model_time = 10;
+    stores.exes.get_mut(exe_b).unwrap().running = true;
+
+    stores.ensure_markov_edge(exe_a, exe_b, 10, MarkovState::BOnly);
+    let edge = stores.markov.get_mut(EdgeKey::new(exe_a, exe_b)).unwrap();
+    edge.time_to_leave[MarkovState::BOnly.index()] = half::f16::from_f32(60.0);
+    edge.transition_prob[MarkovState::BOnly.index()][MarkovState::Both.index()] =
+        half::f16::from_f32(1.0);
+
+    let map_id = stores.ensure_map(MapSegment::new("/usr/lib/liba.so", 0, 2048, 10));
+    stores.attach_map(exe_a, map_id);
+
+    let one_cycle = MarkovPredictor::new(&config).predict(&stores);
+    config.model.horizon_cycles = 6;
+    let six_cycles = MarkovPredictor::new(&config).predict(&stores);
+
+    let short = one_cycle.exe_scores.get(&exe_a).copied().unwrap().to_f32();
+    let long = six_cycles.exe_scores.get(&exe_a).copied().unwrap().to_f32();
+    assert!(
+        (short - (1.0 - (-10.0f32 / 60.0).exp())).abs() < 1e-2,
+        "short {short}"
+    );
+    assert!((long - (1.0 - (-1.0f32).exp())).abs() < 1e-2, "long {long}");
+
+    let eta = *six_cycles.exe_eta.get(&exe_a).unwrap();
+    assert!(eta > 0.0 && eta < 30.0, "eta {eta}");
+    assert_eq!(six_cycles.map_eta.get(&map_id).copied(), Some(eta));
+    assert!(!six_cycles.exe_eta.contains_key(&exe_b));
+
+    config.model.horizon = Some(Duration::from_secs(10));
+    let overridden = MarkovPredictor::new(&config).predict(&stores);
+    let score = overridden.exe_scores.get(&exe_a).copied().unwrap().to_f32();
+    assert!((score - short).abs() < 1e-3);
+}
+
+#[test]
+fn time_of_day_predictor_boosts_exe_launched_at_this_hour() {
+    let config = Config::default();
+    let decay = config.model.time_of_day.decay_factor();
+
+    // Monday 1970-01-05 09:15 UTC, and the same time one week later.
+    let monday_nine = 4 * 24 * 3600 + 9 * 3600 + 15 * 60;
+    let week = 7 * 24 * 3600;
+
+    let mut stores = Stores::default();
+    let ide = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/ide")));
+    let player = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/player")));
+    for i in 0..4 {
+        stores
+            .launches
+            .record(ide, monday_nine + i * week, 0, decay);
+        stores
+            .launches
+            .record(player, monday_nine + 12 * 3600 + i * week, 0, decay);
+    }
+    stores.wall_time = Some(monday_nine + 4 * week - 10 * 60);
+
+    let map_id = stores.ensure_map(MapSegment::new("/usr/lib/libide.so", 0, 2048, 0));
+    stores.attach_map(ide, map_id);
+
+    let prediction = TimeOfDayPredictor::new(&config).predict(&stores);
+
+    let ide_score = prediction.exe_scores.get(&ide).copied().unwrap().to_f32();
+    let player_score = prediction
+        .exe_scores
+        .get(&player)
+        .copied()
+        .unwrap()
+        .to_f32();
+    assert!(ide_score > 0.4, "ide score {ide_score}");
+    assert_eq!(player_score, 0.0);
+
+    let map_score = prediction
+        .map_scores
+        .get(&map_id)
+        .copied()
+        .unwrap()
+        .to_f32();
+    assert!((map_score - ide_score).abs() < 1e-3);
+
+    stores.exes.get_mut(ide).unwrap().running = true;
+    let prediction = TimeOfDayPredictor::new(&config).predict(&stores);
+    assert_eq!(
+        prediction.exe_scores.get(&ide).copied().unwrap().to_f32(),
+        0.0
+    );
+}
+
+#[test]
+fn sequence_predictor_scores_exe_that_follows_recent_launch() {
+    let config = Config::default();
+    let settings = &config.model.sequence;
+    let (order, gap, decay) = (
+        settings.max_order,
+        settings.gap.as_secs(),
+        settings.decay_factor(),
+    );
+
+    let mut stores = Stores::default();
+    let term = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/term")));
+    let editor = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/editor")));
+    let browser = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/browser")));
+
+    // Each session: the terminal, then the editor 20s later; the browser
+    // comes much later and never follows the terminal.
+    for session in 0..5 {
+        let start = session * 3600;
+        stores.sequences.record(term, start, order, gap, decay);
+        stores
+            .sequences
+            .record(editor, start + 20, order, gap, decay);
+        stores
+            .sequences
+            .record(browser, start + 1800, order, gap, decay);
+    }
+    let now = 5 * 3600;
+    stores.sequences.record(term, now, order, gap, decay);
+    stores.model_time = now;
+    stores.exes.get_mut(term).unwrap().running = true;
+
+    let map_id = stores.ensure_map(MapSegment::new("/usr/lib/libeditor.so", 0, 2048, 0));
+    stores.attach_map(editor, map_id);
+
+    let predictor = SequencePredictor::new(&config);
+    let prediction = predictor.predict(&stores);
+
+    let editor_score = prediction
+        .exe_scores
+        .get(&editor)
+        .copied()
+        .unwrap()
+        .to_f32();
+    let browser_score = prediction
+        .exe_scores
+        .get(&browser)
+        .copied()
+        .unwrap()
+        .to_f32();
+    assert!(editor_score > 0.6, "editor score {editor_score}");
+    assert_eq!(browser_score, 0.0);
+    let map_score = prediction
+        .map_scores
+        .get(&map_id)
+        .copied()
+        .unwrap()
+        .to_f32();
+    assert!((map_score - editor_score).abs() < 1e-3);
+
+    // Once the follow-up window has passed nothing is predicted.
+    stores.model_time = now + gap + 1;
+    let prediction = predictor.predict(&stores);
+    assert_eq!(
+        prediction
+            .exe_scores
+            .get(&editor)
+            .copied()
+            .unwrap()
+            .to_f32(),
+        0.0
+    );
+}
+
+struct Fixed(Vec<(ExeId, f32)>);
+
+impl Predictor for Fixed {
+    fn score_exes(&self, _stores: &Stores) -> Prediction {
+        let mut prediction = Prediction::default();
+        for &(exe_id, score) in &self.0 {
+            prediction
+                .exe_scores
+                .insert(exe_id, half::f16::from_f32(score));
+        }
+        prediction
+    }
+}
+
+#[test]
+fn composite_predictor_combines_component_scores() {
+    let mut stores = Stores::default();
+    let a = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/a")));
+    let b = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/b")));
+    let map_id = stores.ensure_map(MapSegment::new("/usr/lib/liba.so", 0, 2048, 0));
+    stores.attach_map(a, map_id);
+
+    let build = |combine| {
+        let mut composite = CompositePredictor::with_combine(combine);
+        composite.push("first", 1.0, Box::new(Fixed(vec![(a, 0.5), (b, 0.0)])));
+        composite.push("second", 3.0, Box::new(Fixed(vec![(a, 1.0)])));
+        composite.push("disabled", 0.0, Box::new(Fixed(vec![(b, 1.0)])));
+        composite
+    };
+
+    let prediction = build(CombineMode::Weighted).predict(&stores);
+    let a_score = prediction.exe_scores.get(&a).copied().unwrap().to_f32();
+    let b_score = prediction.exe_scores.get(&b).copied().unwrap().to_f32();
+    assert!((a_score - 0.875).abs() < 1e-3, "a score {a_score}");
+    assert_eq!(b_score, 0.0);
+    let map_score = prediction
+        .map_scores
+        .get(&map_id)
+        .copied()
+        .unwrap()
+        .to_f32();
+    assert!((map_score - a_score).abs() < 1e-3);
+
+    assert_eq!(prediction.components.len(), 2);
+    assert_eq!(
+        prediction.explain(a),
+        vec![("first", 1.0, 0.5), ("second", 3.0, 1.0)]
+    );
+
+    // Noisy-OR clamps weights to 1: 1 - (1 - 0.5) * (1 - 1.0).
+    let prediction = build(CombineMode::NoisyOr).predict(&stores);
+    let a_score = prediction.exe_scores.get(&a).copied().unwrap().to_f32();
+    assert!((a_score - 1.0).abs() < 1e-3, "a score {a_score}");
+}
+
+#[test]
+fn frequency_predictor_prefers_recently_used_exes() {
+    let config = Config::default();
+    let day = 24 * 3600;
+
+    let mut stores = Stores::default();
+    let recent = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/recent")));
+    let stale = stores.ensure_exe(ExeKey::new(PathBuf::from("/usr/bin/stale")));
+    stores.model_time = 10 * day;
+    for (exe_id, seen) in [(recent, 10 * day - 60), (stale, 3 * day)] {
+        let exe = stores.exes.get_mut(exe_id).unwrap();
+        exe.total_running_time = 2 * day;
+        exe.last_seen_time = Some(seen);
+    }
+
+    let prediction = FrequencyPredictor::new(&config).predict(&stores);
+    let recent_score = prediction
+        .exe_scores
+        .get(&recent)
+        .copied()
+        .unwrap()
+        .to_f32();
+    let stale_score = prediction.exe_scores.get(&stale).copied().unwrap().to_f32();
+    assert!(
+        (recent_score - 0.2).abs() < 1e-2,
+        "recent score {recent_score}"
+    );
+    assert!(stale_score < 0.01, "stale score {stale_score}");
+}
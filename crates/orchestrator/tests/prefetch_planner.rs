@@ -4,8 +4,10 @@ use config::{Config, MemoryPolicy, SortStrategy};
 use half::f16;
 use orchestrator::domain::{MapSegment, MemStat};
 use orchestrator::prediction::Prediction;
-use orchestrator::prefetch::GreedyPrefetchPlanner;
-use orchestrator::prefetch::PrefetchPlanner;
+use orchestrator::prefetch::{
+    Cooldown, DensityPrefetchPlanner, GreedyPrefetchPlanner, PlanPreview, PressureScale,
+};
+use orchestrator::prefetch::{PrefetchPlan, PrefetchPlanner, PrefetchReport};
 use orchestrator::stores::Stores;
 use std::os::linux::fs::MetadataExt;
 use tempfile::tempdir;
@@ -23,12 +25,28 @@ fn segment_with_meta(
     seg
 }
 
+/// Plan the way the engine does: residency probes first, then the plan.
+fn probe_and_plan(
+    planner: &dyn PrefetchPlanner,
+    prediction: &Prediction,
+    stores: &Stores,
+    mem: &MemStat,
+    cooldown: &Cooldown,
+) -> PrefetchPlan {
+    let pressure = PressureScale::default();
+    if let Some(probes) = planner.residency_probes(prediction, stores, mem, pressure) {
+        probes();
+    }
+    planner.plan(prediction, stores, mem, pressure, cooldown)
+}
+
 #[test]
 fn planner_selects_maps_within_budget() {
     let mut config = Config::default();
     config.model.memory = MemoryPolicy {
         memtotal: 0,
         memavailable: 100,
+        ..Default::default()
     };
     config.system.sortstrategy = SortStrategy::None;
 
@@ -51,9 +69,18 @@ fn planner_selects_maps_within_budget() {
         cached: 0,
         pagein: 0,
         pageout: 0,
+        memory_pressure: None,
+        io_pressure: None,
+        cgroup: None,
     };
 
-    let plan = planner.plan(&prediction, &stores, &mem);
+    let plan = planner.plan(
+        &prediction,
+        &stores,
+        &mem,
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
 
     assert_eq!(plan.maps.len(), 2);
     assert!(plan.maps.contains(&map_a));
@@ -77,6 +104,7 @@ fn planner_sorts_by_block_with_score_tiebreak() {
     config.model.memory = MemoryPolicy {
         memtotal: 0,
         memavailable: 100,
+        ..Default::default()
     };
     config.system.sortstrategy = SortStrategy::Block;
 
@@ -99,9 +127,18 @@ fn planner_sorts_by_block_with_score_tiebreak() {
         cached: 0,
         pagein: 0,
         pageout: 0,
+        memory_pressure: None,
+        io_pressure: None,
+        cgroup: None,
     };
 
-    let plan = planner.plan(&prediction, &stores, &mem);
+    let plan = planner.plan(
+        &prediction,
+        &stores,
+        &mem,
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
 
     assert_eq!(plan.maps, vec![map_b, map_c, map_a]);
 }
@@ -125,6 +162,7 @@ fn planner_sorts_by_inode_with_score_tiebreak() {
     config.model.memory = MemoryPolicy {
         memtotal: 0,
         memavailable: 100,
+        ..Default::default()
     };
     config.system.sortstrategy = SortStrategy::Inode;
 
@@ -145,9 +183,18 @@ fn planner_sorts_by_inode_with_score_tiebreak() {
         cached: 0,
         pagein: 0,
         pageout: 0,
+        memory_pressure: None,
+        io_pressure: None,
+        cgroup: None,
     };
 
-    let plan = planner.plan(&prediction, &stores, &mem);
+    let plan = planner.plan(
+        &prediction,
+        &stores,
+        &mem,
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
 
     let expected = if inode_a <= inode_b {
         vec![map_a, map_b]
@@ -156,3 +203,340 @@ fn planner_sorts_by_inode_with_score_tiebreak() {
     };
     assert_eq!(plan.maps, expected);
 }
+
+#[test]
+fn planner_prioritizes_soonest_eta_when_enabled() {
+    let mut config = Config::default();
+    config.model.memory = MemoryPolicy {
+        memtotal: 0,
+        memavailable: 100,
+        ..Default::default()
+    };
+    config.system.sortstrategy = SortStrategy::None;
+
+    let mut stores = Stores::default();
+    let later = stores.ensure_map(MapSegment::new("/later", 0, 2048, 0));
+    let soon = stores.ensure_map(MapSegment::new("/soon", 0, 2048, 0));
+    let sooner = stores.ensure_map(MapSegment::new("/sooner", 0, 2048, 0));
+    let unknown = stores.ensure_map(MapSegment::new("/unknown", 0, 2048, 0));
+
+    let mut prediction = Prediction::default();
+    prediction.map_scores.insert(later, f16::from_f32(0.9));
+    prediction.map_scores.insert(soon, f16::from_f32(0.6));
+    prediction.map_scores.insert(sooner, f16::from_f32(0.5));
+    prediction.map_scores.insert(unknown, f16::from_f32(0.95));
+    prediction.map_eta.insert(later, 50.0);
+    prediction.map_eta.insert(soon, 5.0);
+    prediction.map_eta.insert(sooner, 2.0);
+
+    let mem = MemStat {
+        total: 0,
+        available: 8,
+        free: 8,
+        cached: 0,
+        pagein: 0,
+        pageout: 0,
+        memory_pressure: None,
+        io_pressure: None,
+        cgroup: None,
+    };
+
+    let plan = GreedyPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &mem,
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(plan.maps, vec![unknown, later, soon, sooner]);
+
+    // Same cycle ranks by score; no ETA ranks at the end of the horizon.
+    config.system.prioritize_by_eta = true;
+    let plan = GreedyPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &mem,
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(plan.maps, vec![soon, sooner, unknown, later]);
+}
+
+fn kb_mem(available: u64) -> MemStat {
+    MemStat {
+        total: 0,
+        available,
+        free: available,
+        cached: 0,
+        pagein: 0,
+        pageout: 0,
+        memory_pressure: None,
+        io_pressure: None,
+        cgroup: None,
+    }
+}
+
+#[test]
+fn density_planner_prefers_many_small_maps_over_one_large() {
+    let mut config = Config::default();
+    config.model.memory = MemoryPolicy {
+        memtotal: 0,
+        memavailable: 100,
+        ..Default::default()
+    };
+    config.system.sortstrategy = SortStrategy::None;
+
+    let mut stores = Stores::default();
+    let mut prediction = Prediction::default();
+    let big = stores.ensure_map(MapSegment::new("/big", 0, 8192, 0));
+    prediction.map_scores.insert(big, f16::from_f32(0.9));
+    let small: Vec<_> = (0..4)
+        .map(|i| {
+            let id = stores.ensure_map(MapSegment::new(format!("/small{i}"), 0, 2048, 0));
+            prediction.map_scores.insert(id, f16::from_f32(0.5));
+            id
+        })
+        .collect();
+
+    let greedy = GreedyPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &kb_mem(8),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(greedy.maps, vec![big]);
+
+    let density = DensityPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &kb_mem(8),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(density.maps.len(), 4);
+    assert!(small.iter().all(|id| density.maps.contains(id)));
+    assert_eq!(density.total_bytes, 4 * 2048);
+    assert_eq!(density.budget_bytes, 8 * 1024);
+}
+
+#[test]
+fn density_planner_knapsack_pass_beats_greedy_fill() {
+    let mut config = Config::default();
+    config.model.memory = MemoryPolicy {
+        memtotal: 0,
+        memavailable: 100,
+        ..Default::default()
+    };
+    config.system.sortstrategy = SortStrategy::None;
+
+    let mut stores = Stores::default();
+    let mut prediction = Prediction::default();
+    let a = stores.ensure_map(MapSegment::new("/a", 0, 6 * 1024, 0));
+    let b = stores.ensure_map(MapSegment::new("/b", 0, 5 * 1024, 0));
+    let c = stores.ensure_map(MapSegment::new("/c", 0, 5 * 1024, 0));
+    prediction.map_scores.insert(a, f16::from_f32(0.66));
+    prediction.map_scores.insert(b, f16::from_f32(0.5));
+    prediction.map_scores.insert(c, f16::from_f32(0.5));
+
+    let plan = DensityPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &kb_mem(10),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(plan.maps, vec![a]);
+
+    config.system.knapsack_candidates = 8;
+    let plan = DensityPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &kb_mem(10),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(plan.maps.len(), 2);
+    assert!(plan.maps.contains(&b) && plan.maps.contains(&c));
+    assert_eq!(plan.total_bytes, 10 * 1024);
+}
+
+#[test]
+fn planner_charges_only_uncached_bytes() {
+    let dir = tempdir().unwrap();
+    let cached = dir.path().join("cached.bin");
+    // Freshly written data sits in the page cache.
+    std::fs::write(&cached, vec![7u8; 8192]).unwrap();
+    let missing = dir.path().join("missing.bin");
+
+    let mut config = Config::default();
+    config.model.memory = MemoryPolicy {
+        memtotal: 0,
+        memavailable: 100,
+        ..Default::default()
+    };
+    config.system.sortstrategy = SortStrategy::None;
+
+    let mut stores = Stores::default();
+    let map_cached = stores.ensure_map(MapSegment::new(&cached, 0, 8192, 0));
+    let map_missing = stores.ensure_map(MapSegment::new(&missing, 0, 4096, 0));
+    let mut prediction = Prediction::default();
+    prediction.map_scores.insert(map_cached, f16::from_f32(0.9));
+    prediction
+        .map_scores
+        .insert(map_missing, f16::from_f32(0.5));
+
+    // Planning alone never probes, so unprobed maps are charged in full.
+    let planner = GreedyPrefetchPlanner::new(&config);
+    let plan = planner.plan(
+        &prediction,
+        &stores,
+        &kb_mem(4),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(plan.maps, vec![map_missing]);
+
+    let plan = probe_and_plan(
+        &planner,
+        &prediction,
+        &stores,
+        &kb_mem(4),
+        &Cooldown::default(),
+    );
+    assert_eq!(plan.maps, vec![map_cached, map_missing]);
+    assert_eq!(plan.total_bytes, 4096);
+
+    config.system.residency_aware = false;
+    let plan = GreedyPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &kb_mem(4),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(plan.maps, vec![map_missing]);
+    assert_eq!(plan.total_bytes, 4096);
+}
+
+#[test]
+fn planner_skips_recently_prefetched_maps_unless_evicted() {
+    let dir = tempdir().unwrap();
+    let cached = dir.path().join("cached.bin");
+    std::fs::write(&cached, vec![7u8; 8192]).unwrap();
+    // Cached when prefetched, then deleted so it looks evicted.
+    let gone = dir.path().join("gone.bin");
+    std::fs::write(&gone, vec![7u8; 4096]).unwrap();
+    // Never readable, so its prefetch fails.
+    let missing = dir.path().join("missing.bin");
+
+    let mut config = Config::default();
+    config.model.memory = MemoryPolicy {
+        memtotal: 0,
+        memavailable: 100,
+        ..Default::default()
+    };
+    config.system.sortstrategy = SortStrategy::None;
+
+    let mut stores = Stores::default();
+    let map_cached = stores.ensure_map(MapSegment::new(&cached, 0, 8192, 0));
+    let map_gone = stores.ensure_map(MapSegment::new(&gone, 0, 4096, 0));
+    let map_missing = stores.ensure_map(MapSegment::new(&missing, 0, 4096, 0));
+    let mut prediction = Prediction::default();
+    prediction.map_scores.insert(map_cached, f16::from_f32(0.9));
+    prediction.map_scores.insert(map_gone, f16::from_f32(0.7));
+    prediction
+        .map_scores
+        .insert(map_missing, f16::from_f32(0.5));
+    let prefetched = PrefetchReport {
+        prefetched: vec![
+            stores.maps.get(map_cached).unwrap().key(),
+            stores.maps.get(map_gone).unwrap().key(),
+        ],
+        ..Default::default()
+    };
+
+    let planner = GreedyPrefetchPlanner::new(&config);
+    let cooldown = Cooldown::default();
+    let first = probe_and_plan(&planner, &prediction, &stores, &kb_mem(16), &cooldown);
+    assert_eq!(first.maps, vec![map_cached, map_gone, map_missing]);
+    assert_eq!((first.cooldown_skipped, first.evicted), (0, 0));
+
+    // Only maps actually prefetched cool down, keyed to their residency
+    // right after the prefetch.
+    planner.record_prefetch(&prefetched, &cooldown).unwrap()();
+    std::fs::remove_file(&gone).unwrap();
+    let second = probe_and_plan(&planner, &prediction, &stores, &kb_mem(16), &cooldown);
+    assert_eq!(second.maps, vec![map_gone, map_missing]);
+    assert_eq!((second.cooldown_skipped, second.evicted), (1, 1));
+
+    config.system.prefetch_cooldown = std::time::Duration::ZERO;
+    let planner = GreedyPrefetchPlanner::new(&config);
+    assert!(planner.record_prefetch(&prefetched, &cooldown).is_none());
+    let again = planner.plan(
+        &prediction,
+        &stores,
+        &kb_mem(16),
+        PressureScale::default(),
+        &cooldown,
+    );
+    assert_eq!(again.maps, vec![map_cached, map_gone, map_missing]);
+    assert_eq!(again.cooldown_skipped, 0);
+}
+
+#[test]
+fn planners_charge_overlapping_segments_once() {
+    let mut config = Config::default();
+    config.model.memory = MemoryPolicy {
+        memtotal: 0,
+        memavailable: 100,
+        ..Default::default()
+    };
+    config.system.sortstrategy = SortStrategy::None;
+    config.system.residency_aware = false;
+    config.system.knapsack_candidates = 2;
+
+    let mut stores = Stores::default();
+    let mut prediction = Prediction::default();
+    let segments = [
+        ("/lib/libfoo.so", 0, 12288, 7, 0.9),
+        ("/lib/libfoo.so", 0, 8192, 7, 0.8),
+        ("/lib/libfoo.so.1", 4096, 4096, 7, 0.7),
+        ("/lib/libbar.so", 0, 4096, 9, 0.6),
+    ];
+    let ids: Vec<_> = segments
+        .iter()
+        .map(|(path, offset, length, inode, score)| {
+            let id = stores.ensure_map(segment_with_meta(*path, *offset, *length, 1, *inode));
+            prediction.map_scores.insert(id, f16::from_f32(*score));
+            id
+        })
+        .collect();
+
+    let greedy = GreedyPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &kb_mem(12),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    assert_eq!(greedy.maps, ids[..3].to_vec());
+    assert_eq!(greedy.total_bytes, 12288);
+    assert_eq!(greedy.charged, vec![12288, 0, 0]);
+    let preview = PlanPreview::new(&greedy, &prediction, &stores);
+    let cumulative: Vec<_> = preview.entries.iter().map(|e| e.cumulative_bytes).collect();
+    assert_eq!(cumulative, vec![12288; 3]);
+
+    let density = DensityPrefetchPlanner::new(&config).plan(
+        &prediction,
+        &stores,
+        &kb_mem(12),
+        PressureScale::default(),
+        &Cooldown::default(),
+    );
+    // libfoo.so.1 and libbar.so are densest; the 8 KB segment then only
+    // costs the 4 KB libfoo.so.1 does not already cover.
+    assert_eq!(density.maps.len(), 3);
+    assert!(!density.maps.contains(&ids[0]));
+    assert_eq!(density.total_bytes, 12288);
+}
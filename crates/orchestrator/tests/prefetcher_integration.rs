@@ -1,7 +1,11 @@
 #![forbid(unsafe_code)]
 
-use orchestrator::{MapSegment, PosixFadvisePrefetcher, PrefetchPlan, Prefetcher, Stores};
+use orchestrator::{
+    MapSegment, PosixFadvisePrefetcher, PrefetchPlan, Prefetcher, Stores, ThrottleSettings,
+};
+use std::time::Duration;
 use tempfile::tempdir;
+use tokio_util::sync::CancellationToken;
 
 #[tokio::test]
 async fn prefetcher_reports_failures_for_missing_file() {
@@ -17,12 +21,110 @@ async fn prefetcher_reports_failures_for_missing_file() {
         maps: vec![map_id],
         total_bytes: 4096,
         budget_bytes: 4096,
+        ..Default::default()
     };
 
     let prefetcher = PosixFadvisePrefetcher::new(1);
-    let report = prefetcher.execute(&plan, &stores).await;
+    let report = prefetcher
+        .execute(&plan, &stores, &CancellationToken::new())
+        .await;
 
     assert_eq!(report.num_maps, 0);
     assert_eq!(report.total_bytes, 4096);
     assert!(report.failures.contains(&map_key));
 }
+
+#[tokio::test]
+async fn prefetcher_skips_cached_pages() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("cached.bin");
+    std::fs::write(&path, vec![1u8; 8192]).unwrap();
+
+    let mut stores = Stores::default();
+    let map_id = stores.ensure_map(MapSegment::new(path, 0, 8192, 0));
+
+    let plan = PrefetchPlan {
+        maps: vec![map_id],
+        total_bytes: 0,
+        budget_bytes: 8192,
+        ..Default::default()
+    };
+
+    let prefetcher = PosixFadvisePrefetcher::new(1);
+    let report = prefetcher
+        .execute(&plan, &stores, &CancellationToken::new())
+        .await;
+
+    assert_eq!(report.num_maps, 1);
+    assert_eq!(report.total_bytes, 0);
+    assert_eq!(report.read_bytes, 0);
+    assert!(report.failures.is_empty());
+}
+
+#[tokio::test]
+async fn prefetcher_paces_dispatch_to_rate_ceiling() {
+    let dir = tempdir().unwrap();
+    let mut stores = Stores::default();
+    let mut maps = Vec::new();
+    for name in ["first.bin", "second.bin"] {
+        let path = dir.path().join(name);
+        // A sparse file has no pages in the page cache until it is read.
+        std::fs::File::create(&path)
+            .unwrap()
+            .set_len(64 * 1024)
+            .unwrap();
+        maps.push(stores.ensure_map(MapSegment::new(path, 0, 64 * 1024, 0)));
+    }
+
+    let plan = PrefetchPlan {
+        maps,
+        total_bytes: 128 * 1024,
+        budget_bytes: 128 * 1024,
+        ..Default::default()
+    };
+
+    let throttle = ThrottleSettings::new(&config::Throttle {
+        max_bytes_per_sec: 256 * 1024,
+        io_pressure: 0.0,
+        foreground_bytes_per_sec: 0,
+        ..Default::default()
+    });
+    let prefetcher = PosixFadvisePrefetcher::new(2).with_throttle(throttle);
+    let report = prefetcher
+        .execute(&plan, &stores, &CancellationToken::new())
+        .await;
+
+    assert_eq!(report.num_maps, 2);
+    assert!(report.failures.is_empty());
+    // The second 64 KiB chunk waits a quarter second behind the first.
+    assert!(report.throttled >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn cancelled_prefetch_reports_skipped_maps() {
+    let dir = tempdir().unwrap();
+    let mut stores = Stores::default();
+    let mut maps = Vec::new();
+    for name in ["first.bin", "second.bin", "third.bin"] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, vec![1u8; 4096]).unwrap();
+        maps.push(stores.ensure_map(MapSegment::new(path, 0, 4096, 0)));
+    }
+
+    let plan = PrefetchPlan {
+        maps,
+        total_bytes: 3 * 4096,
+        budget_bytes: 3 * 4096,
+        ..Default::default()
+    };
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let prefetcher = PosixFadvisePrefetcher::new(1).with_chunk_size(1);
+    let report = prefetcher.execute(&plan, &stores, &cancel).await;
+
+    assert!(report.cancelled);
+    assert_eq!(report.num_maps, 0);
+    assert_eq!(report.skipped, 3);
+    assert!(report.failures.is_empty());
+}
@@ -7,6 +7,7 @@ use orchestrator::persistence::NoopRepository;
 use orchestrator::prediction::MarkovPredictor;
 use orchestrator::prefetch::{GreedyPrefetchPlanner, NoopPrefetcher};
 use orchestrator::{PreloadEngine, Services};
+use std::os::unix::fs::MetadataExt;
 
 #[cfg(target_os = "linux")]
 #[tokio::test]
@@ -40,4 +41,11 @@ async fn procfs_scanner_observes_current_exe() {
 
     let has_map = stores.maps.iter().any(|(_, map)| *map.path == *exe_path);
     assert!(has_map, "expected at least one map for current exe");
+
+    // Devices match `stat`, as fanotify records them, so segments of one
+    // file from either source coalesce.
+    let meta = std::fs::metadata(&exe_path).unwrap();
+    for (_, map) in stores.maps.iter().filter(|(_, map)| *map.path == *exe_path) {
+        assert_eq!((map.device, map.inode), (meta.dev(), meta.ino()));
+    }
 }
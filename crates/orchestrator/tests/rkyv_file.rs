@@ -0,0 +1,223 @@
+#![forbid(unsafe_code)]
+
+use orchestrator::StateRepository;
+use orchestrator::domain::MapKey;
+use orchestrator::persistence::{
+    ActiveRecord, ExeMapRecord, ExeRecord, LaunchHistogramRecord, LaunchSequenceRecord, MapRecord,
+    MarkovRecord, Recovery, RkyvFileRepository, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta,
+    StateSnapshot, StoresSnapshot,
+};
+use orchestrator::stores::HOURS_PER_WEEK;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+
+fn snapshot(exe: &str) -> StoresSnapshot {
+    let mut weights = [0.0f32; HOURS_PER_WEEK];
+    weights[9] = 2.5;
+    StoresSnapshot {
+        meta: SnapshotMeta {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            app_version: Some("test".into()),
+            created_at: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        },
+        state: StateSnapshot {
+            model_time: 10,
+            last_accounting_time: 5,
+            exes: vec![ExeRecord {
+                path: PathBuf::from(exe),
+                total_running_time: 42,
+                last_seen_time: Some(9),
+            }],
+            maps: vec![MapRecord {
+                path: PathBuf::from("/usr/lib/libfoo.so"),
+                offset: 0,
+                length: 4096,
+                update_time: 10,
+            }],
+            exe_maps: vec![ExeMapRecord {
+                exe_path: PathBuf::from(exe),
+                map_key: MapKey::new(PathBuf::from("/usr/lib/libfoo.so"), 0, 4096),
+                prob: 0.5,
+            }],
+            markov_edges: vec![MarkovRecord {
+                exe_a: PathBuf::from(exe),
+                exe_b: PathBuf::from("/usr/bin/other"),
+                time_to_leave: [1.0, 2.0, 3.0, 4.0],
+                transition_prob: [[0.25; 4]; 4],
+                both_running_time: 7,
+                state: 0,
+                last_change_time: 0,
+                state_last_left: [0; 4],
+            }],
+            launch_histograms: vec![LaunchHistogramRecord {
+                exe_path: PathBuf::from(exe),
+                weights,
+                updated_at: 1_700_000_000,
+            }],
+            launch_sequences: vec![LaunchSequenceRecord {
+                context: vec![PathBuf::from(exe)],
+                followers: vec![(PathBuf::from("/usr/bin/other"), 1.5)],
+                updated_at: 8,
+            }],
+            active_exes: vec![ActiveRecord {
+                exe_path: PathBuf::from(exe),
+                last_active: 9,
+            }],
+            cold_markov_edges: vec![MarkovRecord {
+                exe_a: PathBuf::from(exe),
+                exe_b: PathBuf::from("/usr/bin/dormant"),
+                time_to_leave: [4.0, 3.0, 2.0, 1.0],
+                transition_prob: [[0.5; 4]; 4],
+                both_running_time: 3,
+                state: 0,
+                last_change_time: 0,
+                state_last_left: [0; 4],
+            }],
+        },
+    }
+}
+
+#[tokio::test]
+async fn rkyv_roundtrip_snapshot() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("state.rkyv");
+
+    let repo = RkyvFileRepository::new(path.clone()).await.unwrap();
+    assert!(repo.load().await.unwrap().state.exes.is_empty());
+    let saved = snapshot("/usr/bin/app");
+    repo.save(&saved).await.unwrap();
+    assert!(!dir.path().join("state.rkyv.tmp").exists());
+
+    let repo = RkyvFileRepository::new(path).await.unwrap();
+    assert_eq!(repo.recovery(), &Recovery::Clean);
+    let loaded = repo.load().await.unwrap();
+
+    assert_eq!(loaded.meta.app_version.as_deref(), Some("test"));
+    assert_eq!(loaded.meta.created_at, saved.meta.created_at);
+    let state = &loaded.state;
+    assert_eq!((state.model_time, state.last_accounting_time), (10, 5));
+    assert_eq!(state.exes[0].path, PathBuf::from("/usr/bin/app"));
+    assert_eq!(state.exes[0].last_seen_time, Some(9));
+    assert_eq!(state.maps[0].length, 4096);
+    assert_eq!(state.exe_maps[0].map_key, saved.state.exe_maps[0].map_key);
+    assert_eq!(state.exe_maps[0].prob, 0.5);
+    assert_eq!(state.markov_edges[0].time_to_leave, [1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(state.markov_edges[0].transition_prob, [[0.25; 4]; 4]);
+    assert_eq!(state.markov_edges[0].both_running_time, 7);
+    assert_eq!(
+        state.launch_histograms[0].weights,
+        saved.state.launch_histograms[0].weights
+    );
+    assert_eq!(
+        state.launch_sequences[0].followers,
+        vec![(PathBuf::from("/usr/bin/other"), 1.5)]
+    );
+    assert_eq!(state.active_exes[0].exe_path, PathBuf::from("/usr/bin/app"));
+    assert_eq!(state.active_exes[0].last_active, 9);
+    assert_eq!(
+        state.cold_markov_edges[0].exe_b,
+        PathBuf::from("/usr/bin/dormant")
+    );
+    assert_eq!(
+        state.cold_markov_edges[0].time_to_leave,
+        [4.0, 3.0, 2.0, 1.0]
+    );
+    assert_eq!(state.cold_markov_edges[0].both_running_time, 3);
+}
+
+#[tokio::test]
+async fn corrupt_archive_restores_newest_backup() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("state.rkyv");
+
+    let repo = RkyvFileRepository::new(path.clone())
+        .await
+        .unwrap()
+        .with_backups(2);
+    repo.save(&snapshot("/usr/bin/old")).await.unwrap();
+    repo.save(&snapshot("/usr/bin/mid")).await.unwrap();
+    // Backups are refreshed at most once a day.
+    assert!(!dir.path().join("state.rkyv.bak.2").exists());
+
+    let newest = std::fs::File::options()
+        .write(true)
+        .open(dir.path().join("state.rkyv.bak.1"))
+        .unwrap();
+    newest
+        .set_modified(SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60))
+        .unwrap();
+    repo.save(&snapshot("/usr/bin/new")).await.unwrap();
+    assert!(dir.path().join("state.rkyv.bak.2").exists());
+
+    // Flip one payload byte: the header checksum no longer matches.
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(&path, &bytes).unwrap();
+
+    let repo = RkyvFileRepository::new(path).await.unwrap();
+    assert_eq!(
+        repo.recovery(),
+        &Recovery::RestoredBackup {
+            backup: dir.path().join("state.rkyv.bak.1"),
+            quarantined: dir.path().join("state.rkyv.corrupt"),
+        }
+    );
+    let loaded = repo.load().await.unwrap();
+    assert_eq!(loaded.state.exes[0].path, PathBuf::from("/usr/bin/new"));
+}
+
+#[tokio::test]
+async fn unreadable_archive_is_not_quarantined() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("state.rkyv");
+    std::fs::create_dir(&path).unwrap();
+
+    assert!(RkyvFileRepository::new(path.clone()).await.is_err());
+    assert!(path.is_dir());
+    assert!(!dir.path().join("state.rkyv.corrupt").exists());
+}
+
+#[tokio::test]
+async fn archive_from_newer_schema_is_quarantined() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("state.rkyv");
+
+    let repo = RkyvFileRepository::new(path.clone()).await.unwrap();
+    let mut newer = snapshot("/usr/bin/app");
+    newer.meta.schema_version = SNAPSHOT_SCHEMA_VERSION + 1;
+    repo.save(&newer).await.unwrap();
+
+    let repo = RkyvFileRepository::new(path).await.unwrap();
+    assert_eq!(
+        repo.recovery(),
+        &Recovery::Reset {
+            quarantined: dir.path().join("state.rkyv.newer"),
+        }
+    );
+    assert!(repo.load().await.unwrap().state.exes.is_empty());
+}
+
+#[tokio::test]
+async fn read_only_archive_loads_but_never_recovers_or_saves() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("state.rkyv");
+    assert!(RkyvFileRepository::open_read_only(&path).await.is_err());
+
+    let repo = RkyvFileRepository::new(path.clone()).await.unwrap();
+    repo.save(&snapshot("/usr/bin/app")).await.unwrap();
+
+    let repo = RkyvFileRepository::open_read_only(&path).await.unwrap();
+    let loaded = repo.load().await.unwrap();
+    assert_eq!(loaded.state.exes[0].path, PathBuf::from("/usr/bin/app"));
+    assert!(repo.save(&snapshot("/usr/bin/other")).await.is_err());
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(RkyvFileRepository::open_read_only(&path).await.is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    assert!(!dir.path().join("state.rkyv.corrupt").exists());
+}
@@ -3,10 +3,13 @@
 use orchestrator::StateRepository;
 use orchestrator::domain::MapKey;
 use orchestrator::persistence::{
-    ExeMapRecord, ExeRecord, MapRecord, MarkovRecord, SNAPSHOT_SCHEMA_VERSION, SnapshotMeta,
-    SqliteRepository, StateSnapshot, StoresSnapshot,
+    ActiveRecord, AppUsage, DayUsage, DeltaTracker, ExeMapRecord, ExeRecord, LaunchHistogramRecord,
+    LaunchSequenceRecord, LaunchUsage, MapRecord, MarkovRecord, Recovery, SNAPSHOT_SCHEMA_VERSION,
+    SnapshotMeta, SqliteRepository, StateSnapshot, StoresSnapshot, UsageRecord,
 };
+use orchestrator::stores::HOURS_PER_WEEK;
 use std::path::PathBuf;
+use std::time::Duration;
 use tempfile::tempdir;
 
 #[tokio::test]
@@ -14,6 +17,9 @@ async fn sqlite_roundtrip_snapshot() {
     let dir = tempdir().unwrap();
     let db_path = dir.path().join("state.db");
 
+    let mut histogram_weights = [0.0f32; HOURS_PER_WEEK];
+    histogram_weights[9] = 2.5;
+
     let snapshot = StoresSnapshot {
         meta: SnapshotMeta {
             schema_version: SNAPSHOT_SCHEMA_VERSION,
@@ -45,6 +51,36 @@ async fn sqlite_roundtrip_snapshot() {
                 time_to_leave: [0.0; 4],
                 transition_prob: [[0.0; 4]; 4],
                 both_running_time: 0,
+                state: 0,
+                last_change_time: 0,
+                state_last_left: [0; 4],
+            }],
+            launch_histograms: vec![LaunchHistogramRecord {
+                exe_path: PathBuf::from("/usr/bin/app"),
+                weights: histogram_weights,
+                updated_at: 1_700_000_000,
+            }],
+            launch_sequences: vec![LaunchSequenceRecord {
+                context: vec![
+                    PathBuf::from("/usr/bin/app"),
+                    PathBuf::from("/usr/bin/app2"),
+                ],
+                followers: vec![(PathBuf::from("/usr/bin/app3"), 1.5)],
+                updated_at: 8,
+            }],
+            active_exes: vec![ActiveRecord {
+                exe_path: PathBuf::from("/usr/bin/app"),
+                last_active: 9,
+            }],
+            cold_markov_edges: vec![MarkovRecord {
+                exe_a: PathBuf::from("/usr/bin/app"),
+                exe_b: PathBuf::from("/usr/bin/app3"),
+                time_to_leave: [1.0, 2.0, 3.0, 4.0],
+                transition_prob: [[0.25; 4]; 4],
+                both_running_time: 6,
+                state: 0,
+                last_change_time: 0,
+                state_last_left: [0; 4],
             }],
         },
     };
@@ -57,5 +93,293 @@ async fn sqlite_roundtrip_snapshot() {
     assert_eq!(loaded.state.maps.len(), 1);
     assert_eq!(loaded.state.exe_maps.len(), 1);
     assert_eq!(loaded.state.markov_edges.len(), 1);
+    assert_eq!(loaded.state.launch_histograms.len(), 1);
+    assert_eq!(loaded.state.launch_histograms[0].weights, histogram_weights);
+    assert_eq!(loaded.state.launch_sequences.len(), 1);
+    assert_eq!(loaded.state.launch_sequences[0].context.len(), 2);
+    assert_eq!(
+        loaded.state.launch_sequences[0].followers,
+        vec![(PathBuf::from("/usr/bin/app3"), 1.5)]
+    );
+    assert_eq!(loaded.state.active_exes.len(), 1);
+    assert_eq!(loaded.state.active_exes[0].last_active, 9);
+    assert_eq!(loaded.state.cold_markov_edges.len(), 1);
+    assert_eq!(
+        loaded.state.cold_markov_edges[0].time_to_leave,
+        [1.0, 2.0, 3.0, 4.0]
+    );
+    assert_eq!(loaded.state.cold_markov_edges[0].both_running_time, 6);
     assert_eq!(loaded.state.model_time, 10);
 }
+
+fn exe(path: &str, total_running_time: u64) -> ExeRecord {
+    ExeRecord {
+        path: PathBuf::from(path),
+        total_running_time,
+        last_seen_time: None,
+    }
+}
+
+#[tokio::test]
+async fn sqlite_delta_save_matches_full_snapshot() {
+    let dir = tempdir().unwrap();
+    let repo = SqliteRepository::new(dir.path().join("state.db"))
+        .await
+        .unwrap();
+
+    let mut snapshot = StoresSnapshot {
+        meta: SnapshotMeta::default(),
+        state: StateSnapshot {
+            model_time: 10,
+            last_accounting_time: 5,
+            exes: vec![
+                exe("/usr/bin/a", 1),
+                exe("/usr/bin/b", 2),
+                exe("/usr/bin/c", 3),
+            ],
+            launch_sequences: vec![LaunchSequenceRecord {
+                context: vec![PathBuf::from("/usr/bin/a")],
+                followers: vec![(PathBuf::from("/usr/bin/b"), 1.0)],
+                updated_at: 8,
+            }],
+            ..StateSnapshot::default()
+        },
+    };
+    repo.save(&snapshot).await.unwrap();
+    let tracker = DeltaTracker::new(&snapshot);
+
+    snapshot.state.model_time = 20;
+    snapshot.state.exes = vec![
+        exe("/usr/bin/a", 1),
+        exe("/usr/bin/b", 7),
+        exe("/usr/bin/d", 0),
+    ];
+    snapshot.state.maps.push(MapRecord {
+        path: PathBuf::from("/usr/lib/libfoo.so"),
+        offset: 0,
+        length: 4096,
+        update_time: 20,
+    });
+    snapshot.state.launch_sequences[0].followers = vec![(PathBuf::from("/usr/bin/d"), 0.5)];
+
+    let (delta, _) = tracker.diff(&snapshot);
+    assert_eq!(delta.exes.upserted.len(), 2);
+    assert_eq!(delta.exes.removed, vec![PathBuf::from("/usr/bin/c")]);
+    assert_eq!(delta.len(), 5);
+    repo.save_delta(&snapshot, &delta).await.unwrap();
+
+    let loaded = repo.load().await.unwrap();
+    let mut exes: Vec<_> = loaded
+        .state
+        .exes
+        .iter()
+        .map(|e| (e.path.to_str().unwrap().to_owned(), e.total_running_time))
+        .collect();
+    exes.sort();
+    assert_eq!(
+        exes,
+        vec![
+            ("/usr/bin/a".to_owned(), 1),
+            ("/usr/bin/b".to_owned(), 7),
+            ("/usr/bin/d".to_owned(), 0),
+        ]
+    );
+    assert_eq!(loaded.state.maps.len(), 1);
+    assert_eq!(loaded.state.launch_sequences.len(), 1);
+    assert_eq!(
+        loaded.state.launch_sequences[0].followers,
+        vec![(PathBuf::from("/usr/bin/d"), 0.5)]
+    );
+    assert_eq!(loaded.state.model_time, 20);
+}
+
+fn exes_snapshot(exes: Vec<ExeRecord>) -> StoresSnapshot {
+    StoresSnapshot {
+        meta: SnapshotMeta::default(),
+        state: StateSnapshot {
+            model_time: 10,
+            last_accounting_time: 0,
+            exes,
+            ..StateSnapshot::default()
+        },
+    }
+}
+
+#[tokio::test]
+async fn corrupt_database_restores_newest_backup() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("state.db");
+
+    let repo = SqliteRepository::new(db_path.clone())
+        .await
+        .unwrap()
+        .with_backups(2);
+    repo.save(&exes_snapshot(vec![exe("/usr/bin/old", 1)]))
+        .await
+        .unwrap();
+    repo.save(&exes_snapshot(vec![exe("/usr/bin/mid", 2)]))
+        .await
+        .unwrap();
+    // Backups are refreshed at most once a day.
+    assert!(!dir.path().join("state.db.bak.2").exists());
+
+    age(&dir.path().join("state.db.bak.1"));
+    repo.save(&exes_snapshot(vec![exe("/usr/bin/new", 3)]))
+        .await
+        .unwrap();
+    repo.close().await;
+    assert!(dir.path().join("state.db.bak.2").exists());
+
+    std::fs::write(&db_path, b"definitely not a database").unwrap();
+
+    let repo = SqliteRepository::new(db_path.clone()).await.unwrap();
+    assert_eq!(
+        repo.recovery(),
+        &Recovery::RestoredBackup {
+            backup: dir.path().join("state.db.bak.1"),
+            quarantined: dir.path().join("state.db.corrupt"),
+        }
+    );
+    let loaded = repo.load().await.unwrap();
+    assert_eq!(loaded.state.exes.len(), 1);
+    assert_eq!(loaded.state.exes[0].path, PathBuf::from("/usr/bin/new"));
+}
+
+/// Backdate `path` by two days.
+fn age(path: &std::path::Path) {
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    let two_days = Duration::from_secs(2 * 24 * 60 * 60);
+    file.set_modified(std::time::SystemTime::now() - two_days)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn corrupt_database_without_backups_starts_empty() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("state.db");
+    std::fs::write(&db_path, b"definitely not a database").unwrap();
+
+    let repo = SqliteRepository::new(db_path).await.unwrap();
+    assert_eq!(
+        repo.recovery(),
+        &Recovery::Reset {
+            quarantined: dir.path().join("state.db.corrupt"),
+        }
+    );
+    assert!(repo.load().await.unwrap().state.exes.is_empty());
+    assert_eq!(
+        std::fs::read(dir.path().join("state.db.corrupt")).unwrap(),
+        b"definitely not a database"
+    );
+}
+
+#[tokio::test]
+async fn unreadable_database_is_not_quarantined() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("state.db");
+    std::fs::create_dir(&db_path).unwrap();
+
+    assert!(SqliteRepository::new(db_path.clone()).await.is_err());
+    assert!(db_path.is_dir());
+    assert!(!dir.path().join("state.db.corrupt").exists());
+}
+
+#[tokio::test]
+async fn database_from_newer_schema_is_quarantined() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("state.db");
+
+    let repo = SqliteRepository::new(db_path.clone()).await.unwrap();
+    let mut snapshot = exes_snapshot(vec![exe("/usr/bin/app", 1)]);
+    snapshot.meta.schema_version = SNAPSHOT_SCHEMA_VERSION + 1;
+    repo.save(&snapshot).await.unwrap();
+    repo.close().await;
+
+    let repo = SqliteRepository::new(db_path).await.unwrap();
+    assert_eq!(
+        repo.recovery(),
+        &Recovery::Reset {
+            quarantined: dir.path().join("state.db.newer"),
+        }
+    );
+    assert!(repo.load().await.unwrap().state.exes.is_empty());
+}
+
+#[tokio::test]
+async fn usage_history_keeps_a_rolling_window() {
+    let dir = tempdir().unwrap();
+    let repo = SqliteRepository::new(dir.path().join("state.db"))
+        .await
+        .unwrap();
+    let day = 24 * 60 * 60;
+    let retention = Duration::from_secs(2 * day);
+    let launch = |path: &str, predicted| LaunchUsage {
+        exe_path: PathBuf::from(path),
+        predicted,
+    };
+
+    let record = |recorded_at, launches| UsageRecord {
+        recorded_at,
+        launches,
+        prefetched_bytes: 4096,
+        ..UsageRecord::default()
+    };
+    // Written in two batches, as successive saves would.
+    repo.record_usage(
+        &[
+            record(10 * day, vec![launch("/usr/bin/old", true)]),
+            record(11 * day, vec![launch("/usr/bin/app", false)]),
+            record(
+                11 * day + 60,
+                vec![launch("/usr/bin/app", true), launch("/usr/bin/other", true)],
+            ),
+        ],
+        retention,
+    )
+    .await
+    .unwrap();
+    repo.record_usage(
+        &[record(12 * day + 60, vec![launch("/usr/bin/app", true)])],
+        retention,
+    )
+    .await
+    .unwrap();
+
+    // The tick on day 10 fell out of the two-day window.
+    assert_eq!(
+        repo.usage_by_app(0, 10).await.unwrap(),
+        vec![
+            AppUsage {
+                exe_path: PathBuf::from("/usr/bin/app"),
+                launches: 3,
+                predicted: 2,
+            },
+            AppUsage {
+                exe_path: PathBuf::from("/usr/bin/other"),
+                launches: 1,
+                predicted: 1,
+            },
+        ]
+    );
+    assert_eq!(repo.usage_by_app(0, 1).await.unwrap().len(), 1);
+    assert_eq!(
+        repo.usage_by_day(0).await.unwrap(),
+        vec![
+            DayUsage {
+                day: 11 * day,
+                ticks: 2,
+                launches: 3,
+                predicted: 2,
+                prefetched_bytes: 8192,
+            },
+            DayUsage {
+                day: 12 * day,
+                ticks: 1,
+                launches: 1,
+                predicted: 1,
+                prefetched_bytes: 4096,
+            },
+        ]
+    );
+    assert_eq!(repo.usage_by_day(12 * day).await.unwrap().len(), 1);
+}